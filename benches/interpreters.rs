@@ -0,0 +1,41 @@
+//! Compares `interpret::interpret` with evaluating a `resolve::Resolved` tree.
+//! Run with `cargo bench --bench interpreters`.
+//!
+//! The two don't implement the same language: `interpret` scopes variables dynamically and
+//! `resolve` lexically. They agree on the benchmarked inputs, which never return a function.
+
+use lamb::{interpret, parse, read, resolve};
+use std::time::{Duration, Instant};
+
+const ITERATIONS: u32 = 10_000;
+
+fn time(iterations: u32, mut run: impl FnMut()) -> Duration {
+    let start = Instant::now();
+    for _ in 0..iterations {
+        run();
+    }
+    start.elapsed() / iterations
+}
+
+fn main() {
+    println!("interpret scopes variables dynamically, resolved lexically");
+    for name in ["factorial", "summation"] {
+        let code = read::build(&format!("tests/inputs/{}.txt", name));
+        let ast = parse(&code);
+        let resolved = resolve::resolve(&ast);
+
+        let interpreted = time(ITERATIONS, || {
+            interpret::interpret(&ast);
+        });
+        let evaluated = time(ITERATIONS, || {
+            resolve::evaluate(&resolved);
+        });
+        println!(
+            "{:<10} interpret: {:>10?}  resolved: {:>10?}  speedup: {:.1}x",
+            name,
+            interpreted,
+            evaluated,
+            interpreted.as_secs_f64() / evaluated.as_secs_f64()
+        );
+    }
+}
@@ -0,0 +1,544 @@
+use crate::{
+    parse::Constructor,
+    type_check::{
+        Type, TypedAST, TypedASTEnum, TypedConstruct, TypedDataDeclaration,
+        TypedFunctionApplication, TypedFunctionDefinition, TypedIf, TypedMatch, TypedMatchBranch,
+        TypedRecursiveBinding, TypedRecursiveFunction, TypedRecursiveFunctionGroup,
+        TypedTypeDeclaration,
+    },
+    Syntax,
+};
+use std::{
+    convert::TryInto,
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// The directory, relative to the working directory, in which `lamb` caches type checked
+/// programs.
+pub const DIRECTORY: &str = ".lamb-cache";
+
+const MAGIC: &[u8; 4] = b"LAMB";
+
+/// Incremented whenever the encoding of a `TypedAST` changes, so stale cache files are ignored.
+pub const VERSION: u32 = 3;
+
+/// The 64 bit FNV-1a hash of `source`.
+pub fn checksum(source: &str) -> u64 {
+    source.bytes().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+/// The byte that records which syntax a cached program was read in, since the same source can
+/// mean different programs in different syntaxes.
+fn syntax_tag(syntax: Syntax) -> u8 {
+    match syntax {
+        Syntax::Core => 0,
+        Syntax::Ml => 1,
+        Syntax::Sexp => 2,
+        Syntax::Json => 3,
+    }
+}
+
+/// Encodes `typed_ast`, which was type checked from `source` written in `syntax`, as `MAGIC`,
+/// `VERSION`, the tag of `syntax`, the checksum of `source` and then the tree in prefix order.
+/// Integers are little endian.
+pub fn encode(typed_ast: &TypedAST, source: &str, syntax: Syntax) -> Vec<u8> {
+    let mut encoder = Encoder { bytes: Vec::new() };
+    encoder.bytes.extend_from_slice(MAGIC);
+    encoder.bytes.extend_from_slice(&VERSION.to_le_bytes());
+    encoder.u8(syntax_tag(syntax));
+    encoder.u64(checksum(source));
+    encoder.typed_ast(typed_ast);
+    encoder.bytes
+}
+
+/// Decodes bytes written by `encode`, returning `None` if they are malformed, were written by
+/// another version or don't match `source` and `syntax`.
+pub fn decode(bytes: &[u8], source: &str, syntax: Syntax) -> Option<TypedAST> {
+    let mut decoder = Decoder { bytes, offset: 0 };
+    if decoder.take(MAGIC.len())? != MAGIC
+        || u32::from_le_bytes(decoder.take(4)?.try_into().ok()?) != VERSION
+        || decoder.u8()? != syntax_tag(syntax)
+        || decoder.u64()? != checksum(source)
+    {
+        return None;
+    }
+    let typed_ast = decoder.typed_ast()?;
+    if decoder.offset == bytes.len() {
+        Some(typed_ast)
+    } else {
+        None
+    }
+}
+
+/// The file in `directory` that caches the program in `file`.
+fn cache_file(directory: &Path, file: &str) -> PathBuf {
+    let path = fs::canonicalize(file).unwrap_or_else(|_| PathBuf::from(file));
+    directory.join(format!("{:016x}.lambc", checksum(&path.to_string_lossy())))
+}
+
+/// Returns the type checked program in `file`, whose contents are `source` written in `syntax`,
+/// if it was cached in `directory` from the same source and syntax.
+pub fn cached(directory: &Path, file: &str, source: &str, syntax: Syntax) -> Option<TypedAST> {
+    fs::read(cache_file(directory, file))
+        .ok()
+        .and_then(|bytes| decode(&bytes, source, syntax))
+}
+
+/// Caches `typed_ast`, the type checked program in `file` whose contents are `source` written in
+/// `syntax`, in `directory`.
+pub fn store(directory: &Path, file: &str, source: &str, syntax: Syntax, typed_ast: &TypedAST) {
+    // The cache is only an optimization, so failing to write it isn't an error.
+    let _ = fs::create_dir_all(directory).and_then(|_| {
+        fs::write(
+            cache_file(directory, file),
+            encode(typed_ast, source, syntax),
+        )
+    });
+}
+
+/// Returns the type checked program in `file`, whose contents are `source` written in `syntax`.
+/// It is read from `directory` if it was cached from the same source, and otherwise type checked
+/// and cached.
+pub fn load(directory: &Path, file: &str, source: &str, syntax: Syntax) -> TypedAST {
+    cached(directory, file, source, syntax).unwrap_or_else(|| {
+        let typed_ast = TypedAST::new(&crate::desugar(source, syntax));
+        store(directory, file, source, syntax, &typed_ast);
+        typed_ast
+    })
+}
+
+/// Deletes `directory` and everything cached in it.
+pub fn clean(directory: &Path) {
+    if directory.exists() {
+        fs::remove_dir_all(directory)
+            .unwrap_or_else(|error| panic!("Unable to remove {}: {}", directory.display(), error));
+    }
+}
+
+struct Encoder {
+    bytes: Vec<u8>,
+}
+
+impl Encoder {
+    fn u8(&mut self, byte: u8) {
+        self.bytes.push(byte);
+    }
+
+    fn u64(&mut self, number: u64) {
+        self.bytes.extend_from_slice(&number.to_le_bytes());
+    }
+
+    fn usize(&mut self, number: usize) {
+        self.u64(number as u64);
+    }
+
+    fn string(&mut self, string: &str) {
+        self.usize(string.len());
+        self.bytes.extend_from_slice(string.as_bytes());
+    }
+
+    fn strings(&mut self, strings: &[String]) {
+        self.usize(strings.len());
+        for string in strings {
+            self.string(string);
+        }
+    }
+
+    fn ty(&mut self, ty: &Type) {
+        match ty {
+            Type::Number => self.u8(0),
+            Type::Boolean => self.u8(1),
+            Type::Function { argument, ret } => {
+                self.u8(2);
+                self.ty(argument);
+                self.ty(ret);
+            }
+            Type::Data(name) => {
+                self.u8(3);
+                self.string(name);
+            }
+            Type::Reference(ty) => {
+                self.u8(4);
+                self.ty(ty);
+            }
+            Type::Named(name) => {
+                self.u8(5);
+                self.string(name);
+            }
+        }
+    }
+
+    fn types(&mut self, types: &[Type]) {
+        self.usize(types.len());
+        for ty in types {
+            self.ty(ty);
+        }
+    }
+
+    fn typed_asts(&mut self, typed_asts: &[TypedAST]) {
+        self.usize(typed_asts.len());
+        for typed_ast in typed_asts {
+            self.typed_ast(typed_ast);
+        }
+    }
+
+    fn typed_ast(&mut self, typed_ast: &TypedAST) {
+        self.ty(&typed_ast.ty);
+        match &*typed_ast.ast {
+            TypedASTEnum::NumberLiteral(number) => {
+                self.u8(0);
+                self.u64(*number as u64);
+            }
+            TypedASTEnum::Plus(lhs, rhs) => {
+                self.u8(1);
+                self.typed_ast(lhs);
+                self.typed_ast(rhs);
+            }
+            TypedASTEnum::Multiply(lhs, rhs) => {
+                self.u8(2);
+                self.typed_ast(lhs);
+                self.typed_ast(rhs);
+            }
+            TypedASTEnum::TrueLiteral => self.u8(3),
+            TypedASTEnum::FalseLiteral => self.u8(4),
+            TypedASTEnum::Equals(lhs, rhs) => {
+                self.u8(5);
+                self.typed_ast(lhs);
+                self.typed_ast(rhs);
+            }
+            TypedASTEnum::If(if_struct) => {
+                self.u8(6);
+                self.typed_ast(&if_struct.condition);
+                self.typed_ast(&if_struct.then);
+                self.typed_ast(&if_struct.els);
+            }
+            TypedASTEnum::Identifier(name) => {
+                self.u8(7);
+                self.string(name);
+            }
+            TypedASTEnum::FunctionApplication(function_application) => {
+                self.u8(8);
+                self.typed_ast(&function_application.function);
+                self.typed_ast(&function_application.argument);
+            }
+            TypedASTEnum::FunctionDefinition(function_definition) => {
+                self.u8(9);
+                self.string(&function_definition.argument_name);
+                self.ty(&function_definition.argument_type);
+                self.ty(&function_definition.return_type);
+                self.typed_ast(&function_definition.body);
+            }
+            TypedASTEnum::RecursiveFunction(recursive_function) => {
+                self.u8(10);
+                self.string(&recursive_function.function_name);
+                self.string(&recursive_function.argument_name);
+                self.ty(&recursive_function.argument_type);
+                self.ty(&recursive_function.return_type);
+                self.typed_ast(&recursive_function.body);
+                self.typed_ast(&recursive_function.function_use);
+            }
+            TypedASTEnum::RecursiveFunctionGroup(recursive_function_group) => {
+                self.u8(11);
+                self.usize(recursive_function_group.functions.len());
+                for function in &recursive_function_group.functions {
+                    self.string(&function.function_name);
+                    self.string(&function.argument_name);
+                    self.ty(&function.argument_type);
+                    self.ty(&function.return_type);
+                    self.typed_ast(&function.body);
+                }
+                self.typed_ast(&recursive_function_group.function_use);
+            }
+            TypedASTEnum::DataDeclaration(data_declaration) => {
+                self.u8(12);
+                self.string(&data_declaration.name);
+                self.usize(data_declaration.constructors.len());
+                for constructor in &data_declaration.constructors {
+                    self.string(&constructor.name);
+                    self.types(&constructor.fields);
+                }
+                self.typed_ast(&data_declaration.body);
+            }
+            TypedASTEnum::Construct(construct) => {
+                self.u8(13);
+                self.string(&construct.constructor);
+                self.usize(construct.tag);
+                self.typed_asts(&construct.arguments);
+            }
+            TypedASTEnum::Match(match_struct) => {
+                self.u8(14);
+                self.typed_ast(&match_struct.scrutinee);
+                self.usize(match_struct.branches.len());
+                for branch in &match_struct.branches {
+                    self.string(&branch.constructor);
+                    match branch.tag {
+                        Some(tag) => {
+                            self.u8(1);
+                            self.usize(tag);
+                        }
+                        None => self.u8(0),
+                    }
+                    self.strings(&branch.variables);
+                    self.types(&branch.field_types);
+                    self.typed_ast(&branch.body);
+                }
+            }
+            TypedASTEnum::Reference(ast) => {
+                self.u8(15);
+                self.typed_ast(ast);
+            }
+            TypedASTEnum::Dereference(ast) => {
+                self.u8(16);
+                self.typed_ast(ast);
+            }
+            TypedASTEnum::Set(reference, value) => {
+                self.u8(17);
+                self.typed_ast(reference);
+                self.typed_ast(value);
+            }
+            TypedASTEnum::PhysicalEquals(lhs, rhs) => {
+                self.u8(18);
+                self.typed_ast(lhs);
+                self.typed_ast(rhs);
+            }
+            TypedASTEnum::Fix(ast) => {
+                self.u8(19);
+                self.typed_ast(ast);
+            }
+            TypedASTEnum::TypeDeclaration(type_declaration) => {
+                self.u8(20);
+                self.string(&type_declaration.name);
+                self.ty(&type_declaration.ty);
+                self.typed_ast(&type_declaration.body);
+            }
+        }
+    }
+}
+
+/// Reads what `Encoder` writes. Every method returns `None` if the bytes are malformed.
+struct Decoder<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Decoder<'a> {
+    fn take(&mut self, count: usize) -> Option<&'a [u8]> {
+        let end = self.offset.checked_add(count)?;
+        let bytes = self.bytes.get(self.offset..end)?;
+        self.offset = end;
+        Some(bytes)
+    }
+
+    fn u8(&mut self) -> Option<u8> {
+        Some(self.take(1)?[0])
+    }
+
+    fn u64(&mut self) -> Option<u64> {
+        Some(u64::from_le_bytes(self.take(8)?.try_into().ok()?))
+    }
+
+    fn usize(&mut self) -> Option<usize> {
+        self.u64()?.try_into().ok()
+    }
+
+    fn string(&mut self) -> Option<String> {
+        let length = self.usize()?;
+        String::from_utf8(self.take(length)?.to_vec()).ok()
+    }
+
+    /// Decodes a length followed by that many elements with `element`.
+    fn sequence<T>(&mut self, element: impl Fn(&mut Self) -> Option<T>) -> Option<Vec<T>> {
+        let length = self.usize()?;
+        // The length isn't trusted to preallocate, since the bytes may be malformed.
+        let mut elements = Vec::new();
+        for _ in 0..length {
+            elements.push(element(self)?);
+        }
+        Some(elements)
+    }
+
+    fn ty(&mut self) -> Option<Type> {
+        Some(match self.u8()? {
+            0 => Type::Number,
+            1 => Type::Boolean,
+            2 => Type::Function {
+                argument: Box::new(self.ty()?),
+                ret: Box::new(self.ty()?),
+            },
+            3 => Type::Data(self.string()?),
+            4 => Type::Reference(Box::new(self.ty()?)),
+            5 => Type::Named(self.string()?),
+            _ => return None,
+        })
+    }
+
+    fn typed_ast(&mut self) -> Option<TypedAST> {
+        let ty = self.ty()?;
+        let ast = match self.u8()? {
+            0 => TypedASTEnum::NumberLiteral(self.u64()? as i64),
+            1 => TypedASTEnum::Plus(self.typed_ast()?, self.typed_ast()?),
+            2 => TypedASTEnum::Multiply(self.typed_ast()?, self.typed_ast()?),
+            3 => TypedASTEnum::TrueLiteral,
+            4 => TypedASTEnum::FalseLiteral,
+            5 => TypedASTEnum::Equals(self.typed_ast()?, self.typed_ast()?),
+            6 => TypedASTEnum::If(TypedIf {
+                condition: self.typed_ast()?,
+                then: self.typed_ast()?,
+                els: self.typed_ast()?,
+            }),
+            7 => TypedASTEnum::Identifier(self.string()?),
+            8 => TypedASTEnum::FunctionApplication(TypedFunctionApplication {
+                function: self.typed_ast()?,
+                argument: self.typed_ast()?,
+            }),
+            9 => TypedASTEnum::FunctionDefinition(TypedFunctionDefinition {
+                argument_name: self.string()?,
+                argument_type: self.ty()?,
+                return_type: self.ty()?,
+                body: self.typed_ast()?,
+            }),
+            10 => TypedASTEnum::RecursiveFunction(TypedRecursiveFunction {
+                function_name: self.string()?,
+                argument_name: self.string()?,
+                argument_type: self.ty()?,
+                return_type: self.ty()?,
+                body: self.typed_ast()?,
+                function_use: self.typed_ast()?,
+            }),
+            11 => TypedASTEnum::RecursiveFunctionGroup(TypedRecursiveFunctionGroup {
+                functions: self.sequence(|decoder| {
+                    Some(TypedRecursiveBinding {
+                        function_name: decoder.string()?,
+                        argument_name: decoder.string()?,
+                        argument_type: decoder.ty()?,
+                        return_type: decoder.ty()?,
+                        body: decoder.typed_ast()?,
+                    })
+                })?,
+                function_use: self.typed_ast()?,
+            }),
+            12 => TypedASTEnum::DataDeclaration(TypedDataDeclaration {
+                name: self.string()?,
+                constructors: self.sequence(|decoder| {
+                    Some(Constructor {
+                        name: decoder.string()?,
+                        fields: decoder.sequence(Self::ty)?,
+                    })
+                })?,
+                body: self.typed_ast()?,
+            }),
+            13 => TypedASTEnum::Construct(TypedConstruct {
+                constructor: self.string()?,
+                tag: self.usize()?,
+                arguments: self.sequence(Self::typed_ast)?,
+            }),
+            14 => TypedASTEnum::Match(TypedMatch {
+                scrutinee: self.typed_ast()?,
+                branches: self.sequence(|decoder| {
+                    Some(TypedMatchBranch {
+                        constructor: decoder.string()?,
+                        tag: match decoder.u8()? {
+                            0 => None,
+                            1 => Some(decoder.usize()?),
+                            _ => return None,
+                        },
+                        variables: decoder.sequence(Self::string)?,
+                        field_types: decoder.sequence(Self::ty)?,
+                        body: decoder.typed_ast()?,
+                    })
+                })?,
+            }),
+            15 => TypedASTEnum::Reference(self.typed_ast()?),
+            16 => TypedASTEnum::Dereference(self.typed_ast()?),
+            17 => TypedASTEnum::Set(self.typed_ast()?, self.typed_ast()?),
+            18 => TypedASTEnum::PhysicalEquals(self.typed_ast()?, self.typed_ast()?),
+            19 => TypedASTEnum::Fix(self.typed_ast()?),
+            20 => TypedASTEnum::TypeDeclaration(TypedTypeDeclaration {
+                name: self.string()?,
+                ty: self.ty()?,
+                body: self.typed_ast()?,
+            }),
+            _ => return None,
+        };
+        Some(TypedAST {
+            ty,
+            ast: Box::new(ast),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        parse::AST,
+        read::{
+            self,
+            tests::{SYNTAX_ERRORS, TYPE_ERRORS},
+        },
+        tokenize::TokenStream,
+    };
+
+    fn typed_ast(source: &str) -> TypedAST {
+        TypedAST::new(&AST::build(&mut TokenStream::build(source.chars())))
+    }
+
+    #[test]
+    fn cache_round_trip() {
+        for (name, source) in read::tests::inputs(&[&SYNTAX_ERRORS[..], &TYPE_ERRORS[..]].concat())
+        {
+            let typed_ast = typed_ast(&source);
+            let bytes = encode(&typed_ast, &source, Syntax::Core);
+            assert_eq!(
+                decode(&bytes, &source, Syntax::Core),
+                Some(typed_ast),
+                "{}",
+                name
+            );
+        }
+    }
+
+    #[test]
+    fn cache_rejects_stale() {
+        let source = read::build("tests/inputs/factorial.txt");
+        let mut bytes = encode(&typed_ast(&source), &source, Syntax::Core);
+        assert_eq!(decode(&bytes, "numC(1)", Syntax::Core), None);
+        assert_eq!(
+            decode(&bytes[..bytes.len() - 1], &source, Syntax::Core),
+            None
+        );
+
+        assert_eq!(decode(&bytes, &source, Syntax::Ml), None);
+
+        bytes[MAGIC.len()] += 1;
+        assert_eq!(decode(&bytes, &source, Syntax::Core), None);
+    }
+
+    #[test]
+    fn cache_load_and_clean() {
+        let directory =
+            std::env::temp_dir().join(format!("lamb-cache-test-{}", std::process::id()));
+        let file = "tests/inputs/factorial.txt";
+        let source = read::build(file);
+
+        let typed_ast = load(&directory, file, &source, Syntax::Core);
+        let cache_file = cache_file(&directory, file);
+        assert_eq!(
+            decode(&fs::read(&cache_file).unwrap(), &source, Syntax::Core).as_ref(),
+            Some(&typed_ast)
+        );
+        assert_eq!(load(&directory, file, &source, Syntax::Core), typed_ast);
+
+        // A changed source is type checked again and replaces the cache file.
+        let changed = source.replace("numC(5)", "numC(6)");
+        assert_ne!(changed, source);
+        load(&directory, file, &changed, Syntax::Core);
+        assert!(decode(&fs::read(&cache_file).unwrap(), &changed, Syntax::Core).is_some());
+
+        clean(&directory);
+        assert!(!directory.exists());
+    }
+}
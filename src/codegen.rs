@@ -1,16 +1,22 @@
 use crate::{
     type_check::Type,
-    type_check::{TypedAST, TypedASTEnum},
+    type_check::{TypedAST, TypedASTEnum, TypedMatch, TypedMatchBranch},
 };
 
 use inkwell::{
+    basic_block::BasicBlock,
     builder::Builder,
     context::Context,
+    memory_buffer::MemoryBuffer,
     module::Module,
+    passes::PassManager,
     support::LLVMString,
     types::{BasicType, BasicTypeEnum, FunctionType},
-    values::{BasicValueEnum, CallableValue, FunctionValue},
-    AddressSpace, IntPredicate,
+    values::{
+        BasicValue, BasicValueEnum, CallSiteValue, CallableValue, FunctionValue, IntValue,
+        PointerValue,
+    },
+    AddressSpace, IntPredicate, OptimizationLevel,
 };
 
 use std::{
@@ -26,22 +32,75 @@ pub struct CodeGen<'ctx> {
     context: &'ctx Context,
     builder: Builder<'ctx>,
     module: Module<'ctx>,
+    /// The name and definition of every `typeC` alias in scope, innermost last.
+    aliases: Vec<(String, Type)>,
+    /// The number of calls named `MUSTTAIL_CALL`.
+    musttail_calls: usize,
 }
 
+/// The name of the calls that `run_with` makes `musttail`.
+const MUSTTAIL_CALL: &str = "lamb_musttail_call";
+
 impl<'ctx> CodeGen<'ctx> {
     pub fn run(typed_ast: &TypedAST) -> Result<u64, LLVMString> {
+        CodeGen::run_with(typed_ast, OptimizationLevel::Default)
+    }
+
+    fn run_with(
+        typed_ast: &TypedAST,
+        optimization_level: OptimizationLevel,
+    ) -> Result<u64, LLVMString> {
         let context = Context::create();
         let mut codegen = CodeGen {
             context: &context,
             module: context.create_module("lamb_module"),
             builder: context.create_builder(),
+            aliases: Vec::new(),
+            musttail_calls: 0,
         };
 
-        let main = codegen.module(typed_ast);
+        codegen.module(typed_ast);
 
         codegen.module.verify()?;
 
-        let execution_engine = codegen.module.create_execution_engine()?;
+        // Only a `musttail` call is guaranteed not to grow the stack, but LLVM 13's C API can only
+        // mark calls as `tail`. So the calls named `MUSTTAIL_CALL` are marked `musttail` in the
+        // printed module, which is then parsed back. If the printer's layout changes, the count
+        // of marked calls stops matching instead of the calls silently growing the stack.
+        let prefix = format!("%{}", MUSTTAIL_CALL);
+        let mut marked = 0;
+        let ir: Vec<String> = codegen
+            .module
+            .print_to_string()
+            .to_string_lossy()
+            .lines()
+            .map(|line| {
+                if line.trim_start().starts_with(&prefix) && line.contains(" = tail call ") {
+                    marked += 1;
+                    line.replacen(" = tail call ", " = musttail call ", 1)
+                } else {
+                    line.to_string()
+                }
+            })
+            .collect();
+        assert_eq!(
+            marked, codegen.musttail_calls,
+            "Only {} of the {} tail calls were marked musttail",
+            marked, codegen.musttail_calls
+        );
+        let module = context.create_module_from_ir(MemoryBuffer::create_from_memory_range_copy(
+            ir.join("\n").as_bytes(),
+            "lamb_module",
+        ))?;
+        module.verify()?;
+
+        let pass_manager = PassManager::create(());
+        pass_manager.add_tail_call_elimination_pass();
+        pass_manager.add_cfg_simplification_pass();
+        pass_manager.run_on(&module);
+
+        let execution_engine = module.create_jit_execution_engine(optimization_level)?;
+        let main = module.get_function("lamb_main_function").unwrap();
 
         let run_result;
         unsafe {
@@ -51,7 +110,7 @@ impl<'ctx> CodeGen<'ctx> {
         Ok(run_result)
     }
 
-    fn module(&mut self, typed_ast: &TypedAST) -> FunctionValue<'ctx> {
+    fn module(&mut self, typed_ast: &TypedAST) {
         let main_return_type = match typed_ast.ty {
             Type::Number => self.context.i64_type(),
             Type::Boolean => self.context.bool_type(),
@@ -68,12 +127,9 @@ impl<'ctx> CodeGen<'ctx> {
             .append_basic_block(main_function, "lamb_main_entry");
         self.builder.position_at_end(main_basic_block);
 
-        let return_value = self.codegen(typed_ast).into_int_value();
-        self.builder.build_return(Some(&return_value));
+        self.codegen_return(typed_ast, &mut HashMap::new());
 
         main_function.verify(false);
-
-        main_function
     }
 
     fn codegen_helper(
@@ -113,6 +169,47 @@ impl<'ctx> CodeGen<'ctx> {
                     )
                     .into()
             }
+            TypedASTEnum::PhysicalEquals(op1, op2) => {
+                let i64_type = self.context.i64_type();
+                let lhs = self
+                    .codegen_helper(op1, argument_values)
+                    .into_pointer_value();
+                let rhs = self
+                    .codegen_helper(op2, argument_values)
+                    .into_pointer_value();
+                let lhs = self
+                    .builder
+                    .build_ptr_to_int(lhs, i64_type, "lamb_physical_equals_lhs");
+                let rhs = self
+                    .builder
+                    .build_ptr_to_int(rhs, i64_type, "lamb_physical_equals_rhs");
+                self.builder
+                    .build_int_compare(IntPredicate::EQ, lhs, rhs, "lamb_physical_equals")
+                    .into()
+            }
+            TypedASTEnum::Reference(ast) => {
+                let value = self.codegen_helper(ast, argument_values);
+                let reference = self
+                    .builder
+                    .build_malloc(self.llvm_basic_type(&ast.ty), "lamb_reference")
+                    .unwrap();
+                self.builder.build_store(reference, value);
+                reference.into()
+            }
+            TypedASTEnum::Dereference(ast) => {
+                let reference = self
+                    .codegen_helper(ast, argument_values)
+                    .into_pointer_value();
+                self.builder.build_load(reference, "lamb_dereference")
+            }
+            TypedASTEnum::Set(reference, value) => {
+                let reference = self
+                    .codegen_helper(reference, argument_values)
+                    .into_pointer_value();
+                let value = self.codegen_helper(value, argument_values);
+                self.builder.build_store(reference, value);
+                value
+            }
             TypedASTEnum::If(if_struct) => {
                 let condition = self.codegen_helper(&if_struct.condition, argument_values);
                 let then_block = self.context.insert_basic_block_after(
@@ -146,14 +243,14 @@ impl<'ctx> CodeGen<'ctx> {
 
                 self.builder.position_at_end(post_dominator_block);
 
-                let phi_value = match typed_ast.ty {
+                let phi_value = match self.unalias(&typed_ast.ty) {
                     Type::Boolean => self
                         .builder
                         .build_phi(self.context.bool_type(), "lamb_phi_bool"),
                     Type::Number => self
                         .builder
                         .build_phi(self.context.i64_type(), "lamb_phi_int"),
-                    Type::Function { .. } => self
+                    _ => self
                         .builder
                         .build_phi(self.llvm_basic_type(&typed_ast.ty), "lamb_hi_bool"),
                 };
@@ -170,121 +267,705 @@ impl<'ctx> CodeGen<'ctx> {
                 }
             },
             TypedASTEnum::FunctionApplication(function_application) => {
-                let function_pointer = self
+                let closure = self
                     .codegen_helper(&function_application.function, argument_values)
                     .into_pointer_value();
                 let argument = self.codegen_helper(&function_application.argument, argument_values);
-                self.builder
-                    .build_call(
-                        CallableValue::try_from(function_pointer).unwrap(),
-                        &[argument.into()],
-                        "lamb_function_call",
-                    )
+                let function_type = self.callee_type(&function_application.function.ty);
+                self.build_closure_call(closure, argument, function_type, "lamb_function_call")
                     .try_as_basic_value()
                     .unwrap_left()
             }
             TypedASTEnum::FunctionDefinition(function_definition) => {
-                let previous_basic_block = self.builder.get_insert_block().unwrap();
-
-                let function_type = self.function_prototype(
-                    &function_definition.argument_type,
+                let captured = captured_variables(
+                    &function_definition.body,
+                    &[&function_definition.argument_name],
+                );
+                let function_value = self.build_function(
+                    "lamb_function",
+                    None,
+                    (
+                        &function_definition.argument_name,
+                        &function_definition.argument_type,
+                    ),
                     &function_definition.return_type,
+                    &function_definition.body,
+                    &captured,
                 );
-                let function_value = self
-                    .module
-                    .add_function("lamb_function", function_type, None);
+                let closure = self.build_closure(function_value, captured.len());
+                self.capture(closure, &captured, argument_values);
+                closure.into()
+            }
+            TypedASTEnum::Fix(generator) => {
+                if let TypedASTEnum::FunctionDefinition(generator_definition) = &*generator.ast {
+                    if let TypedASTEnum::FunctionDefinition(unrolled) =
+                        &*generator_definition.body.ast
+                    {
+                        // fixC(fdC(f, T, T, fdC(x, A, R, body))) is the function x -> body in which
+                        // f refers to the function's own closure.
+                        let captured = captured_variables(
+                            &unrolled.body,
+                            &[&generator_definition.argument_name, &unrolled.argument_name],
+                        );
+                        let function_value = self.build_function(
+                            "lamb_fixpoint",
+                            Some(&generator_definition.argument_name),
+                            (&unrolled.argument_name, &unrolled.argument_type),
+                            &unrolled.return_type,
+                            &unrolled.body,
+                            &captured,
+                        );
+                        let closure = self.build_closure(function_value, captured.len());
+                        self.capture(closure, &captured, argument_values);
+                        return closure.into();
+                    }
+                }
+
+                // Otherwise f is only known at runtime. Each evaluation of the fixC allocates a
+                // closure of the fixpoint that captures f, and the fixpoint unrolls f with its
+                // own closure before every call.
+                let previous_basic_block = self.builder.get_insert_block().unwrap();
 
-                let function_entry_basic_block = self
+                let function_type = self.callee_type(&typed_ast.ty);
+                let fixpoint = self
+                    .module
+                    .add_function("lamb_fixpoint", function_type, None);
+                let fixpoint_entry_basic_block = self
                     .context
-                    .append_basic_block(function_value, "lamb_function_entry");
-                self.builder.position_at_end(function_entry_basic_block);
+                    .append_basic_block(fixpoint, "lamb_fixpoint_entry");
+                self.builder.position_at_end(fixpoint_entry_basic_block);
 
-                argument_values.insert(
-                    function_definition.argument_name.clone(),
-                    function_value.get_first_param().unwrap(),
-                );
-                let return_value = self.codegen_helper(&function_definition.body, argument_values);
+                let fixpoint_closure = fixpoint.get_first_param().unwrap().into_pointer_value();
+                let generator_slot = self
+                    .builder
+                    .build_load(
+                        self.field_pointer(fixpoint_closure, 0),
+                        "lamb_fixpoint_generator",
+                    )
+                    .into_int_value();
+                let generator_closure = self
+                    .slot_to_value(generator_slot, &generator.ty)
+                    .into_pointer_value();
+                let generator_type = self.callee_type(&generator.ty);
+                let unrolled = self
+                    .build_closure_call(
+                        generator_closure,
+                        fixpoint_closure.into(),
+                        generator_type,
+                        "lamb_fixpoint_unroll",
+                    )
+                    .try_as_basic_value()
+                    .unwrap_left()
+                    .into_pointer_value();
+                // The unrolled function has the fixpoint's prototype.
+                self.musttail_calls += 1;
+                let return_value = self
+                    .build_closure_call(
+                        unrolled,
+                        fixpoint.get_nth_param(1).unwrap(),
+                        function_type,
+                        MUSTTAIL_CALL,
+                    )
+                    .try_as_basic_value()
+                    .unwrap_left();
                 self.builder.build_return(Some(&return_value));
-                argument_values
-                    .remove(&function_definition.argument_name)
-                    .unwrap();
-
-                self.builder.position_at_end(previous_basic_block);
 
-                function_value.verify(false);
+                fixpoint.verify(false);
 
-                function_value.as_global_value().as_pointer_value().into()
+                self.builder.position_at_end(previous_basic_block);
+                let generator_closure = self.codegen_helper(generator, argument_values);
+                let closure = self.build_closure(fixpoint, 1);
+                let generator_slot = self.value_to_slot(generator_closure, &generator.ty);
+                self.builder
+                    .build_store(self.field_pointer(closure, 0), generator_slot);
+                closure.into()
             }
             TypedASTEnum::RecursiveFunction(recursive_function) => {
-                let function_type = self.function_prototype(
-                    &recursive_function.argument_type,
-                    &recursive_function.return_type,
+                let captured = captured_variables(
+                    &recursive_function.body,
+                    &[
+                        &recursive_function.function_name,
+                        &recursive_function.argument_name,
+                    ],
                 );
-                let function_value = self.module.add_function(
+                let function_value = self.build_function(
                     &recursive_function.function_name,
-                    function_type,
-                    None,
+                    Some(&recursive_function.function_name),
+                    (
+                        &recursive_function.argument_name,
+                        &recursive_function.argument_type,
+                    ),
+                    &recursive_function.return_type,
+                    &recursive_function.body,
+                    &captured,
                 );
+                let closure = self.build_closure(function_value, captured.len());
+                self.capture(closure, &captured, argument_values);
 
-                let function_entry_basic_block = self
-                    .context
-                    .append_basic_block(function_value, "lamb_recursive_function_entry");
-                self.builder.position_at_end(function_entry_basic_block);
+                argument_values.insert(recursive_function.function_name.clone(), closure.into());
+                let function_use_result_value =
+                    self.codegen_helper(&recursive_function.function_use, argument_values);
+                argument_values
+                    .remove(&recursive_function.function_name)
+                    .unwrap();
 
-                argument_values.insert(
-                    recursive_function.function_name.clone(),
-                    function_value.as_global_value().as_pointer_value().into(),
+                function_use_result_value
+            }
+            TypedASTEnum::RecursiveFunctionGroup(recursive_function_group) => {
+                // Allocate every closure before filling any in so that the closures can capture
+                // each other.
+                let mut closures = Vec::new();
+                for function in &recursive_function_group.functions {
+                    let captured = captured_variables(
+                        &function.body,
+                        &[&function.function_name, &function.argument_name],
+                    );
+                    let function_value = self.build_function(
+                        &function.function_name,
+                        Some(&function.function_name),
+                        (&function.argument_name, &function.argument_type),
+                        &function.return_type,
+                        &function.body,
+                        &captured,
+                    );
+                    let closure = self.build_closure(function_value, captured.len());
+                    argument_values.insert(function.function_name.clone(), closure.into());
+                    closures.push((closure, captured));
+                }
+                for (closure, captured) in &closures {
+                    self.capture(*closure, captured, argument_values);
+                }
+
+                let function_use_result_value =
+                    self.codegen_helper(&recursive_function_group.function_use, argument_values);
+                for function in &recursive_function_group.functions {
+                    argument_values.remove(&function.function_name).unwrap();
+                }
+
+                function_use_result_value
+            }
+            TypedASTEnum::DataDeclaration(data_declaration) => {
+                self.codegen_helper(&data_declaration.body, argument_values)
+            }
+            TypedASTEnum::TypeDeclaration(type_declaration) => {
+                self.aliases
+                    .push((type_declaration.name.clone(), type_declaration.ty.clone()));
+                let value = self.codegen_helper(&type_declaration.body, argument_values);
+                self.aliases.pop();
+                value
+            }
+            TypedASTEnum::Construct(construct) => {
+                // A constructed value is a heap allocated array of i64 slots: the constructor's
+                // tag followed by one slot per field.
+                let i64_type = self.context.i64_type();
+                let constructed = self
+                    .builder
+                    .build_array_malloc(
+                        i64_type,
+                        i64_type.const_int(construct.arguments.len() as u64 + 1, false),
+                        "lamb_constructed",
+                    )
+                    .unwrap();
+                self.builder
+                    .build_store(constructed, i64_type.const_int(construct.tag as u64, false));
+                for (index, argument) in construct.arguments.iter().enumerate() {
+                    let value = self.codegen_helper(argument, argument_values);
+                    let slot = self.value_to_slot(value, &argument.ty);
+                    let field_pointer = self.field_pointer(constructed, index);
+                    self.builder.build_store(field_pointer, slot);
+                }
+                constructed.into()
+            }
+            TypedASTEnum::Match(match_struct) => {
+                let (scrutinee, branch_blocks) = self.codegen_switch(match_struct, argument_values);
+                let merge_block = self.context.insert_basic_block_after(
+                    *branch_blocks.last().unwrap(),
+                    "lamb_match_merge_block",
+                );
+
+                let mut incoming = Vec::new();
+                for (branch, branch_block) in match_struct.branches.iter().zip(branch_blocks) {
+                    self.builder.position_at_end(branch_block);
+                    self.bind_fields(branch, scrutinee, argument_values);
+                    let branch_value = self.codegen_helper(&branch.body, argument_values);
+                    for variable in &branch.variables {
+                        argument_values.remove(variable).unwrap();
+                    }
+                    self.builder.build_unconditional_branch(merge_block);
+                    incoming.push((branch_value, self.builder.get_insert_block().unwrap()));
+                }
+
+                self.builder.position_at_end(merge_block);
+                let phi_value = self
+                    .builder
+                    .build_phi(self.llvm_basic_type(&typed_ast.ty), "lamb_match_phi");
+                let incoming: Vec<(&dyn BasicValue<'ctx>, _)> = incoming
+                    .iter()
+                    .map(|(value, block)| (value as &dyn BasicValue<'ctx>, *block))
+                    .collect();
+                phi_value.add_incoming(&incoming);
+                phi_value.as_basic_value()
+            }
+        }
+    }
+
+    /// Emits `typed_ast` as the rest of a function's body. The value of a call in tail position is
+    /// returned right after the call, rather than through the phi of an enclosing `ifC` or
+    /// `matchC`, so that the call can be compiled to a jump. When the callee has the caller's
+    /// prototype, the call is made a `musttail` call, which guarantees the jump.
+    fn codegen_return(
+        &mut self,
+        typed_ast: &TypedAST,
+        argument_values: &mut HashMap<String, BasicValueEnum<'ctx>>,
+    ) {
+        match &*typed_ast.ast {
+            TypedASTEnum::If(if_struct) => {
+                let condition = self.codegen_helper(&if_struct.condition, argument_values);
+                let then_block = self.context.insert_basic_block_after(
+                    self.builder.get_insert_block().unwrap(),
+                    "lamb_then_block",
                 );
-                argument_values.insert(
-                    recursive_function.argument_name.clone(),
-                    function_value.get_first_param().unwrap(),
+                let else_block = self
+                    .context
+                    .insert_basic_block_after(then_block, "lamb_else_block");
+                self.builder.build_conditional_branch(
+                    condition.into_int_value(),
+                    then_block,
+                    else_block,
                 );
-                let return_value = self.codegen_helper(&recursive_function.body, argument_values);
+
+                self.builder.position_at_end(then_block);
+                self.codegen_return(&if_struct.then, argument_values);
+                self.builder.position_at_end(else_block);
+                self.codegen_return(&if_struct.els, argument_values);
+            }
+            TypedASTEnum::Match(match_struct) => {
+                let (scrutinee, branch_blocks) = self.codegen_switch(match_struct, argument_values);
+                for (branch, branch_block) in match_struct.branches.iter().zip(branch_blocks) {
+                    self.builder.position_at_end(branch_block);
+                    self.bind_fields(branch, scrutinee, argument_values);
+                    self.codegen_return(&branch.body, argument_values);
+                    for variable in &branch.variables {
+                        argument_values.remove(variable).unwrap();
+                    }
+                }
+            }
+            TypedASTEnum::DataDeclaration(data_declaration) => {
+                self.codegen_return(&data_declaration.body, argument_values)
+            }
+            TypedASTEnum::TypeDeclaration(type_declaration) => {
+                self.aliases
+                    .push((type_declaration.name.clone(), type_declaration.ty.clone()));
+                self.codegen_return(&type_declaration.body, argument_values);
+                self.aliases.pop();
+            }
+            TypedASTEnum::FunctionApplication(function_application) => {
+                let closure = self
+                    .codegen_helper(&function_application.function, argument_values)
+                    .into_pointer_value();
+                let argument = self.codegen_helper(&function_application.argument, argument_values);
+                let function_type = self.callee_type(&function_application.function.ty);
+                let caller_type = self
+                    .builder
+                    .get_insert_block()
+                    .unwrap()
+                    .get_parent()
+                    .unwrap()
+                    .get_type();
+                let name = if function_type == caller_type {
+                    self.musttail_calls += 1;
+                    MUSTTAIL_CALL
+                } else {
+                    "lamb_function_call"
+                };
+                let return_value = self
+                    .build_closure_call(closure, argument, function_type, name)
+                    .try_as_basic_value()
+                    .unwrap_left();
                 self.builder.build_return(Some(&return_value));
-                argument_values
-                    .remove(&recursive_function.argument_name)
-                    .unwrap();
+            }
+            _ => {
+                let return_value = self.codegen_helper(typed_ast, argument_values);
+                self.builder.build_return(Some(&return_value));
+            }
+        }
+    }
 
-                function_value.verify(false);
+    /// Evaluates the scrutinee of `match_struct` and switches on its tag to a new block for each
+    /// branch. Returns the scrutinee and the blocks.
+    fn codegen_switch(
+        &mut self,
+        match_struct: &TypedMatch,
+        argument_values: &mut HashMap<String, BasicValueEnum<'ctx>>,
+    ) -> (PointerValue<'ctx>, Vec<BasicBlock<'ctx>>) {
+        let scrutinee = self
+            .codegen_helper(&match_struct.scrutinee, argument_values)
+            .into_pointer_value();
+        let tag = self
+            .builder
+            .build_load(scrutinee, "lamb_match_tag")
+            .into_int_value();
 
-                self.builder.position_at_end(
-                    function_value
-                        .get_previous_function()
-                        .unwrap()
-                        .get_last_basic_block()
-                        .unwrap(),
-                );
-                let function_use_result_value = self.codegen(&recursive_function.function_use);
-                argument_values
-                    .remove(&recursive_function.function_name)
-                    .unwrap();
+        let switch_block = self.builder.get_insert_block().unwrap();
+        let mut previous_block = switch_block;
+        let branch_blocks: Vec<_> = match_struct
+            .branches
+            .iter()
+            .map(|_| {
+                previous_block = self
+                    .context
+                    .insert_basic_block_after(previous_block, "lamb_match_branch_block");
+                previous_block
+            })
+            .collect();
 
-                function_use_result_value
+        let mut cases = Vec::new();
+        let mut default_block = None;
+        for (branch, branch_block) in match_struct.branches.iter().zip(&branch_blocks) {
+            match branch.tag {
+                Some(tag) => cases.push((
+                    self.context.i64_type().const_int(tag as u64, false),
+                    *branch_block,
+                )),
+                None => default_block = Some(*branch_block),
             }
         }
+        // The type checker guarantees that the match is exhaustive.
+        let default_block = default_block.unwrap_or_else(|| {
+            let unreachable_block = self
+                .context
+                .insert_basic_block_after(previous_block, "lamb_match_unreachable_block");
+            self.builder.position_at_end(unreachable_block);
+            self.builder.build_unreachable();
+            unreachable_block
+        });
+        self.builder.position_at_end(switch_block);
+        self.builder.build_switch(tag, default_block, &cases);
+
+        (scrutinee, branch_blocks)
     }
 
-    fn codegen(&mut self, typed_ast: &TypedAST) -> BasicValueEnum<'ctx> {
-        self.codegen_helper(typed_ast, &mut HashMap::new())
+    /// Binds the variables of `branch` to the fields of `scrutinee`.
+    fn bind_fields(
+        &mut self,
+        branch: &TypedMatchBranch,
+        scrutinee: PointerValue<'ctx>,
+        argument_values: &mut HashMap<String, BasicValueEnum<'ctx>>,
+    ) {
+        for (index, (variable, field_type)) in
+            branch.variables.iter().zip(&branch.field_types).enumerate()
+        {
+            let field_pointer = self.field_pointer(scrutinee, index);
+            let slot = self
+                .builder
+                .build_load(field_pointer, "lamb_match_field")
+                .into_int_value();
+            let value = self.slot_to_value(slot, field_type);
+            argument_values.insert(variable.clone(), value);
+        }
     }
 
-    fn llvm_basic_type(&self, ty: &Type) -> BasicTypeEnum<'ctx> {
+    /// Emits a function whose first parameter is its own closure and whose second is `argument`.
+    /// The variables in `captured` are loaded from the closure, and `recursive_name` refers to the
+    /// closure itself.
+    fn build_function(
+        &mut self,
+        name: &str,
+        recursive_name: Option<&String>,
+        argument: (&String, &Type),
+        return_type: &Type,
+        body: &TypedAST,
+        captured: &[(String, Type)],
+    ) -> FunctionValue<'ctx> {
+        let previous_basic_block = self.builder.get_insert_block().unwrap();
+
+        let function_type = self.function_prototype(argument.1, return_type);
+        let function_value = self.module.add_function(name, function_type, None);
+        let function_entry_basic_block = self
+            .context
+            .append_basic_block(function_value, "lamb_function_entry");
+        self.builder.position_at_end(function_entry_basic_block);
+
+        let closure = function_value.get_first_param().unwrap();
+        let mut argument_values = HashMap::new();
+        for (index, (variable, ty)) in captured.iter().enumerate() {
+            let field_pointer = self.field_pointer(closure.into_pointer_value(), index);
+            let slot = self
+                .builder
+                .build_load(field_pointer, "lamb_captured")
+                .into_int_value();
+            argument_values.insert(variable.clone(), self.slot_to_value(slot, ty));
+        }
+        if let Some(recursive_name) = recursive_name {
+            argument_values.insert(recursive_name.clone(), closure);
+        }
+        argument_values.insert(argument.0.clone(), function_value.get_nth_param(1).unwrap());
+        self.codegen_return(body, &mut argument_values);
+
+        function_value.verify(false);
+
+        self.builder.position_at_end(previous_basic_block);
+        function_value
+    }
+
+    /// A closure is a heap allocated array of i64 slots: a pointer to its function followed by one
+    /// slot per captured variable. The captured slots are filled in by `capture`.
+    fn build_closure(
+        &self,
+        function_value: FunctionValue<'ctx>,
+        captured: usize,
+    ) -> PointerValue<'ctx> {
+        let i64_type = self.context.i64_type();
+        let closure = self
+            .builder
+            .build_array_malloc(
+                i64_type,
+                i64_type.const_int(captured as u64 + 1, false),
+                "lamb_closure",
+            )
+            .unwrap();
+        let function_pointer = self.builder.build_ptr_to_int(
+            function_value.as_global_value().as_pointer_value(),
+            i64_type,
+            "lamb_function_pointer",
+        );
+        self.builder.build_store(closure, function_pointer);
+        closure
+    }
+
+    /// Stores the values of the variables in `captured` in the slots of `closure`.
+    fn capture(
+        &self,
+        closure: PointerValue<'ctx>,
+        captured: &[(String, Type)],
+        argument_values: &HashMap<String, BasicValueEnum<'ctx>>,
+    ) {
+        for (index, (variable, ty)) in captured.iter().enumerate() {
+            let value = match argument_values.get(variable) {
+                Some(basic_value_enum) => *basic_value_enum,
+                None => panic!("identifier not found: ({})", variable),
+            };
+            let slot = self.value_to_slot(value, ty);
+            self.builder
+                .build_store(self.field_pointer(closure, index), slot);
+        }
+    }
+
+    /// Calls the function of `closure`, of type `function_type`, with the closure and `argument`.
+    fn build_closure_call(
+        &self,
+        closure: PointerValue<'ctx>,
+        argument: BasicValueEnum<'ctx>,
+        function_type: FunctionType<'ctx>,
+        name: &str,
+    ) -> CallSiteValue<'ctx> {
+        let function_pointer = self
+            .builder
+            .build_load(closure, "lamb_function_pointer")
+            .into_int_value();
+        let function_pointer = self.builder.build_int_to_ptr(
+            function_pointer,
+            function_type.ptr_type(AddressSpace::Generic),
+            "lamb_function_pointer",
+        );
+        let call = self.builder.build_call(
+            CallableValue::try_from(function_pointer).unwrap(),
+            &[closure.into(), argument.into()],
+            name,
+        );
+        // No lamb function accesses its caller's stack, so every call can be a tail call.
+        call.set_tail_call(true);
+        call
+    }
+
+    /// The type that `ty` names if it is an alias, and otherwise `ty` itself. The type checker
+    /// expands every alias in the definitions of `typeC`s.
+    fn unalias<'a>(&'a self, ty: &'a Type) -> &'a Type {
         match ty {
+            Type::Named(name) => match self.aliases.iter().rev().find(|(alias, _)| alias == name) {
+                Some((_, definition)) => definition,
+                None => panic!("Type '{}' is not declared", name),
+            },
+            ty => ty,
+        }
+    }
+
+    fn llvm_basic_type(&self, ty: &Type) -> BasicTypeEnum<'ctx> {
+        match self.unalias(ty) {
             Type::Number => self.context.i64_type().into(),
             Type::Boolean => self.context.bool_type().into(),
-            Type::Function { argument, ret } => self
-                .llvm_basic_type(ret)
-                .fn_type(&[self.llvm_basic_type(argument).into()], false)
-                .ptr_type(AddressSpace::Global)
+            Type::Function { .. } => self
+                .context
+                .i64_type()
+                .ptr_type(AddressSpace::Generic)
+                .into(),
+            Type::Data(_) => self
+                .context
+                .i64_type()
+                .ptr_type(AddressSpace::Generic)
+                .into(),
+            Type::Reference(ty) => self
+                .llvm_basic_type(ty)
+                .ptr_type(AddressSpace::Generic)
+                .into(),
+            // The definitions of aliases are expanded.
+            Type::Named(_) => unreachable!(),
+        }
+    }
+
+    /// Returns a pointer to the slot holding field `index` of a constructed value.
+    fn field_pointer(&self, constructed: PointerValue<'ctx>, index: usize) -> PointerValue<'ctx> {
+        let slot_index = self.context.i64_type().const_int(index as u64 + 1, false);
+        unsafe {
+            self.builder
+                .build_gep(constructed, &[slot_index], "lamb_field_pointer")
+        }
+    }
+
+    /// Widens `value` of type `ty` to the i64 stored in a constructed value's slot.
+    fn value_to_slot(&self, value: BasicValueEnum<'ctx>, ty: &Type) -> IntValue<'ctx> {
+        let i64_type = self.context.i64_type();
+        match self.unalias(ty) {
+            Type::Number => value.into_int_value(),
+            Type::Boolean => self.builder.build_int_z_extend(
+                value.into_int_value(),
+                i64_type,
+                "lamb_bool_to_slot",
+            ),
+            Type::Function { .. } | Type::Data(_) | Type::Reference(_) => self
+                .builder
+                .build_ptr_to_int(value.into_pointer_value(), i64_type, "lamb_pointer_to_slot"),
+            Type::Named(_) => unreachable!(),
+        }
+    }
+
+    /// The inverse of `value_to_slot`.
+    fn slot_to_value(&self, slot: IntValue<'ctx>, ty: &Type) -> BasicValueEnum<'ctx> {
+        match self.unalias(ty) {
+            Type::Number => slot.into(),
+            Type::Boolean => self
+                .builder
+                .build_int_truncate(slot, self.context.bool_type(), "lamb_slot_to_bool")
+                .into(),
+            Type::Function { .. } | Type::Data(_) | Type::Reference(_) => self
+                .builder
+                .build_int_to_ptr(
+                    slot,
+                    self.llvm_basic_type(ty).into_pointer_type(),
+                    "lamb_slot_to_pointer",
+                )
                 .into(),
+            Type::Named(_) => unreachable!(),
         }
     }
 
-    fn function_prototype(&mut self, argument: &Type, ret: &Type) -> FunctionType<'ctx> {
+    /// Every lamb function takes its closure followed by its argument.
+    fn function_prototype(&self, argument: &Type, ret: &Type) -> FunctionType<'ctx> {
+        let closure_type = self.llvm_basic_type(&Type::Function {
+            argument: Box::new(argument.clone()),
+            ret: Box::new(ret.clone()),
+        });
         let argument_type = self.llvm_basic_type(argument);
         let return_type = self.llvm_basic_type(ret);
 
-        return_type.fn_type(&[argument_type.into()], false)
+        return_type.fn_type(&[closure_type.into(), argument_type.into()], false)
+    }
+
+    /// Returns the prototype of the functions of type `ty`.
+    fn callee_type(&self, ty: &Type) -> FunctionType<'ctx> {
+        match self.unalias(ty) {
+            Type::Function { argument, ret } => self.function_prototype(argument, ret),
+            _ => unreachable!("only functions can be called"),
+        }
+    }
+}
+
+/// Returns the variables that `typed_ast` uses but doesn't bind, other than those in `bound`,
+/// with their types, in the order of their first use.
+fn captured_variables(typed_ast: &TypedAST, bound: &[&String]) -> Vec<(String, Type)> {
+    let mut bound = bound.iter().map(|name| (*name).clone()).collect();
+    let mut free = Vec::new();
+    free_variables(typed_ast, &mut bound, &mut free);
+    free
+}
+
+fn free_variables(typed_ast: &TypedAST, bound: &mut Vec<String>, free: &mut Vec<(String, Type)>) {
+    match &*typed_ast.ast {
+        TypedASTEnum::NumberLiteral(_) => (),
+        TypedASTEnum::TrueLiteral | TypedASTEnum::FalseLiteral => (),
+        TypedASTEnum::Plus(lhs, rhs)
+        | TypedASTEnum::Multiply(lhs, rhs)
+        | TypedASTEnum::Equals(lhs, rhs)
+        | TypedASTEnum::PhysicalEquals(lhs, rhs)
+        | TypedASTEnum::Set(lhs, rhs) => {
+            free_variables(lhs, bound, free);
+            free_variables(rhs, bound, free);
+        }
+        TypedASTEnum::Reference(ast) | TypedASTEnum::Dereference(ast) | TypedASTEnum::Fix(ast) => {
+            free_variables(ast, bound, free)
+        }
+        TypedASTEnum::If(if_struct) => {
+            free_variables(&if_struct.condition, bound, free);
+            free_variables(&if_struct.then, bound, free);
+            free_variables(&if_struct.els, bound, free);
+        }
+        TypedASTEnum::Identifier(identifier) => {
+            if !bound.contains(identifier)
+                && free.iter().all(|(variable, _)| variable != identifier)
+            {
+                free.push((identifier.clone(), typed_ast.ty.clone()));
+            }
+        }
+        TypedASTEnum::FunctionApplication(function_application) => {
+            free_variables(&function_application.function, bound, free);
+            free_variables(&function_application.argument, bound, free);
+        }
+        TypedASTEnum::FunctionDefinition(function_definition) => {
+            bound.push(function_definition.argument_name.clone());
+            free_variables(&function_definition.body, bound, free);
+            bound.pop();
+        }
+        TypedASTEnum::RecursiveFunction(recursive_function) => {
+            bound.push(recursive_function.function_name.clone());
+            bound.push(recursive_function.argument_name.clone());
+            free_variables(&recursive_function.body, bound, free);
+            bound.pop();
+            free_variables(&recursive_function.function_use, bound, free);
+            bound.pop();
+        }
+        TypedASTEnum::RecursiveFunctionGroup(recursive_function_group) => {
+            let length = bound.len();
+            for function in &recursive_function_group.functions {
+                bound.push(function.function_name.clone());
+            }
+            for function in &recursive_function_group.functions {
+                bound.push(function.argument_name.clone());
+                free_variables(&function.body, bound, free);
+                bound.pop();
+            }
+            free_variables(&recursive_function_group.function_use, bound, free);
+            bound.truncate(length);
+        }
+        TypedASTEnum::DataDeclaration(data_declaration) => {
+            free_variables(&data_declaration.body, bound, free)
+        }
+        TypedASTEnum::TypeDeclaration(type_declaration) => {
+            free_variables(&type_declaration.body, bound, free)
+        }
+        TypedASTEnum::Construct(construct) => {
+            for argument in &construct.arguments {
+                free_variables(argument, bound, free);
+            }
+        }
+        TypedASTEnum::Match(match_struct) => {
+            free_variables(&match_struct.scrutinee, bound, free);
+            for branch in &match_struct.branches {
+                let length = bound.len();
+                bound.extend(branch.variables.iter().cloned());
+                free_variables(&branch.body, bound, free);
+                bound.truncate(length);
+            }
+        }
     }
 }
 
@@ -490,4 +1171,55 @@ mod tests {
         };
         assert_eq!(CodeGen::run(&input).unwrap(), 1)
     }
+
+    #[test]
+    fn codegen_tail_calls_without_optimization() {
+        // The million mutually recursive calls only fit in this stack as musttail calls.
+        let code = crate::read::build("tests/inputs/tail_call_million.txt");
+        let result = std::thread::Builder::new()
+            .stack_size(1 << 20)
+            .spawn(move || {
+                let typed_ast = TypedAST::new(&crate::parse(&code));
+                CodeGen::run_with(&typed_ast, OptimizationLevel::None).unwrap()
+            })
+            .unwrap()
+            .join()
+            .unwrap();
+        assert_eq!(result, 1)
+    }
+
+    #[test]
+    fn codegen_captures_named_types() {
+        let code = r#"typeC("Count", numT, appC(appC(fdC("x", namedT("Count"), funT(numT, numT), fdC("y", numT, numT, plusC(idC("x"), idC("y")))), numC(1)), numC(2)))"#;
+        let typed_ast = TypedAST::new(&crate::parse(code));
+        assert_eq!(CodeGen::run(&typed_ast).unwrap(), 3)
+    }
+
+    #[test]
+    fn codegen_closure_captures_argument() {
+        let code = r#"appC(appC(fdC("x", numT, funT(numT, numT), fdC("y", numT, numT, plusC(idC("x"), idC("y")))), numC(6)), numC(7))"#;
+        let typed_ast = TypedAST::new(&crate::parse(code));
+        assert_eq!(CodeGen::run(&typed_ast).unwrap(), 13)
+    }
+
+    #[test]
+    fn codegen_closure_captures_in_recursive_function() {
+        let code = r#"appC(fdC("n", numT, numT, recC("sum", "i", numT, numT, ifC(eqC(idC("i"), idC("n")), idC("i"), plusC(idC("i"), appC(idC("sum"), plusC(idC("i"), numC(1))))), appC(idC("sum"), numC(1)))), numC(4))"#;
+        let typed_ast = TypedAST::new(&crate::parse(code));
+        assert_eq!(CodeGen::run(&typed_ast).unwrap(), 10)
+    }
+
+    #[test]
+    fn codegen_closure_captures_in_recursive_group() {
+        let code = r#"appC(fdC("step", numT, boolT, recGroupC([("even", "n", numT, boolT, ifC(eqC(idC("n"), numC(6)), trueC, appC(idC("odd"), plusC(idC("n"), idC("step"))))), ("odd", "n", numT, boolT, ifC(eqC(idC("n"), numC(6)), falseC, appC(idC("even"), plusC(idC("n"), idC("step")))))], appC(idC("even"), numC(0)))), numC(2))"#;
+        let typed_ast = TypedAST::new(&crate::parse(code));
+        assert_eq!(CodeGen::run(&typed_ast).unwrap(), 0)
+    }
+
+    #[test]
+    fn codegen_closure_returned_from_branch() {
+        let code = r#"appC(ifC(falseC, fdC("x", numT, numT, idC("x")), appC(fdC("k", numT, funT(numT, numT), fdC("x", numT, numT, multC(idC("x"), idC("k")))), numC(3))), numC(5))"#;
+        let typed_ast = TypedAST::new(&crate::parse(code));
+        assert_eq!(CodeGen::run(&typed_ast).unwrap(), 15)
+    }
 }
@@ -0,0 +1,317 @@
+use crate::{
+    interpret::{Data, Environment, Observer},
+    parse::AST,
+    tokenize::Position,
+};
+use std::io::{BufRead, Write};
+
+/// When the debugger next stops, other than at breakpoints.
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum Mode {
+    /// Before the next node is evaluated.
+    Step,
+    /// Before the next node at or above the given depth, which steps over the current node.
+    Next(usize),
+    Continue,
+    /// The user quit, so the program runs to completion without stopping.
+    Detached,
+}
+
+/// A node that has been entered but not left.
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum Entered {
+    /// An `AST::Located` or `AST::Commented`, which isn't counted in the depth.
+    Annotation,
+    Application,
+    Other,
+}
+
+/// An application that is being evaluated.
+struct Call {
+    function: String,
+    position: Option<Position>,
+    /// Whether the function has been applied, as opposed to its argument still being evaluated.
+    applied: bool,
+}
+
+/// An interactive debugger that drives the interpreter as an `Observer`, reading commands from
+/// `input` and writing to `output` whenever it stops. Positions are only known for programs
+/// parsed from a `TokenStream::build_located` stream.
+pub struct Debugger<R: BufRead, W: Write> {
+    input: R,
+    output: W,
+    source: Vec<String>,
+    function_breakpoints: Vec<String>,
+    line_breakpoints: Vec<usize>,
+    mode: Mode,
+    /// The number of nodes, other than `AST::Located` and `AST::Commented`, that have been entered but not left.
+    depth: usize,
+    /// The nodes that have been entered but not left, innermost last.
+    entered: Vec<Entered>,
+    position: Option<Position>,
+    calls: Vec<Call>,
+    breakpoint_hit: bool,
+}
+
+const HELP: &str = "Commands:
+  s, step              stop before the next node
+  n, next              step over the current node
+  c, continue          run until a breakpoint
+  b, break <name|line> stop when the function <name> is applied or <line> is reached
+  e, env               print the variables in scope
+  bt, stack            print the functions being applied
+  w, where             print the current position
+  q, quit              run the rest of the program without stopping
+  h, help              print this message";
+
+impl<R: BufRead, W: Write> Debugger<R, W> {
+    /// Creates a debugger for `source` that stops before the first node.
+    pub fn new(source: &str, input: R, output: W) -> Debugger<R, W> {
+        Debugger {
+            input,
+            output,
+            source: source.lines().map(String::from).collect(),
+            function_breakpoints: Vec::new(),
+            line_breakpoints: Vec::new(),
+            mode: Mode::Step,
+            depth: 0,
+            entered: Vec::new(),
+            position: None,
+            calls: Vec::new(),
+            breakpoint_hit: false,
+        }
+    }
+
+    /// Adds a breakpoint on the applications of the function named `name`, or on the line `name`
+    /// if it is a number.
+    pub fn add_breakpoint(&mut self, name: &str) {
+        match name.parse() {
+            Ok(line) => self.line_breakpoints.push(line),
+            Err(_) => self.function_breakpoints.push(name.to_string()),
+        }
+    }
+
+    fn write_position(&mut self) {
+        match self.position {
+            Some(position) => {
+                let line = self
+                    .source
+                    .get(position.line - 1)
+                    .map_or("", String::as_str);
+                writeln!(self.output, "Stopped at {}", position).unwrap();
+                writeln!(self.output, "{:>4} | {}", position.line, line).unwrap();
+            }
+            None => writeln!(self.output, "Stopped at an unknown position").unwrap(),
+        }
+    }
+
+    /// Reads and runs commands until one of them resumes evaluation.
+    fn prompt(&mut self, environment: &Environment) {
+        self.write_position();
+        loop {
+            write!(self.output, "(lamb) ").unwrap();
+            self.output.flush().unwrap();
+            let mut line = String::new();
+            if self.input.read_line(&mut line).unwrap() == 0 {
+                self.mode = Mode::Detached;
+                return;
+            }
+
+            let mut words = line.split_whitespace();
+            match (words.next(), words.next()) {
+                (Some("s"), _) | (Some("step"), _) => {
+                    self.mode = Mode::Step;
+                    return;
+                }
+                (Some("n"), _) | (Some("next"), _) => {
+                    self.mode = Mode::Next(self.depth);
+                    return;
+                }
+                (Some("c"), _) | (Some("continue"), _) => {
+                    self.mode = Mode::Continue;
+                    return;
+                }
+                (Some("q"), _) | (Some("quit"), _) => {
+                    self.mode = Mode::Detached;
+                    return;
+                }
+                (Some("b"), Some(name)) | (Some("break"), Some(name)) => {
+                    self.add_breakpoint(name);
+                    writeln!(self.output, "Breakpoint set on {}", name).unwrap();
+                }
+                (Some("e"), _) | (Some("env"), _) => {
+                    for (name, data) in environment.bindings() {
+                        writeln!(self.output, "{} = {}", name, data).unwrap();
+                    }
+                }
+                (Some("bt"), _) | (Some("stack"), _) => {
+                    let calls = self.calls.iter().rev().filter(|call| call.applied);
+                    for (index, call) in calls.enumerate() {
+                        match call.position {
+                            Some(position) => {
+                                writeln!(
+                                    self.output,
+                                    "#{} {} at {}",
+                                    index, call.function, position
+                                )
+                            }
+                            None => writeln!(self.output, "#{} {}", index, call.function),
+                        }
+                        .unwrap();
+                    }
+                }
+                (Some("w"), _) | (Some("where"), _) => self.write_position(),
+                (Some("h"), _) | (Some("help"), _) => writeln!(self.output, "{}", HELP).unwrap(),
+                (None, _) => (),
+                (Some(command), _) => writeln!(
+                    self.output,
+                    "Unknown command '{}'. Type 'help' for a list of commands.",
+                    command
+                )
+                .unwrap(),
+            }
+        }
+    }
+}
+
+impl<R: BufRead, W: Write> Observer for Debugger<R, W> {
+    fn enter(&mut self, ast: &AST, environment: &Environment) {
+        self.entered.push(match ast {
+            AST::Located(_, _) | AST::Commented(_, _) => Entered::Annotation,
+            AST::FunctionApplication(_) => Entered::Application,
+            _ => Entered::Other,
+        });
+        if let AST::Located(position, _) = ast {
+            let new_line = self.position.map(|previous| previous.line) != Some(position.line);
+            if new_line && self.line_breakpoints.contains(&position.line) {
+                self.breakpoint_hit = true;
+            }
+            self.position = Some(*position);
+            return;
+        }
+        if let AST::Commented(_, _) = ast {
+            return;
+        }
+
+        if let AST::FunctionApplication(function_application) = ast {
+            self.calls.push(Call {
+                function: function_name(&function_application.function),
+                position: self.position,
+                applied: false,
+            });
+        }
+        self.depth += 1;
+
+        let stop = match self.mode {
+            Mode::Step => true,
+            Mode::Next(depth) => self.depth <= depth || self.breakpoint_hit,
+            Mode::Continue => self.breakpoint_hit,
+            Mode::Detached => false,
+        };
+        self.breakpoint_hit = false;
+        if stop {
+            self.prompt(environment);
+        }
+    }
+
+    fn leave(&mut self, _data: &Data) {
+        match self.entered.pop().unwrap() {
+            Entered::Annotation => (),
+            Entered::Application => {
+                self.calls.pop();
+                self.depth -= 1;
+            }
+            Entered::Other => self.depth -= 1,
+        }
+    }
+
+    fn apply(&mut self, _function: &Data, _argument: &Data) {
+        // The debugger stops before the first node of the function's body is evaluated, when the
+        // argument is in scope.
+        if let Some(call) = self.calls.last_mut() {
+            call.applied = true;
+            if self.function_breakpoints.contains(&call.function) {
+                self.breakpoint_hit = true;
+            }
+        }
+    }
+}
+
+/// The name of the function applied by `appC(function, ...)`, if it is an identifier.
+fn function_name(function: &AST) -> String {
+    match function {
+        AST::Located(_, ast) | AST::Commented(_, ast) => function_name(ast),
+        AST::Identifier(name) => name.clone(),
+        _ => String::from("<anonymous>"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        interpret::{interpret_with_observer, Budget},
+        tokenize::TokenStream,
+    };
+
+    const COUNT: &str = r#"recC(
+  "count",
+  "n",
+  numT,
+  numT,
+  ifC(
+    eqC(idC("n"), numC(0)),
+    numC(0),
+    appC(idC("count"), plusC(idC("n"), numC(-1)))
+  ),
+  appC(idC("count"), numC(2))
+)"#;
+
+    /// Runs `COUNT` in the debugger with the commands in `input`, returning the debugger's output.
+    fn debug(input: &str, breakpoints: &[&str]) -> String {
+        let ast = AST::build(&mut TokenStream::build_located(COUNT.chars()));
+        let mut output = Vec::new();
+        let mut debugger = Debugger::new(COUNT, input.as_bytes(), &mut output);
+        for breakpoint in breakpoints {
+            debugger.add_breakpoint(breakpoint);
+        }
+        assert_eq!(
+            interpret_with_observer(&ast, &Budget::default(), &mut debugger),
+            Ok(Data::Number(0))
+        );
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn debug_step() {
+        let output = debug("step\nstep\nquit\n", &[]);
+        assert!(output.starts_with("Stopped at 1:1\n   1 | recC(\n"));
+        assert!(output.contains("Stopped at 11:3\n  11 |   appC(idC(\"count\"), numC(2))\n"));
+        assert!(output.contains("Stopped at 11:8\n"));
+        assert_eq!(output.matches("Stopped at").count(), 3);
+    }
+
+    #[test]
+    fn debug_next() {
+        let output = debug("next\n", &[]);
+        assert_eq!(output.matches("Stopped at").count(), 1);
+    }
+
+    #[test]
+    fn debug_function_breakpoint() {
+        let output = debug("continue\nenv\nstack\ncontinue\nstack\nquit\n", &["count"]);
+        assert!(output.contains("Stopped at 6:3\n"));
+        assert!(output.contains("count = <function>\nn = 2\n"));
+        assert!(output.contains("#0 count at 11:3\n(lamb) "));
+        assert!(output.contains("#0 count at 9:5\n#1 count at 11:3\n"));
+    }
+
+    #[test]
+    fn debug_line_breakpoint() {
+        let output = debug("break 9\ncontinue\nenv\ncontinue\nenv\ncontinue\n", &[]);
+        assert!(output.contains("Breakpoint set on 9\n"));
+        assert!(output.contains("n = 2\n"));
+        assert!(output.contains("n = 1\n"));
+        assert_eq!(output.matches("Stopped at 9:5").count(), 2);
+    }
+}
@@ -0,0 +1,157 @@
+use std::fmt::Write;
+
+/// The number of unchanged lines `unified` shows around each change.
+const CONTEXT: usize = 3;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Edit<'a> {
+    Same(&'a str),
+    Removed(&'a str),
+    Added(&'a str),
+}
+
+/// The shortest edit from the lines of `old` to the lines of `new`, found with a longest common
+/// subsequence.
+pub fn edits<'a>(old: &'a str, new: &'a str) -> Vec<Edit<'a>> {
+    let old: Vec<&str> = old.lines().collect();
+    let new: Vec<&str> = new.lines().collect();
+
+    // common[i][j] is the length of the longest common subsequence of old[i..] and new[j..].
+    let mut common = vec![vec![0; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            common[i][j] = if old[i] == new[j] {
+                common[i + 1][j + 1] + 1
+            } else {
+                common[i + 1][j].max(common[i][j + 1])
+            };
+        }
+    }
+
+    let mut edits = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            edits.push(Edit::Same(old[i]));
+            i += 1;
+            j += 1;
+        } else if j == new.len() || (i < old.len() && common[i + 1][j] >= common[i][j + 1]) {
+            edits.push(Edit::Removed(old[i]));
+            i += 1;
+        } else {
+            edits.push(Edit::Added(new[j]));
+            j += 1;
+        }
+    }
+    edits
+}
+
+/// A unified diff from `old` to `new`, which is empty if they have the same lines.
+pub fn unified(old: &str, new: &str, old_name: &str, new_name: &str) -> String {
+    let edits = edits(old, new);
+    let changes: Vec<usize> = (0..edits.len())
+        .filter(|index| !matches!(edits[*index], Edit::Same(_)))
+        .collect();
+    if changes.is_empty() {
+        return String::new();
+    }
+
+    // Each hunk covers changes whose contexts overlap.
+    let mut hunks: Vec<(usize, usize)> = Vec::new();
+    for change in changes {
+        let start = change.saturating_sub(CONTEXT);
+        let end = (change + 1 + CONTEXT).min(edits.len());
+        match hunks.last_mut() {
+            Some((_, previous_end)) if start <= *previous_end => *previous_end = end,
+            _ => hunks.push((start, end)),
+        }
+    }
+
+    let mut output = String::new();
+    writeln!(output, "--- {}", old_name).unwrap();
+    writeln!(output, "+++ {}", new_name).unwrap();
+    for (start, end) in hunks {
+        let before = &edits[..start];
+        let old_start = before
+            .iter()
+            .filter(|edit| !matches!(edit, Edit::Added(_)))
+            .count();
+        let new_start = before
+            .iter()
+            .filter(|edit| !matches!(edit, Edit::Removed(_)))
+            .count();
+        let hunk = &edits[start..end];
+        let old_count = hunk
+            .iter()
+            .filter(|edit| !matches!(edit, Edit::Added(_)))
+            .count();
+        let new_count = hunk
+            .iter()
+            .filter(|edit| !matches!(edit, Edit::Removed(_)))
+            .count();
+        writeln!(
+            output,
+            "@@ -{},{} +{},{} @@",
+            old_start + 1,
+            old_count,
+            new_start + 1,
+            new_count
+        )
+        .unwrap();
+        for edit in hunk {
+            match edit {
+                Edit::Same(line) => writeln!(output, " {}", line),
+                Edit::Removed(line) => writeln!(output, "-{}", line),
+                Edit::Added(line) => writeln!(output, "+{}", line),
+            }
+            .unwrap();
+        }
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn diff_edits() {
+        assert_eq!(
+            edits("a\nb\nc\n", "a\nc\nd\n"),
+            vec![
+                Edit::Same("a"),
+                Edit::Removed("b"),
+                Edit::Same("c"),
+                Edit::Added("d")
+            ]
+        );
+    }
+
+    #[test]
+    fn diff_unified() {
+        let old = "1\n2\n3\n4\n5\n6\n7\n8\n9\n10\n11\n12\n";
+        let new = "0\n1\n2\n3\n4\n5\n6\n7\n8\n9\n10\n11\neleven\n";
+        assert_eq!(
+            unified(old, new, "old", "new"),
+            "--- old
++++ new
+@@ -1,3 +1,4 @@
++0
+ 1
+ 2
+ 3
+@@ -9,4 +10,4 @@
+ 9
+ 10
+ 11
+-12
++eleven
+"
+        );
+    }
+
+    #[test]
+    fn diff_same() {
+        assert_eq!(unified("a\nb", "a\nb\n", "old", "new"), "");
+    }
+}
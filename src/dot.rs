@@ -0,0 +1,583 @@
+//! Graphviz DOT graphs of programs, for looking at them with `dot -Tsvg`.
+
+use crate::{
+    format::type_text,
+    parse::{Constructor, AST},
+    type_check::{Type, TypedAST, TypedASTEnum},
+};
+use std::{collections::BTreeSet, fmt::Write};
+
+/// A DOT digraph being written, whose nodes are numbered in the order they are added.
+struct Graph {
+    output: String,
+    nodes: usize,
+}
+
+impl Graph {
+    fn new(attributes: &str) -> Graph {
+        Graph {
+            output: format!("digraph {{\n  {}\n", attributes),
+            nodes: 0,
+        }
+    }
+
+    fn node(&mut self, label: &str, attributes: &str) -> usize {
+        let node = self.nodes;
+        self.nodes += 1;
+        writeln!(
+            self.output,
+            "  n{} [label=\"{}\"{}];",
+            node,
+            escape(label),
+            attributes
+        )
+        .unwrap();
+        node
+    }
+
+    fn edge(&mut self, from: usize, to: usize, label: &str) {
+        if label.is_empty() {
+            writeln!(self.output, "  n{} -> n{};", from, to).unwrap();
+        } else {
+            writeln!(
+                self.output,
+                "  n{} -> n{} [label=\"{}\"];",
+                from,
+                to,
+                escape(label)
+            )
+            .unwrap();
+        }
+    }
+
+    fn finish(mut self) -> String {
+        self.output.push_str("}\n");
+        self.output
+    }
+}
+
+/// Escapes `label` for a double quoted DOT string, where `\n` starts a new line.
+fn escape(label: &str) -> String {
+    label
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+fn quoted(name: &str) -> String {
+    format!("\"{}\"", name)
+}
+
+fn signature(argument_name: &str, argument_type: &Type, return_type: &Type) -> String {
+    format!(
+        "{}, {}, {}",
+        quoted(argument_name),
+        type_text(argument_type),
+        type_text(return_type)
+    )
+}
+
+fn constructors_text(constructors: &[Constructor]) -> String {
+    let constructors: Vec<String> = constructors
+        .iter()
+        .map(|constructor| {
+            let fields: Vec<String> = constructor.fields.iter().map(type_text).collect();
+            format!("({}, [{}])", quoted(&constructor.name), fields.join(", "))
+        })
+        .collect();
+    format!("[{}]", constructors.join(", "))
+}
+
+fn pattern(constructor: &str, variables: &[String]) -> String {
+    let variables: Vec<String> = variables.iter().map(|variable| quoted(variable)).collect();
+    format!("({}, [{}])", quoted(constructor), variables.join(", "))
+}
+
+/// The label of `ast` and its children, each with the label of the edge to it.
+fn ast_node(ast: &AST) -> (String, Vec<(String, &AST)>) {
+    let child = |label: &str, ast| (label.to_string(), ast);
+    match ast {
+        AST::NumberLiteral(number) => (format!("numC({})", number), Vec::new()),
+        AST::TrueLiteral => (String::from("trueC"), Vec::new()),
+        AST::FalseLiteral => (String::from("falseC"), Vec::new()),
+        AST::Identifier(id) => (format!("idC({})", quoted(id)), Vec::new()),
+        AST::Plus(lhs, rhs) => (
+            String::from("plusC"),
+            vec![child("lhs", &**lhs), child("rhs", rhs)],
+        ),
+        AST::Multiply(lhs, rhs) => (
+            String::from("multC"),
+            vec![child("lhs", &**lhs), child("rhs", rhs)],
+        ),
+        AST::Equals(lhs, rhs) => (
+            String::from("eqC"),
+            vec![child("lhs", &**lhs), child("rhs", rhs)],
+        ),
+        AST::PhysicalEquals(lhs, rhs) => (
+            String::from("physEqC"),
+            vec![child("lhs", &**lhs), child("rhs", rhs)],
+        ),
+        AST::Set(reference, value) => (
+            String::from("setC"),
+            vec![child("reference", &**reference), child("value", value)],
+        ),
+        AST::Reference(ast) => (String::from("refC"), vec![child("", &**ast)]),
+        AST::Dereference(ast) => (String::from("derefC"), vec![child("", &**ast)]),
+        AST::Fix(ast) => (String::from("fixC"), vec![child("", &**ast)]),
+        AST::If(if_struct) => (
+            String::from("ifC"),
+            vec![
+                child("condition", &*if_struct.condition),
+                child("then", &if_struct.then),
+                child("else", &if_struct.els),
+            ],
+        ),
+        AST::FunctionApplication(function_application) => (
+            String::from("appC"),
+            vec![
+                child("function", &*function_application.function),
+                child("argument", &function_application.argument),
+            ],
+        ),
+        AST::FunctionDefinition(function_definition) => (
+            format!(
+                "fdC({})",
+                signature(
+                    &function_definition.argument_name,
+                    &function_definition.argument_type,
+                    &function_definition.return_type
+                )
+            ),
+            vec![child("body", &*function_definition.body)],
+        ),
+        AST::RecursiveFunction(recursive_function) => (
+            format!(
+                "recC({}, {})",
+                quoted(&recursive_function.function_name),
+                signature(
+                    &recursive_function.argument_name,
+                    &recursive_function.argument_type,
+                    &recursive_function.return_type
+                )
+            ),
+            vec![
+                child("body", &*recursive_function.body),
+                child("use", &recursive_function.function_use),
+            ],
+        ),
+        AST::RecursiveFunctionGroup(recursive_function_group) => {
+            let mut children: Vec<(String, &AST)> = recursive_function_group
+                .functions
+                .iter()
+                .map(|function| {
+                    (
+                        format!(
+                            "{}({})",
+                            function.function_name,
+                            signature(
+                                &function.argument_name,
+                                &function.argument_type,
+                                &function.return_type
+                            )
+                        ),
+                        &*function.body,
+                    )
+                })
+                .collect();
+            children.push(child("use", &recursive_function_group.function_use));
+            (String::from("recGroupC"), children)
+        }
+        AST::DataDeclaration(data_declaration) => (
+            format!(
+                "dataC({}, {})",
+                quoted(&data_declaration.name),
+                constructors_text(&data_declaration.constructors)
+            ),
+            vec![child("body", &*data_declaration.body)],
+        ),
+        AST::Construct(construct) => (
+            format!("conC({})", quoted(&construct.constructor)),
+            construct
+                .arguments
+                .iter()
+                .enumerate()
+                .map(|(index, argument)| (index.to_string(), argument))
+                .collect(),
+        ),
+        AST::Match(match_struct) => {
+            let mut children = vec![child("scrutinee", &*match_struct.scrutinee)];
+            children.extend(match_struct.branches.iter().map(|branch| {
+                (
+                    pattern(&branch.constructor, &branch.variables),
+                    &*branch.body,
+                )
+            }));
+            (String::from("matchC"), children)
+        }
+        AST::TypeDeclaration(type_declaration) => (
+            format!(
+                "typeC({}, {})",
+                quoted(&type_declaration.name),
+                type_text(&type_declaration.ty)
+            ),
+            vec![child("body", &*type_declaration.body)],
+        ),
+        AST::Located(_, ast) | AST::Commented(_, ast) => ast_node(ast),
+        AST::Error => (String::from("error"), Vec::new()),
+    }
+}
+
+/// The label of `typed_ast`, without its type, and its children, each with the label of the edge
+/// to it.
+fn typed_ast_node(typed_ast: &TypedAST) -> (String, Vec<(String, &TypedAST)>) {
+    let child = |label: &str, typed_ast| (label.to_string(), typed_ast);
+    match &*typed_ast.ast {
+        TypedASTEnum::NumberLiteral(number) => (format!("numC({})", number), Vec::new()),
+        TypedASTEnum::TrueLiteral => (String::from("trueC"), Vec::new()),
+        TypedASTEnum::FalseLiteral => (String::from("falseC"), Vec::new()),
+        TypedASTEnum::Identifier(id) => (format!("idC({})", quoted(id)), Vec::new()),
+        TypedASTEnum::Plus(lhs, rhs) => (
+            String::from("plusC"),
+            vec![child("lhs", lhs), child("rhs", rhs)],
+        ),
+        TypedASTEnum::Multiply(lhs, rhs) => (
+            String::from("multC"),
+            vec![child("lhs", lhs), child("rhs", rhs)],
+        ),
+        TypedASTEnum::Equals(lhs, rhs) => (
+            String::from("eqC"),
+            vec![child("lhs", lhs), child("rhs", rhs)],
+        ),
+        TypedASTEnum::PhysicalEquals(lhs, rhs) => (
+            String::from("physEqC"),
+            vec![child("lhs", lhs), child("rhs", rhs)],
+        ),
+        TypedASTEnum::Set(reference, value) => (
+            String::from("setC"),
+            vec![child("reference", reference), child("value", value)],
+        ),
+        TypedASTEnum::Reference(ast) => (String::from("refC"), vec![child("", ast)]),
+        TypedASTEnum::Dereference(ast) => (String::from("derefC"), vec![child("", ast)]),
+        TypedASTEnum::Fix(ast) => (String::from("fixC"), vec![child("", ast)]),
+        TypedASTEnum::If(if_struct) => (
+            String::from("ifC"),
+            vec![
+                child("condition", &if_struct.condition),
+                child("then", &if_struct.then),
+                child("else", &if_struct.els),
+            ],
+        ),
+        TypedASTEnum::FunctionApplication(function_application) => (
+            String::from("appC"),
+            vec![
+                child("function", &function_application.function),
+                child("argument", &function_application.argument),
+            ],
+        ),
+        TypedASTEnum::FunctionDefinition(function_definition) => (
+            format!(
+                "fdC({})",
+                signature(
+                    &function_definition.argument_name,
+                    &function_definition.argument_type,
+                    &function_definition.return_type
+                )
+            ),
+            vec![child("body", &function_definition.body)],
+        ),
+        TypedASTEnum::RecursiveFunction(recursive_function) => (
+            format!(
+                "recC({}, {})",
+                quoted(&recursive_function.function_name),
+                signature(
+                    &recursive_function.argument_name,
+                    &recursive_function.argument_type,
+                    &recursive_function.return_type
+                )
+            ),
+            vec![
+                child("body", &recursive_function.body),
+                child("use", &recursive_function.function_use),
+            ],
+        ),
+        TypedASTEnum::RecursiveFunctionGroup(recursive_function_group) => {
+            let mut children: Vec<(String, &TypedAST)> = recursive_function_group
+                .functions
+                .iter()
+                .map(|function| {
+                    (
+                        format!(
+                            "{}({})",
+                            function.function_name,
+                            signature(
+                                &function.argument_name,
+                                &function.argument_type,
+                                &function.return_type
+                            )
+                        ),
+                        &function.body,
+                    )
+                })
+                .collect();
+            children.push(child("use", &recursive_function_group.function_use));
+            (String::from("recGroupC"), children)
+        }
+        TypedASTEnum::DataDeclaration(data_declaration) => (
+            format!(
+                "dataC({}, {})",
+                quoted(&data_declaration.name),
+                constructors_text(&data_declaration.constructors)
+            ),
+            vec![child("body", &data_declaration.body)],
+        ),
+        TypedASTEnum::TypeDeclaration(type_declaration) => (
+            format!(
+                "typeC({}, {})",
+                quoted(&type_declaration.name),
+                type_text(&type_declaration.ty)
+            ),
+            vec![child("body", &type_declaration.body)],
+        ),
+        TypedASTEnum::Construct(construct) => (
+            format!("conC({})", quoted(&construct.constructor)),
+            construct
+                .arguments
+                .iter()
+                .enumerate()
+                .map(|(index, argument)| (index.to_string(), argument))
+                .collect(),
+        ),
+        TypedASTEnum::Match(match_struct) => {
+            let mut children = vec![child("scrutinee", &match_struct.scrutinee)];
+            children.extend(match_struct.branches.iter().map(|branch| {
+                (
+                    pattern(&branch.constructor, &branch.variables),
+                    &branch.body,
+                )
+            }));
+            (String::from("matchC"), children)
+        }
+    }
+}
+
+const TREE_ATTRIBUTES: &str = "node [shape=box, fontname=monospace];";
+
+/// The tree of `ast`, with an edge from each node to each of its children.
+pub fn ast(ast: &AST) -> String {
+    fn add(graph: &mut Graph, ast: &AST) -> usize {
+        let (label, children) = ast_node(ast);
+        let node = graph.node(&label, "");
+        for (edge, child) in children {
+            let child = add(graph, child);
+            graph.edge(node, child, &edge);
+        }
+        node
+    }
+
+    let mut graph = Graph::new(TREE_ATTRIBUTES);
+    add(&mut graph, ast);
+    graph.finish()
+}
+
+/// Like `ast`, but each node is labeled with its type as well.
+pub fn typed_ast(typed_ast: &TypedAST) -> String {
+    fn add(graph: &mut Graph, typed_ast: &TypedAST) -> usize {
+        let (label, children) = typed_ast_node(typed_ast);
+        let label = format!("{}\n: {}", label, type_text(&typed_ast.ty));
+        let node = graph.node(&label, "");
+        for (edge, child) in children {
+            let child = add(graph, child);
+            graph.edge(node, child, &edge);
+        }
+        node
+    }
+
+    let mut graph = Graph::new(TREE_ATTRIBUTES);
+    add(&mut graph, typed_ast);
+    graph.finish()
+}
+
+/// Finds the calls in a program, where a call is an application of a name that refers to a
+/// function of a `recC` or `recGroupC`.
+struct CallGraph {
+    graph: Graph,
+    /// The names in scope, with the node of the function each refers to, if any.
+    scope: Vec<(String, Option<usize>)>,
+    calls: BTreeSet<(usize, usize)>,
+}
+
+impl CallGraph {
+    fn function(&mut self, name: &str) -> usize {
+        self.graph.node(name, "")
+    }
+
+    fn resolve(&self, name: &str) -> Option<usize> {
+        self.scope
+            .iter()
+            .rev()
+            .find(|(scoped, _)| scoped == name)
+            .and_then(|(_, function)| *function)
+    }
+
+    /// Visits `ast`, which is evaluated by the function `caller`, with `bound` in scope.
+    fn visit(&mut self, ast: &AST, caller: usize, bound: Vec<(String, Option<usize>)>) {
+        let scope = self.scope.len();
+        self.scope.extend(bound);
+        match strip(ast) {
+            AST::FunctionApplication(function_application) => {
+                if let AST::Identifier(name) = strip(&function_application.function) {
+                    if let Some(callee) = self.resolve(name) {
+                        self.calls.insert((caller, callee));
+                    }
+                }
+                self.visit(&function_application.function, caller, Vec::new());
+                self.visit(&function_application.argument, caller, Vec::new());
+            }
+            AST::FunctionDefinition(function_definition) => self.visit(
+                &function_definition.body,
+                caller,
+                vec![(function_definition.argument_name.clone(), None)],
+            ),
+            AST::RecursiveFunction(recursive_function) => {
+                let function = self.function(&recursive_function.function_name);
+                let name = recursive_function.function_name.clone();
+                self.visit(
+                    &recursive_function.body,
+                    function,
+                    vec![
+                        (name.clone(), Some(function)),
+                        (recursive_function.argument_name.clone(), None),
+                    ],
+                );
+                self.visit(
+                    &recursive_function.function_use,
+                    caller,
+                    vec![(name, Some(function))],
+                );
+            }
+            AST::RecursiveFunctionGroup(recursive_function_group) => {
+                let functions: Vec<(String, Option<usize>)> = recursive_function_group
+                    .functions
+                    .iter()
+                    .map(|function| {
+                        let node = self.function(&function.function_name);
+                        (function.function_name.clone(), Some(node))
+                    })
+                    .collect();
+                for (function, (_, node)) in
+                    recursive_function_group.functions.iter().zip(&functions)
+                {
+                    let mut bound = functions.clone();
+                    bound.push((function.argument_name.clone(), None));
+                    self.visit(&function.body, node.unwrap(), bound);
+                }
+                self.visit(&recursive_function_group.function_use, caller, functions);
+            }
+            AST::Match(match_struct) => {
+                self.visit(&match_struct.scrutinee, caller, Vec::new());
+                for branch in &match_struct.branches {
+                    let bound = branch
+                        .variables
+                        .iter()
+                        .map(|variable| (variable.clone(), None))
+                        .collect();
+                    self.visit(&branch.body, caller, bound);
+                }
+            }
+            ast => {
+                for (_, child) in ast_node(ast).1 {
+                    self.visit(child, caller, Vec::new());
+                }
+            }
+        }
+        self.scope.truncate(scope);
+    }
+}
+
+fn strip(ast: &AST) -> &AST {
+    match ast {
+        AST::Located(_, ast) | AST::Commented(_, ast) => strip(ast),
+        ast => ast,
+    }
+}
+
+/// The functions of the `recC`s and `recGroupC`s in `ast`, with an edge to each function they
+/// call by name. Calls from outside every function come from a `main` node. Functions that are
+/// passed around, or built with `fixC`, aren't followed.
+pub fn call_graph(ast: &AST) -> String {
+    let mut call_graph = CallGraph {
+        graph: Graph::new("node [shape=ellipse, fontname=monospace];"),
+        scope: Vec::new(),
+        calls: BTreeSet::new(),
+    };
+    let main = call_graph.graph.node("main", ", shape=doublecircle");
+    call_graph.visit(ast, main, Vec::new());
+    let mut graph = call_graph.graph;
+    for (caller, callee) in call_graph.calls {
+        graph.edge(caller, callee, "");
+    }
+    graph.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tokenize::TokenStream;
+
+    fn parse(code: &str) -> AST {
+        AST::build(&mut TokenStream::build_located(code.chars()))
+    }
+
+    #[test]
+    fn dot_ast() {
+        assert_eq!(
+            ast(&parse(r#"plusC(numC(1), idC("x"))"#)),
+            r#"digraph {
+  node [shape=box, fontname=monospace];
+  n0 [label="plusC"];
+  n1 [label="numC(1)"];
+  n0 -> n1 [label="lhs"];
+  n2 [label="idC(\"x\")"];
+  n0 -> n2 [label="rhs"];
+}
+"#
+        );
+    }
+
+    #[test]
+    fn dot_typed_ast() {
+        let checked = TypedAST::new(&parse(r#"fdC("x", numT, boolT, eqC(idC("x"), numC(1)))"#));
+        let dot = typed_ast(&checked);
+        assert!(dot.contains(r#"n0 [label="fdC(\"x\", numT, boolT)\n: funT(numT, boolT)"];"#));
+        assert!(dot.contains(r#"n2 [label="idC(\"x\")\n: numT"];"#));
+    }
+
+    #[test]
+    fn dot_call_graph() {
+        let code = std::fs::read_to_string("tests/inputs/mutual_recursion.txt").unwrap();
+        assert_eq!(
+            call_graph(&parse(&code)),
+            r#"digraph {
+  node [shape=ellipse, fontname=monospace];
+  n0 [label="main", shape=doublecircle];
+  n1 [label="is_even"];
+  n2 [label="is_odd"];
+  n0 -> n1;
+  n1 -> n2;
+  n2 -> n1;
+}
+"#
+        );
+    }
+
+    #[test]
+    fn dot_call_graph_shadowing() {
+        // The argument `f` of the inner function shadows the recursive function `f`.
+        let dot = call_graph(&parse(
+            r#"recC("f", "n", numT, numT,
+                appC(fdC("f", funT(numT, numT), numT, appC(idC("f"), numC(1))), idC("f")),
+                numC(0))"#,
+        ));
+        assert!(!dot.contains("->"));
+    }
+}
@@ -1,105 +1,528 @@
-use crate::{parse::AST, type_check::Type};
+use crate::{
+    parse::{Comments, AST},
+    type_check::Type,
+};
 
-use std::fmt::Write;
+/// The width `format` tries to keep lines within.
+pub const WIDTH: usize = 80;
+
+/// A document in the style of Wadler's "A prettier printer": text whose groups are laid out on one
+/// line when they fit and otherwise broken at each of their `Break`s.
+pub(crate) enum Doc {
+    Text(String),
+    /// The string when the enclosing group is flat, and otherwise a new line.
+    Break(&'static str),
+    /// A new line whatever the mode, which breaks every group around it.
+    HardLine,
+    /// Text put at the end of the line, right before the next new line, which breaks every group
+    /// around it.
+    LineSuffix(String),
+    /// Indents the lines that start inside the document by the given number of spaces.
+    Nest(usize, Box<Doc>),
+    Concat(Vec<Doc>),
+    Group(Box<Doc>),
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Mode {
+    Flat,
+    Break,
+}
 
 pub fn format(ast: &AST) -> String {
-    let mut output = String::new();
-    format_ast(&mut output, ast, 0);
+    format_with_width(ast, WIDTH)
+}
+
+/// Formats `ast` so that `AST::build` parses it back to `ast`, without `AST::Located` nodes. The
+/// comments of an `AST::Commented` are put back around the node and its arguments: leading and
+/// trailing comments on their own lines, and an end-of-line comment at the end of the line the
+/// node or argument ends on.
+pub fn format_with_width(ast: &AST, width: usize) -> String {
+    let mut output = render(&ast_doc(ast), width);
+    output.push('\n');
     output
 }
 
-fn write_line(output: &mut String, string: &str, tab_count: u32) {
-    let mut tabs = (0..tab_count).map(|_| "\t").collect::<String>();
-    tabs.push_str(string);
-    writeln!(output, "{}\n", tabs).unwrap();
+pub(crate) fn text(text: &str) -> Doc {
+    Doc::Text(text.to_string())
 }
 
-fn format_binary(output: &mut String, name: &str, lhs: &AST, rhs: &AST, tab_count: u32) {
-    write_line(output, format!("{}(", name).as_str(), tab_count);
-    format_ast(output, lhs, tab_count + 1);
-    write_line(output, ",", tab_count + 1);
-    format_ast(output, rhs, tab_count + 1);
-    write_line(output, ")", tab_count);
+fn string(string: &str) -> Doc {
+    Doc::Text(format!("\"{}\"", string))
 }
 
-fn format_ast(output: &mut String, ast: &AST, tab_count: u32) {
-    match ast {
-        AST::TrueLiteral => write_line(output, "trueC", tab_count),
-        AST::FalseLiteral => write_line(output, "falseC", tab_count),
-        AST::NumberLiteral(number) => {
-            write_line(output, format!("numC({})", number).as_str(), tab_count)
+/// `open` followed by `items` separated by commas and then `close`, with each item on its own
+/// line if they don't all fit on one.
+pub(crate) fn bracket(open: &str, items: Vec<Doc>, close: &str) -> Doc {
+    if items.is_empty() {
+        return Doc::Text(format!("{}{}", open, close));
+    }
+
+    let mut contents = vec![Doc::Break("")];
+    for (index, item) in items.into_iter().enumerate() {
+        if index != 0 {
+            contents.push(text(","));
+            contents.push(Doc::Break(" "));
+        }
+        contents.push(item);
+    }
+    Doc::Group(Box::new(Doc::Concat(vec![
+        text(open),
+        Doc::Nest(2, Box::new(Doc::Concat(contents))),
+        Doc::Break(""),
+        text(close),
+    ])))
+}
+
+/// `name(arguments)`
+fn call(name: &str, arguments: Vec<Doc>) -> Doc {
+    bracket(&format!("{}(", name), arguments, ")")
+}
+
+fn list(items: Vec<Doc>) -> Doc {
+    bracket("[", items, "]")
+}
+
+fn tuple(items: Vec<Doc>) -> Doc {
+    bracket("(", items, ")")
+}
+
+/// The comments of the arguments of a node, which are numbered in the order they start, put
+/// around the arguments as they are laid out.
+struct Arguments<'a> {
+    comments: &'a [(usize, Comments)],
+    started: usize,
+}
+
+impl<'a> Arguments<'a> {
+    fn new(comments: &'a [(usize, Comments)]) -> Arguments<'a> {
+        Arguments {
+            comments,
+            started: 0,
+        }
+    }
+
+    fn argument(&mut self, doc: impl FnOnce(&mut Arguments<'a>) -> Doc) -> Doc {
+        let index = self.started;
+        self.started += 1;
+        let doc = doc(self);
+        match self
+            .comments
+            .iter()
+            .find(|(argument, _)| *argument == index)
+        {
+            Some((_, comments)) => commented(comments, doc),
+            None => doc,
         }
-        AST::Plus(lhs, rhs) => format_binary(output, "plusC", lhs, rhs, tab_count),
-        AST::Multiply(lhs, rhs) => format_binary(output, "multC", lhs, rhs, tab_count),
-        AST::Equals(lhs, rhs) => format_binary(output, "eqC", lhs, rhs, tab_count),
-        AST::Identifier(id) => write_line(output, format!("numC({})", id).as_str(), tab_count),
-        AST::FunctionApplication(function_application) => format_binary(
-            output,
+    }
+
+    fn string(&mut self, string: &str) -> Doc {
+        self.argument(|_| self::string(string))
+    }
+
+    fn ty(&mut self, ty: &Type) -> Doc {
+        self.argument(|_| type_doc(ty))
+    }
+
+    fn list(&mut self, items: impl FnOnce(&mut Arguments<'a>) -> Vec<Doc>) -> Doc {
+        self.argument(|arguments| list(items(arguments)))
+    }
+
+    fn tuple(&mut self, items: impl FnOnce(&mut Arguments<'a>) -> Vec<Doc>) -> Doc {
+        self.argument(|arguments| tuple(items(arguments)))
+    }
+}
+
+/// `doc` with `comments` around it.
+fn commented(comments: &Comments, doc: Doc) -> Doc {
+    let mut docs = Vec::new();
+    for comment in &comments.leading {
+        docs.push(text(comment));
+        docs.push(Doc::HardLine);
+    }
+    docs.push(doc);
+    if let Some(comment) = &comments.end_of_line {
+        docs.push(Doc::LineSuffix(format!(" {}", comment)));
+    }
+    for comment in &comments.trailing {
+        docs.push(Doc::HardLine);
+        docs.push(text(comment));
+    }
+    Doc::Concat(docs)
+}
+
+fn ast_doc(ast: &AST) -> Doc {
+    node_doc(ast, &mut Arguments::new(&[]))
+}
+
+fn node_doc(ast: &AST, arguments: &mut Arguments) -> Doc {
+    match ast {
+        AST::TrueLiteral => text("trueC"),
+        AST::FalseLiteral => text("falseC"),
+        AST::NumberLiteral(number) => Doc::Text(format!("numC({})", number)),
+        AST::Plus(lhs, rhs) => call("plusC", vec![ast_doc(lhs), ast_doc(rhs)]),
+        AST::Multiply(lhs, rhs) => call("multC", vec![ast_doc(lhs), ast_doc(rhs)]),
+        AST::Equals(lhs, rhs) => call("eqC", vec![ast_doc(lhs), ast_doc(rhs)]),
+        AST::PhysicalEquals(lhs, rhs) => call("physEqC", vec![ast_doc(lhs), ast_doc(rhs)]),
+        AST::Set(reference, value) => call("setC", vec![ast_doc(reference), ast_doc(value)]),
+        AST::Reference(ast) => call("refC", vec![ast_doc(ast)]),
+        AST::Dereference(ast) => call("derefC", vec![ast_doc(ast)]),
+        AST::Fix(ast) => call("fixC", vec![ast_doc(ast)]),
+        AST::Identifier(id) => call("idC", vec![arguments.string(id)]),
+        AST::FunctionApplication(function_application) => call(
             "appC",
-            &*function_application.function,
-            &*function_application.argument,
-            tab_count,
+            vec![
+                ast_doc(&function_application.function),
+                ast_doc(&function_application.argument),
+            ],
         ),
-        AST::If(if_struct) => {
-            write_line(output, format!("{}(", "ifC").as_str(), tab_count);
-            format_ast(output, &*if_struct.condition, tab_count + 1);
-            write_line(output, ",", tab_count + 1);
-            format_ast(output, &*if_struct.then, tab_count + 1);
-            write_line(output, ",", tab_count + 1);
-            format_ast(output, &*if_struct.els, tab_count + 1);
-            write_line(output, ")", tab_count);
-        }
-        AST::RecursiveFunction(recursive_function) => {
-            write_line(output, format!("{}(", "recC").as_str(), tab_count);
-            write_line(
-                output,
-                format!("\"{}\",", recursive_function.function_name).as_str(),
-                tab_count + 1,
-            );
-            write_line(output, ",", tab_count + 1);
-            write_line(
-                output,
-                format!("\"{}\",", recursive_function.argument_name).as_str(),
-                tab_count,
-            );
-            write_line(output, ",", tab_count + 1);
-            format_type(output, &recursive_function.argument_type, tab_count + 1);
-            write_line(output, ",", tab_count + 1);
-            format_type(output, &recursive_function.return_type, tab_count + 1);
-            write_line(output, ",", tab_count + 1);
-            format_ast(output, &*recursive_function.body, tab_count + 1);
-            write_line(output, ",", tab_count + 1);
-            format_ast(output, &*recursive_function.function_use, tab_count + 1);
-            write_line(output, ")", tab_count);
+        AST::If(if_struct) => call(
+            "ifC",
+            vec![
+                ast_doc(&if_struct.condition),
+                ast_doc(&if_struct.then),
+                ast_doc(&if_struct.els),
+            ],
+        ),
+        AST::FunctionDefinition(function_definition) => call(
+            "fdC",
+            vec![
+                arguments.string(&function_definition.argument_name),
+                arguments.ty(&function_definition.argument_type),
+                arguments.ty(&function_definition.return_type),
+                ast_doc(&function_definition.body),
+            ],
+        ),
+        AST::RecursiveFunction(recursive_function) => call(
+            "recC",
+            vec![
+                arguments.string(&recursive_function.function_name),
+                arguments.string(&recursive_function.argument_name),
+                arguments.ty(&recursive_function.argument_type),
+                arguments.ty(&recursive_function.return_type),
+                ast_doc(&recursive_function.body),
+                ast_doc(&recursive_function.function_use),
+            ],
+        ),
+        AST::RecursiveFunctionGroup(recursive_function_group) => call(
+            "recGroupC",
+            vec![
+                arguments.list(|arguments| {
+                    recursive_function_group
+                        .functions
+                        .iter()
+                        .map(|function| {
+                            arguments.tuple(|arguments| {
+                                vec![
+                                    arguments.string(&function.function_name),
+                                    arguments.string(&function.argument_name),
+                                    arguments.ty(&function.argument_type),
+                                    arguments.ty(&function.return_type),
+                                    ast_doc(&function.body),
+                                ]
+                            })
+                        })
+                        .collect()
+                }),
+                ast_doc(&recursive_function_group.function_use),
+            ],
+        ),
+        AST::DataDeclaration(data_declaration) => call(
+            "dataC",
+            vec![
+                arguments.string(&data_declaration.name),
+                arguments.list(|arguments| {
+                    data_declaration
+                        .constructors
+                        .iter()
+                        .map(|constructor| {
+                            arguments.tuple(|arguments| {
+                                vec![
+                                    arguments.string(&constructor.name),
+                                    arguments.list(|arguments| {
+                                        constructor
+                                            .fields
+                                            .iter()
+                                            .map(|field| arguments.ty(field))
+                                            .collect()
+                                    }),
+                                ]
+                            })
+                        })
+                        .collect()
+                }),
+                ast_doc(&data_declaration.body),
+            ],
+        ),
+        AST::TypeDeclaration(type_declaration) => call(
+            "typeC",
+            vec![
+                arguments.string(&type_declaration.name),
+                arguments.ty(&type_declaration.ty),
+                ast_doc(&type_declaration.body),
+            ],
+        ),
+        AST::Located(_, ast) => node_doc(ast, arguments),
+        AST::Error => panic!("Can't format a node that failed to parse"),
+        AST::Commented(comments, ast) => commented(
+            comments,
+            node_doc(ast, &mut Arguments::new(&comments.arguments)),
+        ),
+        AST::Construct(construct) => call(
+            "conC",
+            vec![
+                arguments.string(&construct.constructor),
+                arguments.list(|_| construct.arguments.iter().map(ast_doc).collect()),
+            ],
+        ),
+        AST::Match(match_struct) => call(
+            "matchC",
+            vec![
+                ast_doc(&match_struct.scrutinee),
+                arguments.list(|arguments| {
+                    match_struct
+                        .branches
+                        .iter()
+                        .map(|branch| {
+                            arguments.tuple(|arguments| {
+                                vec![
+                                    arguments.string(&branch.constructor),
+                                    arguments.list(|arguments| {
+                                        branch
+                                            .variables
+                                            .iter()
+                                            .map(|variable| arguments.string(variable))
+                                            .collect()
+                                    }),
+                                    ast_doc(&branch.body),
+                                ]
+                            })
+                        })
+                        .collect()
+                }),
+            ],
+        ),
+    }
+}
+
+/// `ty` in the core syntax, on one line.
+pub(crate) fn type_text(ty: &Type) -> String {
+    render(&type_doc(ty), isize::MAX as usize)
+}
+
+fn type_doc(ty: &Type) -> Doc {
+    match ty {
+        Type::Boolean => text("boolT"),
+        Type::Number => text("numT"),
+        Type::Reference(ty) => call("refT", vec![type_doc(ty)]),
+        Type::Data(name) => call("dataT", vec![string(name)]),
+        Type::Named(name) => call("namedT", vec![string(name)]),
+        Type::Function { argument, ret } => call("funT", vec![type_doc(argument), type_doc(ret)]),
+    }
+}
+
+/// Lays `doc` out, choosing for each group whether it is flat or broken from left to right.
+pub(crate) fn render(doc: &Doc, width: usize) -> String {
+    let mut output = String::new();
+    let mut column = 0;
+    let mut line_suffix = String::new();
+    let mut stack = vec![(0, Mode::Break, doc)];
+    while let Some((indent, mode, doc)) = stack.pop() {
+        match doc {
+            Doc::Text(text) => {
+                output.push_str(text);
+                column = match text.rfind('\n') {
+                    // Block comments can span lines.
+                    Some(index) => text.len() - index - 1,
+                    None => column + text.len(),
+                };
+            }
+            Doc::Break(flat) if mode == Mode::Flat => {
+                output.push_str(flat);
+                column += flat.len();
+            }
+            Doc::Break(_) | Doc::HardLine => {
+                output.push_str(&std::mem::take(&mut line_suffix));
+                output.push('\n');
+                output.push_str(&" ".repeat(indent));
+                column = indent;
+            }
+            Doc::LineSuffix(text) => line_suffix.push_str(text),
+            Doc::Nest(extra, doc) => stack.push((indent + extra, mode, doc)),
+            Doc::Concat(docs) => stack.extend(docs.iter().rev().map(|doc| (indent, mode, doc))),
+            Doc::Group(doc) => {
+                let fits =
+                    mode == Mode::Flat || fits(width as isize - column as isize, doc, &stack);
+                let mode = if fits { Mode::Flat } else { Mode::Break };
+                stack.push((indent, mode, doc));
+            }
         }
-        AST::FunctionDefinition(function_definition) => {
-            write_line(output, format!("{}(", "fdC").as_str(), tab_count);
-            write_line(
-                output,
-                format!("\"{}\",", function_definition.argument_name).as_str(),
-                tab_count + 1,
-            );
-            write_line(output, ",", tab_count + 1);
-            format_type(output, &function_definition.argument_type, tab_count + 1);
-            write_line(output, ",", tab_count + 1);
-            format_type(output, &function_definition.return_type, tab_count + 1);
-            write_line(output, ",", tab_count + 1);
-            format_ast(output, &*function_definition.body, tab_count + 1);
-            write_line(output, ")", tab_count);
+    }
+    output.push_str(&line_suffix);
+    output
+}
+
+/// Whether `doc`, laid out flat, and what follows it up to the next line break fit in `remaining`
+/// columns. `rest` is the layout stack that `doc` was popped from.
+fn fits(mut remaining: isize, doc: &Doc, rest: &[(usize, Mode, &Doc)]) -> bool {
+    let mut stack = vec![(Mode::Flat, doc)];
+    let mut rest = rest.iter().rev();
+    while remaining >= 0 {
+        let (mode, doc) = match stack.pop() {
+            Some(next) => next,
+            None => match rest.next() {
+                Some((_, mode, doc)) => (*mode, *doc),
+                None => return true,
+            },
+        };
+        match doc {
+            Doc::Text(text) => remaining -= text.len() as isize,
+            Doc::Break(flat) if mode == Mode::Flat => remaining -= flat.len() as isize,
+            Doc::Break(_) => return true,
+            Doc::HardLine => return mode == Mode::Break,
+            Doc::LineSuffix(_) if mode == Mode::Flat => return false,
+            Doc::LineSuffix(_) => (),
+            Doc::Nest(_, doc) | Doc::Group(doc) => stack.push((mode, doc)),
+            Doc::Concat(docs) => stack.extend(docs.iter().rev().map(|doc| (mode, doc))),
         }
     }
+    false
 }
 
-fn format_type(output: &mut String, ty: &Type, tab_count: u32) {
-    match ty {
-        Type::Boolean => write_line(output, "boolT", tab_count),
-        Type::Number => write_line(output, "numT", tab_count),
-        Type::Function { argument, ret } => {
-            write_line(output, format!("{}(", "funT").as_str(), tab_count);
-            format_type(output, argument, tab_count + 1);
-            write_line(output, ",", tab_count + 1);
-            format_type(output, ret, tab_count + 1);
-            write_line(output, ")", tab_count);
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        read::{self, tests::SYNTAX_ERRORS},
+        tokenize::TokenStream,
+    };
+
+    fn parse(code: &str) -> AST {
+        AST::build(&mut TokenStream::build(code.chars()))
+    }
+
+    #[test]
+    fn format_flat() {
+        assert_eq!(
+            format(&parse("plusC( numC(1),\n numC(2) )")),
+            "plusC(numC(1), numC(2))\n"
+        );
+    }
+
+    #[test]
+    fn format_broken() {
+        let ast = parse(r#"fdC("x", numT, numT, plusC(idC("x"), multC(idC("x"), numC(2))))"#);
+        assert_eq!(
+            format_with_width(&ast, 50),
+            r#"fdC(
+  "x",
+  numT,
+  numT,
+  plusC(idC("x"), multC(idC("x"), numC(2)))
+)
+"#
+        );
+    }
+
+    #[test]
+    fn format_comments() {
+        let code = "#| leading |#\nplusC(numC(1), ; one\n numC(2)) ; trailing";
+        let formatted = format(&crate::parse_commented(code));
+        assert_eq!(
+            formatted,
+            "#| leading |#\nplusC(\n  numC(1), ; one\n  numC(2)\n) ; trailing\n"
+        );
+        assert_eq!(format(&crate::parse_commented(&formatted)), formatted);
+    }
+
+    #[test]
+    fn format_comment_before_closing_parenthesis() {
+        let code = "plusC(plusC(numC(1), numC(2)\n; two\n), numC(3))";
+        let formatted = format(&crate::parse_commented(code));
+        assert_eq!(
+            formatted,
+            "plusC(\n  plusC(\n    numC(1),\n    numC(2)\n    ; two\n  ),\n  numC(3)\n)\n"
+        );
+        assert_eq!(format(&crate::parse_commented(&formatted)), formatted);
+    }
+
+    #[test]
+    fn format_argument_comments() {
+        let code = r#"dataC("T", [("A", [numT ; a
+]), ; b
+("B", [])], matchC(conC("A", [numC(1)]), [("A", ["x" ; x
+], idC("x")), ("B", [], numC(0))]))"#;
+        let formatted = format(&crate::parse_commented(code));
+        assert_eq!(
+            formatted,
+            r#"dataC(
+  "T",
+  [
+    (
+      "A",
+      [
+        numT ; a
+      ]
+    ), ; b
+    ("B", [])
+  ],
+  matchC(
+    conC("A", [numC(1)]),
+    [
+      (
+        "A",
+        [
+          "x" ; x
+        ],
+        idC("x")
+      ),
+      ("B", [], numC(0))
+    ]
+  )
+)
+"#
+        );
+        assert_eq!(format(&crate::parse_commented(&formatted)), formatted);
+
+        let code = r#"fdC("x", numT, numT ; type
+, recGroupC([("f" ; name
+, "y", numT, numT, idC("y"))], idC("x")))"#;
+        assert_eq!(
+            format(&crate::parse_commented(code)),
+            r#"fdC(
+  "x",
+  numT,
+  numT, ; type
+  recGroupC(
+    [
+      (
+        "f", ; name
+        "y",
+        numT,
+        numT,
+        idC("y")
+      )
+    ],
+    idC("x")
+  )
+)
+"#
+        );
+    }
+
+    /// Every input without a syntax error is formatted, at several widths, to code that parses
+    /// back to the same `AST`.
+    #[test]
+    fn format_round_trip() {
+        for (name, code) in read::tests::inputs(&SYNTAX_ERRORS) {
+            let ast = parse(&code);
+            for width in [0, 40, WIDTH, usize::MAX / 2] {
+                let formatted = format_with_width(&ast, width);
+                assert_eq!(parse(&formatted), ast, "{} at width {}", name, width);
+                if width == WIDTH {
+                    assert!(formatted.lines().all(|line| line.len() <= WIDTH));
+                }
+            }
         }
     }
 }
@@ -1,11 +1,27 @@
-use crate::parse::AST;
-use std::collections::HashMap;
+use crate::parse::{MatchBranch, AST, WILDCARD};
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    fmt::{self, Display},
+    rc::Rc,
+    time::Instant,
+    vec,
+};
 
 #[derive(PartialEq, Debug, Clone)]
 pub enum Data {
     Boolean(bool),
     Number(i64),
     Function(Function),
+    Constructed(Constructed),
+    /// A heap allocated cell created by `refC`. Cloning the `Data` aliases the cell.
+    Reference(Rc<RefCell<Data>>),
+    /// The value of `fixC(f)`, where `f` is the contained function. It is unrolled one step each
+    /// time it is applied.
+    Fixpoint(Function),
+    /// An argument that has not been evaluated yet. Only bound to variables, since looking a
+    /// variable up forces it.
+    Thunk(Rc<RefCell<Thunk>>),
 }
 
 impl Data {
@@ -25,6 +41,22 @@ impl Data {
         }
     }
 
+    fn constructed(self) -> Constructed {
+        if let Data::Constructed(constructed) = self {
+            constructed
+        } else {
+            panic!("Trying to access a non-active variant of Data enum")
+        }
+    }
+
+    fn reference(self) -> Rc<RefCell<Data>> {
+        if let Data::Reference(reference) = self {
+            reference
+        } else {
+            panic!("Trying to access a non-active variant of Data enum")
+        }
+    }
+
     fn function(self) -> Function {
         if let Data::Function(function) = self {
             function
@@ -34,31 +66,599 @@ impl Data {
     }
 }
 
+/// Prints `data` the way `vm::Value`s are printed.
+impl Display for Data {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Data::Boolean(boolean) => write!(f, "{}", boolean),
+            Data::Number(number) => write!(f, "{}", number),
+            Data::Function(_) => write!(f, "<function>"),
+            Data::Fixpoint(_) => write!(f, "<fixpoint>"),
+            Data::Constructed(constructed) => {
+                let fields: Vec<String> = constructed.fields.iter().map(Data::to_string).collect();
+                write!(f, "{}({})", constructed.constructor, fields.join(", "))
+            }
+            Data::Reference(reference) => write!(f, "refC({})", reference.borrow()),
+            Data::Thunk(thunk) => match &thunk.borrow().value {
+                Some(value) => write!(f, "{}", value),
+                None => write!(f, "<unevaluated>"),
+            },
+        }
+    }
+}
+
 #[derive(PartialEq, Debug, Clone)]
 pub struct Function {
     pub argument_name: String,
     pub body: AST,
 }
 
+/// An argument delayed by `Strategy::ByName` or `Strategy::ByNeed`.
+#[derive(PartialEq, Debug)]
+pub struct Thunk {
+    expression: AST,
+    /// The innermost binding, when the thunk was created, of every free variable of `expression`.
+    environment: Vec<(String, Data)>,
+    /// Whether `value` is kept once `expression` has been evaluated.
+    memoize: bool,
+    value: Option<Data>,
+}
+
+/// When the argument of `appC` is evaluated.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Strategy {
+    /// Before the function is applied.
+    ByValue,
+    /// Every time the function uses it.
+    ByName,
+    /// The first time the function uses it.
+    ByNeed,
+}
+
+impl Default for Strategy {
+    fn default() -> Self {
+        Strategy::ByValue
+    }
+}
+
+/// A value built by `conC`.
+#[derive(PartialEq, Debug, Clone)]
+pub struct Constructed {
+    pub constructor: String,
+    pub fields: Vec<Data>,
+}
+
+/// Limits on how much work evaluation may do. `None` means the resource is unlimited.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Budget {
+    pub max_steps: Option<u64>,
+    /// The most frames the continuation may hold, which grows with every nested call.
+    pub max_depth: Option<usize>,
+    pub deadline: Option<Instant>,
+}
+
+/// The resource a `Budget` ran out of.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Limit {
+    Steps,
+    Depth,
+    Time,
+}
+
+/// Evaluation was stopped after `steps` steps because it exceeded its `Budget`.
+#[derive(Debug, PartialEq)]
+pub struct OutOfFuel {
+    pub steps: u64,
+    pub limit: Limit,
+}
+
+impl Display for OutOfFuel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let limit = match self.limit {
+            Limit::Steps => "step",
+            Limit::Depth => "recursion depth",
+            Limit::Time => "time",
+        };
+        write!(
+            f,
+            "Out of fuel: exceeded the {} limit after {} steps",
+            limit, self.steps
+        )
+    }
+}
+
+/// Checking the clock is comparatively slow, so the deadline is only checked this often.
+const STEPS_PER_DEADLINE_CHECK: u64 = 1024;
+
 pub fn interpret(ast: &AST) -> Data {
-    interpreter(ast, &mut HashMap::new())
+    interpret_with_budget(ast, &Budget::default()).unwrap()
 }
 
-fn interpreter(ast: &AST, map: &mut HashMap<String, Vec<Data>>) -> Data {
-    match ast {
-        AST::NumberLiteral(number) => Data::Number(*number),
-        AST::Plus(op1, op2) => {
-            Data::Number(interpreter(op1, map).number() + interpreter(op2, map).number())
+pub fn interpret_with_budget(ast: &AST, budget: &Budget) -> Result<Data, OutOfFuel> {
+    interpret_with_strategy(ast, Strategy::default(), budget)
+}
+
+pub fn interpret_with_strategy(
+    ast: &AST,
+    strategy: Strategy,
+    budget: &Budget,
+) -> Result<Data, OutOfFuel> {
+    interpreter(ast.clone(), &mut HashMap::new(), strategy, budget, None)
+}
+
+/// Callbacks made by the interpreter as it evaluates, for building tools such as tracers and
+/// coverage reports. Every method does nothing by default.
+pub trait Observer {
+    /// Called before `ast` is evaluated in `environment`.
+    fn enter(&mut self, _ast: &AST, _environment: &Environment) {}
+
+    /// Called once the node most recently entered, of those that haven't been left, has evaluated
+    /// to `data`. Nodes are left in the reverse of the order they were entered in, so an observer
+    /// that needs the node keeps its own stack of them. While there are nodes left to leave, a tail
+    /// call still grows the continuation by the frames that leave them.
+    fn leave(&mut self, _data: &Data) {}
+
+    /// Called when `function` is applied to `argument`.
+    fn apply(&mut self, _function: &Data, _argument: &Data) {}
+
+    /// Called when the identifier `name` is found to be bound to `data`.
+    fn lookup(&mut self, _name: &str, _data: &Data) {}
+}
+
+/// The variables in scope while the interpreter runs.
+pub struct Environment<'a> {
+    map: &'a HashMap<String, Vec<Data>>,
+}
+
+impl<'a> Environment<'a> {
+    pub fn lookup(&self, name: &str) -> Option<&'a Data> {
+        self.map
+            .get(name)
+            .and_then(|data_values| data_values.last())
+    }
+
+    /// The innermost binding of every variable, sorted by name.
+    pub fn bindings(&self) -> Vec<(&'a str, &'a Data)> {
+        let mut bindings: Vec<(&str, &Data)> = self
+            .map
+            .iter()
+            .map(|(name, data_values)| (name.as_str(), data_values.last().unwrap()))
+            .collect();
+        bindings.sort_by_key(|(name, _)| *name);
+        bindings
+    }
+}
+
+pub fn interpret_with_observer(
+    ast: &AST,
+    budget: &Budget,
+    observer: &mut dyn Observer,
+) -> Result<Data, OutOfFuel> {
+    interpreter(
+        ast.clone(),
+        &mut HashMap::new(),
+        Strategy::default(),
+        budget,
+        Some(observer),
+    )
+}
+
+/// The operators whose operands are both evaluated before they are combined.
+enum BinaryOperator {
+    Plus,
+    Multiply,
+    Equals,
+    PhysicalEquals,
+    Set,
+}
+
+/// The work left to do with the value currently being computed. The interpreter keeps these on
+/// the heap instead of recursing, so the depth of a computation is bounded only by memory.
+enum Frame {
+    /// Evaluate the right operand once the left one is known.
+    BinaryRight(BinaryOperator, AST),
+    /// Combine the already evaluated left operand with the value.
+    BinaryLeft(BinaryOperator, Data),
+    If {
+        then: AST,
+        els: AST,
+    },
+    Reference,
+    Dereference,
+    Fix,
+    /// Evaluate the argument of an application once the function is known.
+    Argument(AST),
+    /// Apply the already evaluated function to the value.
+    Apply(Data),
+    /// Apply the value to the already evaluated argument.
+    ApplyTo(Data),
+    Construct {
+        constructor: String,
+        fields: Vec<Data>,
+        remaining: vec::IntoIter<AST>,
+    },
+    Match(Vec<MatchBranch>),
+    /// Pop the innermost binding of a name once the value of its scope is known.
+    Unbind(String),
+    /// Tell the observer that the innermost node that it entered has been evaluated.
+    Leave,
+    /// Keep the value of a forced `Strategy::ByNeed` thunk.
+    Memoize(Rc<RefCell<Thunk>>),
+}
+
+enum State {
+    Evaluate(AST),
+    Return(Data),
+}
+
+fn interpreter(
+    ast: AST,
+    map: &mut HashMap<String, Vec<Data>>,
+    strategy: Strategy,
+    budget: &Budget,
+    mut observer: Option<&mut dyn Observer>,
+) -> Result<Data, OutOfFuel> {
+    let mut continuation = Vec::new();
+    let mut state = State::Evaluate(ast);
+    let mut steps: u64 = 0;
+    loop {
+        let limit = if matches!(budget.max_steps, Some(max_steps) if steps >= max_steps) {
+            Some(Limit::Steps)
+        } else if matches!(budget.max_depth, Some(max_depth) if continuation.len() > max_depth) {
+            Some(Limit::Depth)
+        } else if steps % STEPS_PER_DEADLINE_CHECK == 0
+            && matches!(budget.deadline, Some(deadline) if Instant::now() >= deadline)
+        {
+            Some(Limit::Time)
+        } else {
+            None
+        };
+        if let Some(limit) = limit {
+            return Err(OutOfFuel { steps, limit });
         }
+
+        state = match state {
+            State::Evaluate(ast) => match &mut observer {
+                Some(observer) => {
+                    observer.enter(&ast, &Environment { map });
+                    continuation.push(Frame::Leave);
+                    let name = match &ast {
+                        AST::Identifier(name) => Some(name.clone()),
+                        _ => None,
+                    };
+                    let state = evaluate(ast, map, &mut continuation);
+                    if let (Some(name), State::Return(data)) = (name, &state) {
+                        observer.lookup(&name, data);
+                    }
+                    state
+                }
+                None => evaluate(ast, map, &mut continuation),
+            },
+            State::Return(data) => match continuation.pop() {
+                Some(frame) => {
+                    if let Some(observer) = &mut observer {
+                        match &frame {
+                            Frame::Leave => observer.leave(&data),
+                            Frame::Apply(function) => observer.apply(function, &data),
+                            Frame::ApplyTo(argument) => observer.apply(&data, argument),
+                            _ => (),
+                        }
+                    }
+                    resume(frame, data, map, strategy, &mut continuation)
+                }
+                None => return Ok(data),
+            },
+        };
+        steps += 1;
+    }
+}
+
+/// Takes one step towards the value of `ast`, pushing whatever remains to be done with it.
+fn evaluate(
+    ast: AST,
+    map: &mut HashMap<String, Vec<Data>>,
+    continuation: &mut Vec<Frame>,
+) -> State {
+    match ast {
+        AST::NumberLiteral(number) => State::Return(Data::Number(number)),
+        AST::TrueLiteral => State::Return(Data::Boolean(true)),
+        AST::FalseLiteral => State::Return(Data::Boolean(false)),
+        AST::Plus(op1, op2) => evaluate_binary(BinaryOperator::Plus, *op1, *op2, continuation),
         AST::Multiply(op1, op2) => {
-            Data::Number(interpreter(&*op1, map).number() * interpreter(&*op2, map).number())
+            evaluate_binary(BinaryOperator::Multiply, *op1, *op2, continuation)
+        }
+        AST::Equals(lhs, rhs) => evaluate_binary(BinaryOperator::Equals, *lhs, *rhs, continuation),
+        AST::PhysicalEquals(lhs, rhs) => {
+            evaluate_binary(BinaryOperator::PhysicalEquals, *lhs, *rhs, continuation)
+        }
+        AST::Set(reference, value) => {
+            evaluate_binary(BinaryOperator::Set, *reference, *value, continuation)
+        }
+        AST::Reference(ast) => {
+            continuation.push(Frame::Reference);
+            State::Evaluate(*ast)
+        }
+        AST::Dereference(ast) => {
+            continuation.push(Frame::Dereference);
+            State::Evaluate(*ast)
+        }
+        AST::Fix(ast) => {
+            continuation.push(Frame::Fix);
+            State::Evaluate(*ast)
+        }
+        AST::If(if_struct) => {
+            continuation.push(Frame::If {
+                then: *if_struct.then,
+                els: *if_struct.els,
+            });
+            State::Evaluate(*if_struct.condition)
+        }
+        AST::Identifier(string) => {
+            match map
+                .get(&string)
+                .unwrap_or_else(|| panic!("Unable to find identifier: {:?}", string.as_str()))
+                .last()
+                .unwrap()
+                .clone()
+            {
+                Data::Thunk(thunk) => force(thunk, map, continuation),
+                data => State::Return(data),
+            }
+        }
+        AST::FunctionDefinition(function_definition) => State::Return(Data::Function(Function {
+            argument_name: function_definition.argument_name,
+            body: *function_definition.body,
+        })),
+        AST::FunctionApplication(function_application) => {
+            continuation.push(Frame::Argument(*function_application.argument));
+            State::Evaluate(*function_application.function)
+        }
+        AST::RecursiveFunction(recursive_function) => {
+            bind_scoped(
+                map,
+                recursive_function.function_name,
+                Data::Function(Function {
+                    argument_name: recursive_function.argument_name,
+                    body: *recursive_function.body,
+                }),
+                continuation,
+            ); // add the function to the current scope
+            State::Evaluate(*recursive_function.function_use)
+        }
+        AST::RecursiveFunctionGroup(recursive_function_group) => {
+            // Every function is bound before the use is evaluated, so each body can call any
+            // function in the group.
+            for function in recursive_function_group.functions {
+                bind_scoped(
+                    map,
+                    function.function_name,
+                    Data::Function(Function {
+                        argument_name: function.argument_name,
+                        body: *function.body,
+                    }),
+                    continuation,
+                );
+            }
+            State::Evaluate(*recursive_function_group.function_use)
+        }
+        AST::DataDeclaration(data_declaration) => State::Evaluate(*data_declaration.body),
+        AST::TypeDeclaration(type_declaration) => State::Evaluate(*type_declaration.body),
+        AST::Located(_, ast) | AST::Commented(_, ast) => State::Evaluate(*ast),
+        AST::Error => panic!("Can't interpret a node that failed to parse"),
+        AST::Construct(construct) => resume_construct(
+            construct.constructor,
+            Vec::new(),
+            construct.arguments.into_iter(),
+            continuation,
+        ),
+        AST::Match(match_struct) => {
+            continuation.push(Frame::Match(match_struct.branches));
+            State::Evaluate(*match_struct.scrutinee)
+        }
+    }
+}
+
+/// Evaluates `thunk`'s expression in the environment it was created in, unless its value is
+/// already known.
+fn force(
+    thunk: Rc<RefCell<Thunk>>,
+    map: &mut HashMap<String, Vec<Data>>,
+    continuation: &mut Vec<Frame>,
+) -> State {
+    let delayed = thunk.borrow();
+    if let Some(value) = &delayed.value {
+        return State::Return(value.clone());
+    }
+
+    if delayed.memoize {
+        continuation.push(Frame::Memoize(thunk.clone()));
+    }
+    for (name, data) in &delayed.environment {
+        bind(map, name, data.clone());
+        continuation.push(Frame::Unbind(name.clone()));
+    }
+    State::Evaluate(delayed.expression.clone())
+}
+
+/// Adds the variables that `ast` uses but doesn't bind, other than those in `bound`, to `free`,
+/// in the order of their first use.
+fn free_variables(ast: &AST, bound: &mut Vec<String>, free: &mut Vec<String>) {
+    match ast {
+        AST::NumberLiteral(_) | AST::TrueLiteral | AST::FalseLiteral | AST::Error => (),
+        AST::Plus(lhs, rhs)
+        | AST::Multiply(lhs, rhs)
+        | AST::Equals(lhs, rhs)
+        | AST::PhysicalEquals(lhs, rhs)
+        | AST::Set(lhs, rhs) => {
+            free_variables(lhs, bound, free);
+            free_variables(rhs, bound, free);
+        }
+        AST::Reference(ast)
+        | AST::Dereference(ast)
+        | AST::Fix(ast)
+        | AST::Located(_, ast)
+        | AST::Commented(_, ast) => free_variables(ast, bound, free),
+        AST::If(if_struct) => {
+            free_variables(&if_struct.condition, bound, free);
+            free_variables(&if_struct.then, bound, free);
+            free_variables(&if_struct.els, bound, free);
+        }
+        AST::Identifier(identifier) => {
+            if !bound.contains(identifier) && !free.contains(identifier) {
+                free.push(identifier.clone());
+            }
+        }
+        AST::FunctionApplication(function_application) => {
+            free_variables(&function_application.function, bound, free);
+            free_variables(&function_application.argument, bound, free);
+        }
+        AST::FunctionDefinition(function_definition) => {
+            bound.push(function_definition.argument_name.clone());
+            free_variables(&function_definition.body, bound, free);
+            bound.pop();
+        }
+        AST::RecursiveFunction(recursive_function) => {
+            bound.push(recursive_function.function_name.clone());
+            bound.push(recursive_function.argument_name.clone());
+            free_variables(&recursive_function.body, bound, free);
+            bound.pop();
+            free_variables(&recursive_function.function_use, bound, free);
+            bound.pop();
+        }
+        AST::RecursiveFunctionGroup(recursive_function_group) => {
+            let length = bound.len();
+            for function in &recursive_function_group.functions {
+                bound.push(function.function_name.clone());
+            }
+            for function in &recursive_function_group.functions {
+                bound.push(function.argument_name.clone());
+                free_variables(&function.body, bound, free);
+                bound.pop();
+            }
+            free_variables(&recursive_function_group.function_use, bound, free);
+            bound.truncate(length);
+        }
+        AST::DataDeclaration(data_declaration) => {
+            free_variables(&data_declaration.body, bound, free)
+        }
+        AST::TypeDeclaration(type_declaration) => {
+            free_variables(&type_declaration.body, bound, free)
+        }
+        AST::Construct(construct) => {
+            for argument in &construct.arguments {
+                free_variables(argument, bound, free);
+            }
+        }
+        AST::Match(match_struct) => {
+            free_variables(&match_struct.scrutinee, bound, free);
+            for branch in &match_struct.branches {
+                let length = bound.len();
+                bound.extend(branch.variables.iter().cloned());
+                free_variables(&branch.body, bound, free);
+                bound.truncate(length);
+            }
+        }
+    }
+}
+
+fn evaluate_binary(
+    operator: BinaryOperator,
+    lhs: AST,
+    rhs: AST,
+    continuation: &mut Vec<Frame>,
+) -> State {
+    continuation.push(Frame::BinaryRight(operator, rhs));
+    State::Evaluate(lhs)
+}
+
+/// Continues the computation that was waiting on `frame` with `data`, the value it was waiting for.
+fn resume(
+    frame: Frame,
+    data: Data,
+    map: &mut HashMap<String, Vec<Data>>,
+    strategy: Strategy,
+    continuation: &mut Vec<Frame>,
+) -> State {
+    match frame {
+        Frame::BinaryRight(operator, rhs) => {
+            continuation.push(Frame::BinaryLeft(operator, data));
+            State::Evaluate(rhs)
+        }
+        Frame::BinaryLeft(operator, lhs_data) => State::Return(binary(operator, lhs_data, data)),
+        Frame::If { then, els } => {
+            if data.boolean() {
+                State::Evaluate(then)
+            } else {
+                State::Evaluate(els)
+            }
+        }
+        Frame::Reference => State::Return(Data::Reference(Rc::new(RefCell::new(data)))),
+        Frame::Dereference => State::Return(data.reference().borrow().clone()),
+        Frame::Fix => State::Return(Data::Fixpoint(data.function())),
+        Frame::Argument(argument) => {
+            continuation.push(Frame::Apply(data));
+            match strategy {
+                Strategy::ByValue => State::Evaluate(argument),
+                Strategy::ByName | Strategy::ByNeed => {
+                    let mut free = Vec::new();
+                    free_variables(&argument, &mut Vec::new(), &mut free);
+                    State::Return(Data::Thunk(Rc::new(RefCell::new(Thunk {
+                        environment: free
+                            .into_iter()
+                            .filter_map(|name| {
+                                let data = map.get(&name)?.last()?.clone();
+                                Some((name, data))
+                            })
+                            .collect(),
+                        expression: argument,
+                        memoize: strategy == Strategy::ByNeed,
+                        value: None,
+                    }))))
+                }
+            }
+        }
+        Frame::Apply(function) => apply(function, data, map, continuation),
+        Frame::ApplyTo(argument) => apply(data, argument, map, continuation),
+        Frame::Construct {
+            constructor,
+            mut fields,
+            remaining,
+        } => {
+            fields.push(data);
+            resume_construct(constructor, fields, remaining, continuation)
+        }
+        Frame::Match(branches) => {
+            let constructed = data.constructed();
+            let branch = branches
+                .into_iter()
+                .find(|branch| {
+                    branch.constructor == constructed.constructor || branch.constructor == WILDCARD
+                })
+                .unwrap_or_else(|| panic!("No branch matches '{}'", constructed.constructor));
+
+            // A wildcard branch has no variables, so nothing is bound for it.
+            for (variable, field) in branch.variables.into_iter().zip(constructed.fields) {
+                bind_scoped(map, variable, field, continuation);
+            }
+            State::Evaluate(*branch.body)
+        }
+        Frame::Unbind(name) => {
+            unbind(map, &name);
+            State::Return(data)
         }
-        AST::TrueLiteral => Data::Boolean(true),
-        AST::FalseLiteral => Data::Boolean(false),
-        AST::Equals(lhs, rhs) => {
-            let lhs_data = interpreter(lhs, map);
-            let rhs_data = interpreter(rhs, map);
+        Frame::Leave => State::Return(data),
+        Frame::Memoize(thunk) => {
+            thunk.borrow_mut().value = Some(data.clone());
+            State::Return(data)
+        }
+    }
+}
 
+fn binary(operator: BinaryOperator, lhs_data: Data, rhs_data: Data) -> Data {
+    match operator {
+        BinaryOperator::Plus => Data::Number(lhs_data.number() + rhs_data.number()),
+        BinaryOperator::Multiply => Data::Number(lhs_data.number() * rhs_data.number()),
+        BinaryOperator::Equals => {
             if let Data::Function(_) = lhs_data {
                 panic!("First argument to EqC is a FunT")
             } else if let Data::Function(_) = rhs_data {
@@ -67,66 +667,102 @@ fn interpreter(ast: &AST, map: &mut HashMap<String, Vec<Data>>) -> Data {
                 Data::Boolean(lhs_data == rhs_data)
             }
         }
-        AST::If(if_struct) => {
-            if interpreter(&*if_struct.condition, map).boolean() {
-                interpreter(&*if_struct.then, map)
-            } else {
-                interpreter(&*if_struct.els, map)
-            }
+        BinaryOperator::PhysicalEquals => {
+            Data::Boolean(Rc::ptr_eq(&lhs_data.reference(), &rhs_data.reference()))
         }
-        AST::Identifier(string) => (*map
-            .get(string)
-            .unwrap_or_else(|| panic!("Unable to find identifier: {:?}", string.as_str())))
-        .last()
-        .unwrap()
-        .clone(),
-        AST::FunctionDefinition(function_definition) => Data::Function(Function {
-            argument_name: function_definition.argument_name.clone(),
-            body: *function_definition.body.clone(),
-        }),
-        AST::FunctionApplication(function_application) => {
-            let function = interpreter(&*function_application.function, map).function();
-            let argument = interpreter(&*function_application.argument, map);
+        BinaryOperator::Set => {
+            *lhs_data.reference().borrow_mut() = rhs_data.clone();
+            rhs_data
+        }
+    }
+}
 
-            map.entry(function.argument_name.clone())
-                .or_insert_with(Vec::new)
-                .push(argument);
+fn resume_construct(
+    constructor: String,
+    fields: Vec<Data>,
+    mut remaining: vec::IntoIter<AST>,
+    continuation: &mut Vec<Frame>,
+) -> State {
+    match remaining.next() {
+        Some(argument) => {
+            continuation.push(Frame::Construct {
+                constructor,
+                fields,
+                remaining,
+            });
+            State::Evaluate(argument)
+        }
+        None => State::Return(Data::Constructed(Constructed {
+            constructor,
+            fields,
+        })),
+    }
+}
 
-            let return_data = interpreter(&function.body, map);
+fn apply(
+    function: Data,
+    argument: Data,
+    map: &mut HashMap<String, Vec<Data>>,
+    continuation: &mut Vec<Frame>,
+) -> State {
+    match function {
+        Data::Function(function) => {
+            bind_scoped(map, function.argument_name, argument, continuation);
+            State::Evaluate(function.body)
+        }
+        Data::Fixpoint(generator) => {
+            // fixC(f)(argument) = f(fixC(f))(argument). The binding of f's argument has to outlive
+            // the call of the function f returns, since that function's body refers to it.
+            bind_scoped(
+                map,
+                generator.argument_name.clone(),
+                Data::Fixpoint(generator.clone()),
+                continuation,
+            );
+            continuation.push(Frame::ApplyTo(argument));
+            State::Evaluate(generator.body)
+        }
+        _ => panic!("Trying to access a non-active variant of Data enum"),
+    }
+}
 
-            match map.get_mut(&function.argument_name) {
-                Some(data_values) => {
-                    data_values.pop();
-                    if data_values.is_empty() {
-                        map.remove(&function.argument_name);
-                    }
-                }
-                None => panic!(),
-            }
+fn bind(map: &mut HashMap<String, Vec<Data>>, name: &str, data: Data) {
+    map.entry(name.to_string()).or_default().push(data);
+}
 
-            return_data
-        }
-        AST::RecursiveFunction(recursive_function) => {
-            map.entry(recursive_function.function_name.clone())
-                .or_insert_with(Vec::new)
-                .push(Data::Function(Function {
-                    argument_name: recursive_function.argument_name.clone(),
-                    body: *recursive_function.body.clone(),
-                })); // add the function to the current scope
-            let return_data = interpreter(&*recursive_function.function_use, map);
-
-            match map.get_mut(&recursive_function.function_name) {
-                Some(data_values) => {
-                    data_values.pop();
-                    if data_values.is_empty() {
-                        map.remove(&recursive_function.function_name);
-                    }
-                }
-                None => panic!(),
-            }
+/// Binds `name` to `data` until the value of the computation in progress is known. When that
+/// computation is in tail position in a scope of `name`, the binding of that scope is replaced
+/// instead. This can't be observed, since the replaced binding would be shadowed until it was
+/// unbound, and it keeps tail calls from growing the bindings. `Frame::Leave`s don't see the
+/// bindings, so they don't stop a computation from being in tail position.
+fn bind_scoped(
+    map: &mut HashMap<String, Vec<Data>>,
+    name: String,
+    data: Data,
+    continuation: &mut Vec<Frame>,
+) {
+    let in_tail_position = continuation
+        .iter()
+        .rev()
+        .take_while(|frame| matches!(frame, Frame::Unbind(_) | Frame::Leave))
+        .any(|frame| matches!(frame, Frame::Unbind(bound) if *bound == name));
+    if in_tail_position {
+        *map.get_mut(&name).unwrap().last_mut().unwrap() = data;
+    } else {
+        bind(map, &name, data);
+        continuation.push(Frame::Unbind(name));
+    }
+}
 
-            return_data
+fn unbind(map: &mut HashMap<String, Vec<Data>>, name: &str) {
+    match map.get_mut(name) {
+        Some(data_values) => {
+            data_values.pop();
+            if data_values.is_empty() {
+                map.remove(name);
+            }
         }
+        None => panic!(),
     }
 }
 
@@ -359,4 +995,339 @@ mod tests {
             Data::Number(6)
         )
     }
+
+    /// `recC` summing the numbers from `n` down to 0 without tail calls.
+    fn summation(n: i64) -> AST {
+        AST::RecursiveFunction(RecursiveFunction {
+            function_name: "sum".to_string(),
+            argument_name: "n".to_string(),
+            argument_type: Type::Number,
+            return_type: Type::Number,
+            body: Box::new(AST::If(If {
+                condition: Box::new(AST::Equals(
+                    Box::new(AST::Identifier("n".to_string())),
+                    Box::new(AST::NumberLiteral(0)),
+                )),
+                then: Box::new(AST::NumberLiteral(0)),
+                els: Box::new(AST::Plus(
+                    Box::new(AST::Identifier("n".to_string())),
+                    Box::new(AST::FunctionApplication(FunctionApplication {
+                        function: Box::new(AST::Identifier("sum".to_string())),
+                        argument: Box::new(AST::Plus(
+                            Box::new(AST::Identifier("n".to_string())),
+                            Box::new(AST::NumberLiteral(-1)),
+                        )),
+                    })),
+                )),
+            })),
+            function_use: Box::new(AST::FunctionApplication(FunctionApplication {
+                function: Box::new(AST::Identifier("sum".to_string())),
+                argument: Box::new(AST::NumberLiteral(n)),
+            })),
+        })
+    }
+
+    #[test]
+    fn interpret_recc_million_iterations() {
+        assert_eq!(
+            interpret(&summation(1_000_000)),
+            Data::Number(500_000_500_000)
+        )
+    }
+
+    /// `recC` that calls itself forever.
+    fn loop_forever() -> AST {
+        AST::RecursiveFunction(RecursiveFunction {
+            function_name: "loop".to_string(),
+            argument_name: "n".to_string(),
+            argument_type: Type::Number,
+            return_type: Type::Number,
+            body: Box::new(AST::FunctionApplication(FunctionApplication {
+                function: Box::new(AST::Identifier("loop".to_string())),
+                argument: Box::new(AST::Identifier("n".to_string())),
+            })),
+            function_use: Box::new(AST::FunctionApplication(FunctionApplication {
+                function: Box::new(AST::Identifier("loop".to_string())),
+                argument: Box::new(AST::NumberLiteral(0)),
+            })),
+        })
+    }
+
+    /// Like `loop_forever`, but the recursive call isn't in tail position.
+    fn recurse_forever() -> AST {
+        AST::RecursiveFunction(RecursiveFunction {
+            function_name: "recurse".to_string(),
+            argument_name: "n".to_string(),
+            argument_type: Type::Number,
+            return_type: Type::Number,
+            body: Box::new(AST::Plus(
+                Box::new(AST::NumberLiteral(1)),
+                Box::new(AST::FunctionApplication(FunctionApplication {
+                    function: Box::new(AST::Identifier("recurse".to_string())),
+                    argument: Box::new(AST::Identifier("n".to_string())),
+                })),
+            )),
+            function_use: Box::new(AST::FunctionApplication(FunctionApplication {
+                function: Box::new(AST::Identifier("recurse".to_string())),
+                argument: Box::new(AST::NumberLiteral(0)),
+            })),
+        })
+    }
+
+    #[test]
+    fn interpret_max_steps() {
+        let budget = Budget {
+            max_steps: Some(1000),
+            ..Budget::default()
+        };
+        assert_eq!(
+            interpret_with_budget(&loop_forever(), &budget),
+            Err(OutOfFuel {
+                steps: 1000,
+                limit: Limit::Steps
+            })
+        )
+    }
+
+    #[test]
+    fn interpret_max_depth() {
+        let budget = Budget {
+            max_depth: Some(100),
+            ..Budget::default()
+        };
+        assert_eq!(
+            interpret_with_budget(&recurse_forever(), &budget)
+                .unwrap_err()
+                .limit,
+            Limit::Depth
+        )
+    }
+
+    #[test]
+    fn interpret_tail_call() {
+        // The continuation of a loop whose recursive call is in tail position doesn't grow, so it
+        // only runs out of steps.
+        let budget = Budget {
+            max_steps: Some(1_000_000),
+            max_depth: Some(10),
+            deadline: None,
+        };
+        assert_eq!(
+            interpret_with_budget(&loop_forever(), &budget)
+                .unwrap_err()
+                .limit,
+            Limit::Steps
+        )
+    }
+
+    #[test]
+    fn interpret_deadline() {
+        let budget = Budget {
+            deadline: Some(Instant::now()),
+            ..Budget::default()
+        };
+        assert_eq!(
+            interpret_with_budget(&loop_forever(), &budget),
+            Err(OutOfFuel {
+                steps: 0,
+                limit: Limit::Time
+            })
+        )
+    }
+
+    #[test]
+    fn interpret_within_budget() {
+        let budget = Budget {
+            max_steps: Some(1000),
+            max_depth: Some(100),
+            deadline: None,
+        };
+        assert_eq!(
+            interpret_with_budget(&summation(5), &budget),
+            Ok(Data::Number(15))
+        )
+    }
+
+    #[derive(Default)]
+    struct Recorder {
+        depth: usize,
+        entered: usize,
+        applications: Vec<i64>,
+        lookups: Vec<String>,
+    }
+
+    impl Observer for Recorder {
+        fn enter(&mut self, _ast: &AST, _environment: &Environment) {
+            self.depth += 1;
+            self.entered += 1;
+        }
+
+        fn leave(&mut self, _data: &Data) {
+            self.depth -= 1;
+        }
+
+        fn apply(&mut self, _function: &Data, argument: &Data) {
+            self.applications.push(argument.number());
+        }
+
+        fn lookup(&mut self, name: &str, _data: &Data) {
+            self.lookups.push(name.to_string());
+        }
+    }
+
+    #[test]
+    fn interpret_observer() {
+        let mut recorder = Recorder::default();
+        assert_eq!(
+            interpret_with_observer(&summation(2), &Budget::default(), &mut recorder),
+            Ok(Data::Number(3))
+        );
+        assert_eq!(recorder.depth, 0);
+        assert!(recorder.entered > 0);
+        assert_eq!(recorder.applications, vec![2, 1, 0]);
+        assert_eq!(
+            recorder
+                .lookups
+                .iter()
+                .filter(|name| name.as_str() == "sum")
+                .count(),
+            3
+        );
+    }
+
+    #[test]
+    fn interpret_observer_leave_data() {
+        #[derive(Default)]
+        struct Leaves {
+            entered: Vec<AST>,
+            left: Vec<(AST, Data)>,
+        }
+        impl Observer for Leaves {
+            fn enter(&mut self, ast: &AST, _environment: &Environment) {
+                self.entered.push(ast.clone());
+            }
+
+            fn leave(&mut self, data: &Data) {
+                let ast = self.entered.pop().unwrap();
+                self.left.push((ast, data.clone()));
+            }
+        }
+
+        let input = AST::Plus(
+            Box::new(AST::NumberLiteral(5)),
+            Box::new(AST::NumberLiteral(-1)),
+        );
+        let mut leaves = Leaves::default();
+        interpret_with_observer(&input, &Budget::default(), &mut leaves).unwrap();
+        assert_eq!(
+            leaves.left,
+            vec![
+                (AST::NumberLiteral(5), Data::Number(5)),
+                (AST::NumberLiteral(-1), Data::Number(-1)),
+                (input, Data::Number(4)),
+            ]
+        );
+    }
+
+    /// Applies `fdC("x", numT, numT, body)` to `argument`.
+    fn apply_to(body: AST, argument: AST) -> AST {
+        AST::FunctionApplication(FunctionApplication {
+            function: Box::new(AST::FunctionDefinition(FunctionDefinition {
+                argument_name: "x".to_string(),
+                argument_type: Type::Number,
+                return_type: Type::Number,
+                body: Box::new(body),
+            })),
+            argument: Box::new(argument),
+        })
+    }
+
+    #[test]
+    fn interpret_strategy_termination() {
+        let input = apply_to(AST::NumberLiteral(1), loop_forever());
+        let budget = Budget {
+            max_steps: Some(10_000),
+            ..Budget::default()
+        };
+        assert_eq!(
+            interpret_with_strategy(&input, Strategy::ByValue, &budget)
+                .unwrap_err()
+                .limit,
+            Limit::Steps
+        );
+        assert_eq!(
+            interpret_with_strategy(&input, Strategy::ByName, &budget),
+            Ok(Data::Number(1))
+        );
+        assert_eq!(
+            interpret_with_strategy(&input, Strategy::ByNeed, &budget),
+            Ok(Data::Number(1))
+        );
+    }
+
+    /// Counts how many times `multC` is evaluated.
+    #[derive(Default)]
+    struct Multiplications(usize);
+
+    impl Observer for Multiplications {
+        fn enter(&mut self, ast: &AST, _environment: &Environment) {
+            if let AST::Multiply(_, _) = ast {
+                self.0 += 1;
+            }
+        }
+    }
+
+    #[test]
+    fn interpret_strategy_memoization() {
+        let input = apply_to(
+            AST::Plus(
+                Box::new(AST::Identifier("x".to_string())),
+                Box::new(AST::Identifier("x".to_string())),
+            ),
+            AST::Multiply(
+                Box::new(AST::NumberLiteral(6)),
+                Box::new(AST::NumberLiteral(7)),
+            ),
+        );
+        for (strategy, multiplications) in [
+            (Strategy::ByValue, 1),
+            (Strategy::ByName, 2),
+            (Strategy::ByNeed, 1),
+        ] {
+            let mut observer = Multiplications::default();
+            assert_eq!(
+                interpreter(
+                    input.clone(),
+                    &mut HashMap::new(),
+                    strategy,
+                    &Budget::default(),
+                    Some(&mut observer)
+                ),
+                Ok(Data::Number(84))
+            );
+            assert_eq!(observer.0, multiplications);
+        }
+    }
+
+    #[test]
+    fn interpret_thunk_free_variables() {
+        let ast = crate::parse(
+            r#"appC(fdC("x", numT, numT, plusC(idC("x"), idC("y"))), plusC(idC("y"), idC("z")))"#,
+        );
+        let mut free = Vec::new();
+        free_variables(&ast, &mut Vec::new(), &mut free);
+        assert_eq!(free, vec!["y".to_string(), "z".to_string()]);
+    }
+
+    #[test]
+    fn interpret_data_display() {
+        let data = Data::Constructed(Constructed {
+            constructor: String::from("cons"),
+            fields: vec![
+                Data::Number(1),
+                Data::Reference(Rc::new(RefCell::new(Data::Boolean(true)))),
+            ],
+        });
+        assert_eq!(data.to_string(), "cons(1, refC(true))");
+    }
 }
@@ -0,0 +1,1029 @@
+//! JSON encodings of the intermediate representations, for other tools to consume.
+//!
+//! Every document is an object with a `"version"`, which is `VERSION`, and a `"stage"`:
+//!
+//! - `{"stage": "tokens", "tokens": [token, ...]}`, where a token is
+//!   `{"token": name, "line": n, "column": n}` with a `"value"` for `QuotedString` and
+//!   `NumberLiteral`, and `name` is a variant of `tokenize::Token`.
+//! - `{"stage": "ast", "ast": node}`.
+//! - `{"stage": "typed", "typed_ast": node}`, where every node also has its `"ty"`.
+//!
+//! A node is `{"kind": variant, ...}`, where `variant` is a variant of `parse::AST` and the other
+//! fields are named after the fields of its struct: `NumberLiteral` has a `"value"`, `Identifier`
+//! a `"name"`, the binary operators an `"lhs"` and an `"rhs"`, `Reference`, `Dereference` and
+//! `Fix` an `"ast"`, `Set` a `"reference"` and a `"value"`, `Located` a `"line"`, a `"column"`
+//! and an `"ast"`, and `Commented` the fields of its comments and an `"ast"`. Comments have
+//! `"leading"` and `"trailing"` arrays, an `"end_of_line"` that may be `null`, and the
+//! `"arguments"` comments, each of which also has the `"index"` of its argument.
+//! Typed nodes add the `"tag"` of `Construct` and the `"tag"` and `"field_types"` of each branch
+//! of `Match`, where the wildcard's tag is `null`. A type is `{"kind": variant, ...}`, where
+//! `variant` is a variant of `type_check::Type`: `Function` has an `"argument"` and a `"ret"`,
+//! `Reference` a `"ty"`, and `Data` and `Named` a `"name"`.
+
+use crate::{
+    parse::{
+        Comments, Construct, Constructor, DataDeclaration, FunctionApplication, FunctionDefinition,
+        If, Match, MatchBranch, RecursiveBinding, RecursiveFunction, RecursiveFunctionGroup,
+        TypeDeclaration, AST,
+    },
+    tokenize::{Position, Token, TokenStream},
+    type_check::{Type, TypedAST, TypedASTEnum},
+};
+use std::{convert::TryInto, fmt, fmt::Display};
+
+/// The extension of files holding a JSON document of the `"ast"` stage.
+pub const EXTENSION: &str = "json";
+
+/// The version of the schema, which changes whenever a document that was valid stops being valid
+/// or means something else.
+pub const VERSION: i64 = 2;
+
+/// A JSON value, whose objects keep the order of their fields.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Json {
+    Null,
+    Boolean(bool),
+    /// Only integers are supported, since the language has no other numbers.
+    Number(i64),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+impl Json {
+    pub fn parse(text: &str) -> Json {
+        let mut parser = Parser {
+            characters: text.chars().collect(),
+            index: 0,
+        };
+        let json = parser.value();
+        parser.whitespace();
+        if parser.index != parser.characters.len() {
+            parser.error("the end of the input");
+        }
+        json
+    }
+
+    /// The field `key` of an object.
+    pub fn get(&self, key: &str) -> &Json {
+        match self {
+            Json::Object(fields) => fields
+                .iter()
+                .find(|(name, _)| name == key)
+                .map(|(_, value)| value)
+                .unwrap_or_else(|| panic!("Missing field \"{}\" in {}.", key, self)),
+            json => panic!("Expected an object with \"{}\", found {}.", key, json),
+        }
+    }
+
+    pub fn as_i64(&self) -> i64 {
+        match self {
+            Json::Number(number) => *number,
+            json => panic!("Expected a number, found {}.", json),
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        match self {
+            Json::String(string) => string,
+            json => panic!("Expected a string, found {}.", json),
+        }
+    }
+
+    pub fn as_array(&self) -> &[Json] {
+        match self {
+            Json::Array(items) => items,
+            json => panic!("Expected an array, found {}.", json),
+        }
+    }
+}
+
+fn write_string(f: &mut fmt::Formatter<'_>, string: &str) -> fmt::Result {
+    write!(f, "\"")?;
+    for character in string.chars() {
+        match character {
+            '"' => write!(f, "\\\"")?,
+            '\\' => write!(f, "\\\\")?,
+            '\n' => write!(f, "\\n")?,
+            '\r' => write!(f, "\\r")?,
+            '\t' => write!(f, "\\t")?,
+            character if character.is_control() => write!(f, "\\u{:04x}", character as u32)?,
+            character => write!(f, "{}", character)?,
+        }
+    }
+    write!(f, "\"")
+}
+
+impl Display for Json {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Json::Null => write!(f, "null"),
+            Json::Boolean(boolean) => write!(f, "{}", boolean),
+            Json::Number(number) => write!(f, "{}", number),
+            Json::String(string) => write_string(f, string),
+            Json::Array(items) => {
+                write!(f, "[")?;
+                for (index, item) in items.iter().enumerate() {
+                    if index != 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{}", item)?;
+                }
+                write!(f, "]")
+            }
+            Json::Object(fields) => {
+                write!(f, "{{")?;
+                for (index, (name, value)) in fields.iter().enumerate() {
+                    if index != 0 {
+                        write!(f, ",")?;
+                    }
+                    write_string(f, name)?;
+                    write!(f, ":{}", value)?;
+                }
+                write!(f, "}}")
+            }
+        }
+    }
+}
+
+struct Parser {
+    characters: Vec<char>,
+    index: usize,
+}
+
+impl Parser {
+    fn error(&self, expected: &str) -> ! {
+        match self.characters.get(self.index) {
+            Some(character) => panic!(
+                "Expected {} at offset {}, found '{}'.",
+                expected, self.index, character
+            ),
+            None => panic!("Expected {}, found the end of the input.", expected),
+        }
+    }
+
+    fn whitespace(&mut self) {
+        while matches!(self.characters.get(self.index), Some(character) if character.is_ascii_whitespace())
+        {
+            self.index += 1;
+        }
+    }
+
+    fn eat(&mut self, character: char) -> bool {
+        self.whitespace();
+        if self.characters.get(self.index) == Some(&character) {
+            self.index += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, character: char) {
+        if !self.eat(character) {
+            self.error(&format!("'{}'", character));
+        }
+    }
+
+    fn literal(&mut self, literal: &str, json: Json) -> Json {
+        for expected in literal.chars() {
+            if self.characters.get(self.index) != Some(&expected) {
+                self.error(&format!("'{}'", literal));
+            }
+            self.index += 1;
+        }
+        json
+    }
+
+    fn value(&mut self) -> Json {
+        self.whitespace();
+        match self.characters.get(self.index) {
+            Some('n') => self.literal("null", Json::Null),
+            Some('t') => self.literal("true", Json::Boolean(true)),
+            Some('f') => self.literal("false", Json::Boolean(false)),
+            Some('"') => Json::String(self.string()),
+            Some('[') => {
+                self.index += 1;
+                let mut items = Vec::new();
+                if !self.eat(']') {
+                    loop {
+                        items.push(self.value());
+                        if self.eat(']') {
+                            break;
+                        }
+                        self.expect(',');
+                    }
+                }
+                Json::Array(items)
+            }
+            Some('{') => {
+                self.index += 1;
+                let mut fields = Vec::new();
+                if !self.eat('}') {
+                    loop {
+                        self.whitespace();
+                        let name = self.string();
+                        self.expect(':');
+                        fields.push((name, self.value()));
+                        if self.eat('}') {
+                            break;
+                        }
+                        self.expect(',');
+                    }
+                }
+                Json::Object(fields)
+            }
+            Some(character) if *character == '-' || character.is_ascii_digit() => {
+                let start = self.index;
+                self.index += 1;
+                while matches!(self.characters.get(self.index), Some(character) if character.is_ascii_digit())
+                {
+                    self.index += 1;
+                }
+                if matches!(self.characters.get(self.index), Some('.' | 'e' | 'E')) {
+                    self.error("an integer");
+                }
+                let number: String = self.characters[start..self.index].iter().collect();
+                Json::Number(
+                    number
+                        .parse()
+                        .unwrap_or_else(|_| panic!("{} isn't a 64 bit integer.", number)),
+                )
+            }
+            _ => self.error("a value"),
+        }
+    }
+
+    fn hex(&mut self) -> u32 {
+        let mut code = 0;
+        for _ in 0..4 {
+            match self
+                .characters
+                .get(self.index)
+                .and_then(|digit| digit.to_digit(16))
+            {
+                Some(digit) => code = code * 16 + digit,
+                None => self.error("a hex digit"),
+            }
+            self.index += 1;
+        }
+        code
+    }
+
+    fn string(&mut self) -> String {
+        if self.characters.get(self.index) != Some(&'"') {
+            self.error("a string");
+        }
+        self.index += 1;
+        let mut string = String::new();
+        loop {
+            let character = match self.characters.get(self.index) {
+                Some(character) => *character,
+                None => self.error("'\"'"),
+            };
+            self.index += 1;
+            match character {
+                '"' => return string,
+                '\\' => {
+                    let escaped = self.characters.get(self.index).copied();
+                    self.index += 1;
+                    string.push(match escaped {
+                        Some('"') => '"',
+                        Some('\\') => '\\',
+                        Some('/') => '/',
+                        Some('b') => '\u{8}',
+                        Some('f') => '\u{c}',
+                        Some('n') => '\n',
+                        Some('r') => '\r',
+                        Some('t') => '\t',
+                        Some('u') => {
+                            let mut code = self.hex();
+                            // Characters outside the basic multilingual plane are surrogate pairs.
+                            if (0xd800..0xdc00).contains(&code)
+                                && self.characters.get(self.index) == Some(&'\\')
+                                && self.characters.get(self.index + 1) == Some(&'u')
+                            {
+                                self.index += 2;
+                                let start = self.index;
+                                let low = self.hex();
+                                if !(0xdc00..0xe000).contains(&low) {
+                                    self.index = start;
+                                    self.error("a low surrogate");
+                                }
+                                code = 0x10000 + ((code - 0xd800) << 10) + (low - 0xdc00);
+                            }
+                            char::from_u32(code).unwrap_or_else(|| self.error("a character"))
+                        }
+                        _ => {
+                            self.index -= 1;
+                            self.error("an escape")
+                        }
+                    });
+                }
+                character => string.push(character),
+            }
+        }
+    }
+}
+
+fn string(string: &str) -> Json {
+    Json::String(string.to_string())
+}
+
+fn strings(strings: &[String]) -> Json {
+    Json::Array(strings.iter().map(|item| string(item)).collect())
+}
+
+fn from_comments(comments: &Comments) -> Vec<(&'static str, Json)> {
+    vec![
+        ("leading", strings(&comments.leading)),
+        (
+            "end_of_line",
+            comments.end_of_line.as_deref().map_or(Json::Null, string),
+        ),
+        ("trailing", strings(&comments.trailing)),
+        (
+            "arguments",
+            Json::Array(
+                comments
+                    .arguments
+                    .iter()
+                    .map(|(index, comments)| {
+                        let mut fields = vec![("index", Json::Number(*index as i64))];
+                        fields.extend(from_comments(comments));
+                        object(fields)
+                    })
+                    .collect(),
+            ),
+        ),
+    ]
+}
+
+fn object(fields: Vec<(&str, Json)>) -> Json {
+    Json::Object(
+        fields
+            .into_iter()
+            .map(|(name, value)| (name.to_string(), value))
+            .collect(),
+    )
+}
+
+fn document(stage: &str, name: &str, json: Json) -> Json {
+    object(vec![
+        ("version", Json::Number(VERSION)),
+        ("stage", string(stage)),
+        (name, json),
+    ])
+}
+
+/// The `"tokens"` document of the tokens left in `token_stream`.
+pub fn tokens_document(token_stream: &mut TokenStream) -> Json {
+    let mut tokens = Vec::new();
+    loop {
+        let position = token_stream.next_position();
+        let token = match token_stream.next() {
+            Some(token) => token,
+            None => break,
+        };
+        let (name, value) = match token {
+            Token::QuotedString(quoted) => (String::from("QuotedString"), Some(string(&quoted))),
+            Token::NumberLiteral(number) => {
+                (String::from("NumberLiteral"), Some(Json::Number(number)))
+            }
+            // The other variants have no fields, so they are debug formatted as their names.
+            token => (format!("{:?}", token), None),
+        };
+        let mut fields = vec![("token", Json::String(name))];
+        fields.extend(value.map(|value| ("value", value)));
+        if let Some(position) = position {
+            fields.push(("line", Json::Number(position.line as i64)));
+            fields.push(("column", Json::Number(position.column as i64)));
+        }
+        tokens.push(object(fields));
+    }
+    document("tokens", "tokens", Json::Array(tokens))
+}
+
+pub fn ast_document(ast: &AST) -> Json {
+    document("ast", "ast", from_ast(ast))
+}
+
+pub fn typed_ast_document(typed_ast: &TypedAST) -> Json {
+    document("typed", "typed_ast", from_typed_ast(typed_ast))
+}
+
+pub fn from_type(ty: &Type) -> Json {
+    match ty {
+        Type::Number => object(vec![("kind", string("Number"))]),
+        Type::Boolean => object(vec![("kind", string("Boolean"))]),
+        Type::Function { argument, ret } => object(vec![
+            ("kind", string("Function")),
+            ("argument", from_type(argument)),
+            ("ret", from_type(ret)),
+        ]),
+        Type::Reference(ty) => object(vec![("kind", string("Reference")), ("ty", from_type(ty))]),
+        Type::Data(name) => object(vec![("kind", string("Data")), ("name", string(name))]),
+        Type::Named(name) => object(vec![("kind", string("Named")), ("name", string(name))]),
+    }
+}
+
+fn from_types(types: &[Type]) -> Json {
+    Json::Array(types.iter().map(from_type).collect())
+}
+
+fn from_constructors(constructors: &[Constructor]) -> Json {
+    Json::Array(
+        constructors
+            .iter()
+            .map(|constructor| {
+                object(vec![
+                    ("name", string(&constructor.name)),
+                    ("fields", from_types(&constructor.fields)),
+                ])
+            })
+            .collect(),
+    )
+}
+
+fn binary(kind: &str, lhs: Json, rhs: Json) -> Vec<(&str, Json)> {
+    vec![("kind", string(kind)), ("lhs", lhs), ("rhs", rhs)]
+}
+
+fn unary(kind: &str, ast: Json) -> Vec<(&str, Json)> {
+    vec![("kind", string(kind)), ("ast", ast)]
+}
+
+pub fn from_ast(ast: &AST) -> Json {
+    object(match ast {
+        AST::NumberLiteral(number) => vec![
+            ("kind", string("NumberLiteral")),
+            ("value", Json::Number(*number)),
+        ],
+        AST::TrueLiteral => vec![("kind", string("TrueLiteral"))],
+        AST::FalseLiteral => vec![("kind", string("FalseLiteral"))],
+        AST::Identifier(name) => vec![("kind", string("Identifier")), ("name", string(name))],
+        AST::Plus(lhs, rhs) => binary("Plus", from_ast(lhs), from_ast(rhs)),
+        AST::Multiply(lhs, rhs) => binary("Multiply", from_ast(lhs), from_ast(rhs)),
+        AST::Equals(lhs, rhs) => binary("Equals", from_ast(lhs), from_ast(rhs)),
+        AST::PhysicalEquals(lhs, rhs) => binary("PhysicalEquals", from_ast(lhs), from_ast(rhs)),
+        AST::Reference(ast) => unary("Reference", from_ast(ast)),
+        AST::Dereference(ast) => unary("Dereference", from_ast(ast)),
+        AST::Fix(ast) => unary("Fix", from_ast(ast)),
+        AST::Set(reference, value) => vec![
+            ("kind", string("Set")),
+            ("reference", from_ast(reference)),
+            ("value", from_ast(value)),
+        ],
+        AST::If(if_struct) => vec![
+            ("kind", string("If")),
+            ("condition", from_ast(&if_struct.condition)),
+            ("then", from_ast(&if_struct.then)),
+            ("els", from_ast(&if_struct.els)),
+        ],
+        AST::FunctionApplication(function_application) => vec![
+            ("kind", string("FunctionApplication")),
+            ("function", from_ast(&function_application.function)),
+            ("argument", from_ast(&function_application.argument)),
+        ],
+        AST::FunctionDefinition(function_definition) => vec![
+            ("kind", string("FunctionDefinition")),
+            ("argument_name", string(&function_definition.argument_name)),
+            (
+                "argument_type",
+                from_type(&function_definition.argument_type),
+            ),
+            ("return_type", from_type(&function_definition.return_type)),
+            ("body", from_ast(&function_definition.body)),
+        ],
+        AST::RecursiveFunction(recursive_function) => vec![
+            ("kind", string("RecursiveFunction")),
+            ("function_name", string(&recursive_function.function_name)),
+            ("argument_name", string(&recursive_function.argument_name)),
+            (
+                "argument_type",
+                from_type(&recursive_function.argument_type),
+            ),
+            ("return_type", from_type(&recursive_function.return_type)),
+            ("body", from_ast(&recursive_function.body)),
+            ("function_use", from_ast(&recursive_function.function_use)),
+        ],
+        AST::RecursiveFunctionGroup(recursive_function_group) => vec![
+            ("kind", string("RecursiveFunctionGroup")),
+            (
+                "functions",
+                Json::Array(
+                    recursive_function_group
+                        .functions
+                        .iter()
+                        .map(|function| {
+                            object(vec![
+                                ("function_name", string(&function.function_name)),
+                                ("argument_name", string(&function.argument_name)),
+                                ("argument_type", from_type(&function.argument_type)),
+                                ("return_type", from_type(&function.return_type)),
+                                ("body", from_ast(&function.body)),
+                            ])
+                        })
+                        .collect(),
+                ),
+            ),
+            (
+                "function_use",
+                from_ast(&recursive_function_group.function_use),
+            ),
+        ],
+        AST::DataDeclaration(data_declaration) => vec![
+            ("kind", string("DataDeclaration")),
+            ("name", string(&data_declaration.name)),
+            (
+                "constructors",
+                from_constructors(&data_declaration.constructors),
+            ),
+            ("body", from_ast(&data_declaration.body)),
+        ],
+        AST::Construct(construct) => vec![
+            ("kind", string("Construct")),
+            ("constructor", string(&construct.constructor)),
+            (
+                "arguments",
+                Json::Array(construct.arguments.iter().map(from_ast).collect()),
+            ),
+        ],
+        AST::Match(match_struct) => vec![
+            ("kind", string("Match")),
+            ("scrutinee", from_ast(&match_struct.scrutinee)),
+            (
+                "branches",
+                Json::Array(
+                    match_struct
+                        .branches
+                        .iter()
+                        .map(|branch| {
+                            object(vec![
+                                ("constructor", string(&branch.constructor)),
+                                ("variables", strings(&branch.variables)),
+                                ("body", from_ast(&branch.body)),
+                            ])
+                        })
+                        .collect(),
+                ),
+            ),
+        ],
+        AST::TypeDeclaration(type_declaration) => vec![
+            ("kind", string("TypeDeclaration")),
+            ("name", string(&type_declaration.name)),
+            ("ty", from_type(&type_declaration.ty)),
+            ("body", from_ast(&type_declaration.body)),
+        ],
+        AST::Located(position, ast) => vec![
+            ("kind", string("Located")),
+            ("line", Json::Number(position.line as i64)),
+            ("column", Json::Number(position.column as i64)),
+            ("ast", from_ast(ast)),
+        ],
+        AST::Commented(comments, ast) => {
+            let mut fields = vec![("kind", string("Commented"))];
+            fields.extend(from_comments(comments));
+            fields.push(("ast", from_ast(ast)));
+            fields
+        }
+        AST::Error => vec![("kind", string("Error"))],
+    })
+}
+
+pub fn from_typed_ast(typed_ast: &TypedAST) -> Json {
+    let mut fields = match &*typed_ast.ast {
+        TypedASTEnum::NumberLiteral(number) => vec![
+            ("kind", string("NumberLiteral")),
+            ("value", Json::Number(*number)),
+        ],
+        TypedASTEnum::TrueLiteral => vec![("kind", string("TrueLiteral"))],
+        TypedASTEnum::FalseLiteral => vec![("kind", string("FalseLiteral"))],
+        TypedASTEnum::Identifier(name) => {
+            vec![("kind", string("Identifier")), ("name", string(name))]
+        }
+        TypedASTEnum::Plus(lhs, rhs) => binary("Plus", from_typed_ast(lhs), from_typed_ast(rhs)),
+        TypedASTEnum::Multiply(lhs, rhs) => {
+            binary("Multiply", from_typed_ast(lhs), from_typed_ast(rhs))
+        }
+        TypedASTEnum::Equals(lhs, rhs) => {
+            binary("Equals", from_typed_ast(lhs), from_typed_ast(rhs))
+        }
+        TypedASTEnum::PhysicalEquals(lhs, rhs) => {
+            binary("PhysicalEquals", from_typed_ast(lhs), from_typed_ast(rhs))
+        }
+        TypedASTEnum::Reference(ast) => unary("Reference", from_typed_ast(ast)),
+        TypedASTEnum::Dereference(ast) => unary("Dereference", from_typed_ast(ast)),
+        TypedASTEnum::Fix(ast) => unary("Fix", from_typed_ast(ast)),
+        TypedASTEnum::Set(reference, value) => vec![
+            ("kind", string("Set")),
+            ("reference", from_typed_ast(reference)),
+            ("value", from_typed_ast(value)),
+        ],
+        TypedASTEnum::If(if_struct) => vec![
+            ("kind", string("If")),
+            ("condition", from_typed_ast(&if_struct.condition)),
+            ("then", from_typed_ast(&if_struct.then)),
+            ("els", from_typed_ast(&if_struct.els)),
+        ],
+        TypedASTEnum::FunctionApplication(function_application) => vec![
+            ("kind", string("FunctionApplication")),
+            ("function", from_typed_ast(&function_application.function)),
+            ("argument", from_typed_ast(&function_application.argument)),
+        ],
+        TypedASTEnum::FunctionDefinition(function_definition) => vec![
+            ("kind", string("FunctionDefinition")),
+            ("argument_name", string(&function_definition.argument_name)),
+            (
+                "argument_type",
+                from_type(&function_definition.argument_type),
+            ),
+            ("return_type", from_type(&function_definition.return_type)),
+            ("body", from_typed_ast(&function_definition.body)),
+        ],
+        TypedASTEnum::RecursiveFunction(recursive_function) => vec![
+            ("kind", string("RecursiveFunction")),
+            ("function_name", string(&recursive_function.function_name)),
+            ("argument_name", string(&recursive_function.argument_name)),
+            (
+                "argument_type",
+                from_type(&recursive_function.argument_type),
+            ),
+            ("return_type", from_type(&recursive_function.return_type)),
+            ("body", from_typed_ast(&recursive_function.body)),
+            (
+                "function_use",
+                from_typed_ast(&recursive_function.function_use),
+            ),
+        ],
+        TypedASTEnum::RecursiveFunctionGroup(recursive_function_group) => vec![
+            ("kind", string("RecursiveFunctionGroup")),
+            (
+                "functions",
+                Json::Array(
+                    recursive_function_group
+                        .functions
+                        .iter()
+                        .map(|function| {
+                            object(vec![
+                                ("function_name", string(&function.function_name)),
+                                ("argument_name", string(&function.argument_name)),
+                                ("argument_type", from_type(&function.argument_type)),
+                                ("return_type", from_type(&function.return_type)),
+                                ("body", from_typed_ast(&function.body)),
+                            ])
+                        })
+                        .collect(),
+                ),
+            ),
+            (
+                "function_use",
+                from_typed_ast(&recursive_function_group.function_use),
+            ),
+        ],
+        TypedASTEnum::DataDeclaration(data_declaration) => vec![
+            ("kind", string("DataDeclaration")),
+            ("name", string(&data_declaration.name)),
+            (
+                "constructors",
+                from_constructors(&data_declaration.constructors),
+            ),
+            ("body", from_typed_ast(&data_declaration.body)),
+        ],
+        TypedASTEnum::TypeDeclaration(type_declaration) => vec![
+            ("kind", string("TypeDeclaration")),
+            ("name", string(&type_declaration.name)),
+            ("ty", from_type(&type_declaration.ty)),
+            ("body", from_typed_ast(&type_declaration.body)),
+        ],
+        TypedASTEnum::Construct(construct) => vec![
+            ("kind", string("Construct")),
+            ("constructor", string(&construct.constructor)),
+            ("tag", Json::Number(construct.tag as i64)),
+            (
+                "arguments",
+                Json::Array(construct.arguments.iter().map(from_typed_ast).collect()),
+            ),
+        ],
+        TypedASTEnum::Match(match_struct) => vec![
+            ("kind", string("Match")),
+            ("scrutinee", from_typed_ast(&match_struct.scrutinee)),
+            (
+                "branches",
+                Json::Array(
+                    match_struct
+                        .branches
+                        .iter()
+                        .map(|branch| {
+                            object(vec![
+                                ("constructor", string(&branch.constructor)),
+                                (
+                                    "tag",
+                                    branch
+                                        .tag
+                                        .map_or(Json::Null, |tag| Json::Number(tag as i64)),
+                                ),
+                                ("variables", strings(&branch.variables)),
+                                ("field_types", from_types(&branch.field_types)),
+                                ("body", from_typed_ast(&branch.body)),
+                            ])
+                        })
+                        .collect(),
+                ),
+            ),
+        ],
+    };
+    fields.insert(1, ("ty", from_type(&typed_ast.ty)));
+    object(fields)
+}
+
+/// The `AST` of an `"ast"` document, such as one written by `ast_document`.
+pub fn load(text: &str) -> AST {
+    let json = Json::parse(text);
+    let version = json.get("version").as_i64();
+    if version != VERSION {
+        panic!(
+            "Unsupported schema version {}, expected {}.",
+            version, VERSION
+        );
+    }
+    let stage = json.get("stage").as_str();
+    if stage != "ast" {
+        panic!(
+            "Expected a document of the \"ast\" stage, found \"{}\".",
+            stage
+        );
+    }
+    to_ast(json.get("ast"))
+}
+
+fn to_string(json: &Json) -> String {
+    json.as_str().to_string()
+}
+
+fn to_strings(json: &Json) -> Vec<String> {
+    json.as_array().iter().map(to_string).collect()
+}
+
+fn to_comments(json: &Json) -> Comments {
+    Comments {
+        leading: to_strings(json.get("leading")),
+        end_of_line: match json.get("end_of_line") {
+            Json::Null => None,
+            comment => Some(to_string(comment)),
+        },
+        trailing: to_strings(json.get("trailing")),
+        arguments: json
+            .get("arguments")
+            .as_array()
+            .iter()
+            .map(|argument| (to_usize(argument.get("index")), to_comments(argument)))
+            .collect(),
+    }
+}
+
+fn to_usize(json: &Json) -> usize {
+    let number = json.as_i64();
+    number
+        .try_into()
+        .unwrap_or_else(|_| panic!("Expected a non-negative number, found {}.", number))
+}
+
+pub fn to_type(json: &Json) -> Type {
+    match json.get("kind").as_str() {
+        "Number" => Type::Number,
+        "Boolean" => Type::Boolean,
+        "Function" => Type::Function {
+            argument: Box::new(to_type(json.get("argument"))),
+            ret: Box::new(to_type(json.get("ret"))),
+        },
+        "Reference" => Type::Reference(Box::new(to_type(json.get("ty")))),
+        "Data" => Type::Data(to_string(json.get("name"))),
+        "Named" => Type::Named(to_string(json.get("name"))),
+        kind => panic!("Unknown type kind \"{}\".", kind),
+    }
+}
+
+fn boxed(json: &Json) -> Box<AST> {
+    Box::new(to_ast(json))
+}
+
+pub fn to_ast(json: &Json) -> AST {
+    match json.get("kind").as_str() {
+        "NumberLiteral" => AST::NumberLiteral(json.get("value").as_i64()),
+        "TrueLiteral" => AST::TrueLiteral,
+        "FalseLiteral" => AST::FalseLiteral,
+        "Identifier" => AST::Identifier(to_string(json.get("name"))),
+        "Plus" => AST::Plus(boxed(json.get("lhs")), boxed(json.get("rhs"))),
+        "Multiply" => AST::Multiply(boxed(json.get("lhs")), boxed(json.get("rhs"))),
+        "Equals" => AST::Equals(boxed(json.get("lhs")), boxed(json.get("rhs"))),
+        "PhysicalEquals" => AST::PhysicalEquals(boxed(json.get("lhs")), boxed(json.get("rhs"))),
+        "Reference" => AST::Reference(boxed(json.get("ast"))),
+        "Dereference" => AST::Dereference(boxed(json.get("ast"))),
+        "Fix" => AST::Fix(boxed(json.get("ast"))),
+        "Set" => AST::Set(boxed(json.get("reference")), boxed(json.get("value"))),
+        "If" => AST::If(If {
+            condition: boxed(json.get("condition")),
+            then: boxed(json.get("then")),
+            els: boxed(json.get("els")),
+        }),
+        "FunctionApplication" => AST::FunctionApplication(FunctionApplication {
+            function: boxed(json.get("function")),
+            argument: boxed(json.get("argument")),
+        }),
+        "FunctionDefinition" => AST::FunctionDefinition(FunctionDefinition {
+            argument_name: to_string(json.get("argument_name")),
+            argument_type: to_type(json.get("argument_type")),
+            return_type: to_type(json.get("return_type")),
+            body: boxed(json.get("body")),
+        }),
+        "RecursiveFunction" => AST::RecursiveFunction(RecursiveFunction {
+            function_name: to_string(json.get("function_name")),
+            argument_name: to_string(json.get("argument_name")),
+            argument_type: to_type(json.get("argument_type")),
+            return_type: to_type(json.get("return_type")),
+            body: boxed(json.get("body")),
+            function_use: boxed(json.get("function_use")),
+        }),
+        "RecursiveFunctionGroup" => AST::RecursiveFunctionGroup(RecursiveFunctionGroup {
+            functions: json
+                .get("functions")
+                .as_array()
+                .iter()
+                .map(|function| RecursiveBinding {
+                    function_name: to_string(function.get("function_name")),
+                    argument_name: to_string(function.get("argument_name")),
+                    argument_type: to_type(function.get("argument_type")),
+                    return_type: to_type(function.get("return_type")),
+                    body: boxed(function.get("body")),
+                })
+                .collect(),
+            function_use: boxed(json.get("function_use")),
+        }),
+        "DataDeclaration" => AST::DataDeclaration(DataDeclaration {
+            name: to_string(json.get("name")),
+            constructors: json
+                .get("constructors")
+                .as_array()
+                .iter()
+                .map(|constructor| Constructor {
+                    name: to_string(constructor.get("name")),
+                    fields: constructor
+                        .get("fields")
+                        .as_array()
+                        .iter()
+                        .map(to_type)
+                        .collect(),
+                })
+                .collect(),
+            body: boxed(json.get("body")),
+        }),
+        "Construct" => AST::Construct(Construct {
+            constructor: to_string(json.get("constructor")),
+            arguments: json
+                .get("arguments")
+                .as_array()
+                .iter()
+                .map(to_ast)
+                .collect(),
+        }),
+        "Match" => AST::Match(Match {
+            scrutinee: boxed(json.get("scrutinee")),
+            branches: json
+                .get("branches")
+                .as_array()
+                .iter()
+                .map(|branch| MatchBranch {
+                    constructor: to_string(branch.get("constructor")),
+                    variables: to_strings(branch.get("variables")),
+                    body: boxed(branch.get("body")),
+                })
+                .collect(),
+        }),
+        "TypeDeclaration" => AST::TypeDeclaration(TypeDeclaration {
+            name: to_string(json.get("name")),
+            ty: to_type(json.get("ty")),
+            body: boxed(json.get("body")),
+        }),
+        "Located" => AST::Located(
+            Position {
+                line: to_usize(json.get("line")),
+                column: to_usize(json.get("column")),
+            },
+            boxed(json.get("ast")),
+        ),
+        "Commented" => AST::Commented(to_comments(json), boxed(json.get("ast"))),
+        "Error" => AST::Error,
+        kind => panic!("Unknown node kind \"{}\".", kind),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{fs, panic};
+
+    fn parse_located(code: &str) -> AST {
+        AST::build(&mut TokenStream::build_located(code.chars()))
+    }
+
+    #[test]
+    fn json_parse_and_display() {
+        let text = r#" {"a": [1, -2, true, false, null], "b\"\\\n": {}, "c": "é😀\/"} "#;
+        let json = Json::parse(text);
+        assert_eq!(
+            json,
+            Json::Object(vec![
+                (
+                    String::from("a"),
+                    Json::Array(vec![
+                        Json::Number(1),
+                        Json::Number(-2),
+                        Json::Boolean(true),
+                        Json::Boolean(false),
+                        Json::Null
+                    ])
+                ),
+                (String::from("b\"\\\n"), Json::Object(Vec::new())),
+                (String::from("c"), string("é😀/")),
+            ])
+        );
+        assert_eq!(
+            json.to_string(),
+            r#"{"a":[1,-2,true,false,null],"b\"\\\n":{},"c":"é😀/"}"#
+        );
+        assert_eq!(Json::parse(&json.to_string()), json);
+    }
+
+    #[test]
+    #[should_panic(expected = "Expected an integer")]
+    fn json_rejects_fractions() {
+        Json::parse("1.5");
+    }
+
+    #[test]
+    fn json_escapes() {
+        assert_eq!(
+            Json::parse(r#""\u00e9\ud83d\ude00""#),
+            Json::String(String::from("\u{e9}\u{1f600}"))
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Expected a low surrogate at offset 9")]
+    fn json_rejects_unpaired_surrogates() {
+        Json::parse(r#""\ud800\u0041""#);
+    }
+
+    #[test]
+    #[should_panic(expected = "Expected a hex digit at offset 3, found '+'")]
+    fn json_rejects_signed_hex() {
+        Json::parse(r#""\u+041""#);
+    }
+
+    #[test]
+    fn json_tokens() {
+        let mut token_stream = TokenStream::build("idC(\"x\")".chars());
+        assert_eq!(
+            tokens_document(&mut token_stream).to_string(),
+            r#"{"version":2,"stage":"tokens","tokens":[{"token":"Identifier","line":1,"column":1},{"token":"LeftParenthesis","line":1,"column":4},{"token":"Quote","line":1,"column":5},{"token":"QuotedString","value":"x","line":1,"column":5},{"token":"Quote","line":1,"column":5},{"token":"RightParenthesis","line":1,"column":8}]}"#
+        );
+    }
+
+    #[test]
+    fn json_typed_ast() {
+        let typed_ast = TypedAST::new(&parse_located("refC(numC(1))"));
+        assert_eq!(
+            typed_ast_document(&typed_ast).to_string(),
+            r#"{"version":2,"stage":"typed","typed_ast":{"kind":"Reference","ty":{"kind":"Reference","ty":{"kind":"Number"}},"ast":{"kind":"NumberLiteral","ty":{"kind":"Number"},"value":1}}}"#
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Unsupported schema version 1")]
+    fn json_rejects_other_versions() {
+        load(r#"{"version": 1, "stage": "ast", "ast": {"kind": "TrueLiteral"}}"#);
+    }
+
+    /// Every core input that parses is written as a document that loads back to the same `AST`,
+    /// with its locations or with its comments.
+    #[test]
+    fn json_round_trip() {
+        for entry in fs::read_dir("tests/inputs").unwrap() {
+            let path = entry.unwrap().path();
+            if matches!(path.extension(), Some(extension) if extension != "txt") {
+                continue;
+            }
+            let code = fs::read_to_string(&path).unwrap();
+            let asts =
+                match panic::catch_unwind(|| [parse_located(&code), crate::parse_commented(&code)])
+                {
+                    Ok(asts) => asts,
+                    Err(_) => continue,
+                };
+            for ast in &asts {
+                assert_eq!(load(&ast_document(ast).to_string()), *ast, "{:?}", path);
+            }
+        }
+    }
+}
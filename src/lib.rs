@@ -1,19 +1,31 @@
+pub mod cache;
 pub mod codegen;
+pub mod debug;
+pub mod diff;
+pub mod dot;
 pub mod format;
 pub mod interpret;
+pub mod json;
 pub mod parse;
 pub mod read;
+pub mod resolve;
+pub mod sexp;
+pub mod step;
+pub mod surface;
 pub mod tokenize;
 pub mod type_check;
+pub mod value;
+pub mod vm;
 
 use inkwell::support::LLVMString;
-use interpret::Data;
+use interpret::{Budget, Data, Observer, OutOfFuel, Strategy};
 use parse::AST;
-use tokenize::TokenStream;
+use std::path::Path;
+use tokenize::{SyntaxError, TokenStream};
 use type_check::{Type, TypedAST};
 
-pub fn tokenize(code: &str) -> TokenStream {
-    TokenStream::build(code.chars().collect())
+pub fn tokenize(code: &str) -> TokenStream<'_> {
+    TokenStream::build(code.chars())
 }
 
 pub fn parse(code: &str) -> AST {
@@ -21,6 +33,99 @@ pub fn parse(code: &str) -> AST {
     AST::build(&mut tokenizer)
 }
 
+/// Like `parse`, but every node is wrapped in an `AST::Located`.
+pub fn parse_located(code: &str) -> AST {
+    let mut tokenizer = TokenStream::build_located(code.chars());
+    AST::build(&mut tokenizer)
+}
+
+/// Like `parse`, but nodes with comments around them or their arguments are wrapped in an
+/// `AST::Commented`.
+pub fn parse_commented(code: &str) -> AST {
+    let mut tokenizer = TokenStream::build_commented(code.chars());
+    AST::build(&mut tokenizer)
+}
+
+/// Like `parse`, but instead of panicking at the first syntax error, puts an `AST::Error`
+/// in place of each node that fails to parse and returns every error.
+pub fn parse_recovering(code: &str) -> (AST, Vec<SyntaxError>) {
+    let mut tokenizer = TokenStream::build_recovering(code.chars());
+    let ast = AST::build(&mut tokenizer);
+    let position = tokenizer.next_position();
+    if let Some(token) = tokenizer.next() {
+        tokenizer.report(SyntaxError {
+            position,
+            message: format!("Expected the end of the input, found '{}'", token),
+        });
+        // Lexes the rest, which reports any text in it that isn't a token.
+        tokenizer.by_ref().for_each(drop);
+    }
+    let mut errors = tokenizer.take_errors();
+    // Text that isn't a token is reported when it is lexed, which can be before the parse errors
+    // in front of it.
+    errors.sort_by_key(|error| error.position);
+    (ast, errors)
+}
+
+/// The concrete syntax that a program is written in.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Syntax {
+    Core,
+    /// The ML-like syntax of `surface`.
+    Ml,
+    /// The s-expressions of `sexp`.
+    Sexp,
+    /// An `"ast"` document of `json`.
+    Json,
+}
+
+impl Syntax {
+    /// The syntax of `file`, judged by its extension.
+    pub fn of_file(file: &str) -> Syntax {
+        match Path::new(file).extension() {
+            Some(extension) if extension == surface::EXTENSION => Syntax::Ml,
+            Some(extension) if extension == sexp::EXTENSION => Syntax::Sexp,
+            Some(extension) if extension == json::EXTENSION => Syntax::Json,
+            _ => Syntax::Core,
+        }
+    }
+}
+
+/// The `AST` of `code`, which is written in `syntax`.
+pub fn desugar(code: &str, syntax: Syntax) -> AST {
+    match syntax {
+        Syntax::Core => parse(code),
+        Syntax::Ml => surface::parse(code),
+        Syntax::Sexp => sexp::parse(code),
+        Syntax::Json => json::load(code),
+    }
+}
+
+/// Like `desugar`, but nodes are wrapped in an `AST::Located` in the syntaxes that record where
+/// they start.
+pub fn desugar_located(code: &str, syntax: Syntax) -> AST {
+    match syntax {
+        Syntax::Core => parse_located(code),
+        Syntax::Ml => surface::parse_located(code),
+        Syntax::Sexp | Syntax::Json => desugar(code, syntax),
+    }
+}
+
+/// `code`, which is written in `from`, written in `to`. Comments are only kept when both are the
+/// core syntax.
+pub fn convert(code: &str, from: Syntax, to: Syntax) -> String {
+    if from == Syntax::Core && to == Syntax::Core {
+        return format(code);
+    }
+    let ast = desugar(code, from);
+    match to {
+        Syntax::Core => format::format(&ast),
+        Syntax::Ml => surface::format(&ast),
+        Syntax::Sexp => sexp::from_ast(&ast).pretty(format::WIDTH),
+        Syntax::Json => format!("{}\n", json::ast_document(&ast)),
+    }
+}
+
 pub fn type_check(code: &str) -> Type {
     let ast = parse(code);
     type_check::type_of(&ast)
@@ -40,12 +145,56 @@ pub fn compile(code: &str) -> Result<u64, LLVMString> {
     codegen::run(&typed_ast)
 }
 
+/// Compiles `code` to bytecode for the virtual machine.
+pub fn bytecode(code: &str) -> vm::Program {
+    vm::compile(&typed_ast(code))
+}
+
+pub fn run_vm(code: &str) -> vm::Value {
+    vm::run(&bytecode(code))
+}
+
 pub fn interpret(code: &str) -> Data {
     let ast = parse(code);
     interpret::interpret(&ast)
 }
 
-pub fn format(code: &str) {
+pub fn interpret_with_budget(code: &str, budget: &Budget) -> Result<Data, OutOfFuel> {
+    let ast = parse(code);
+    interpret::interpret_with_budget(&ast, budget)
+}
+
+pub fn interpret_with_strategy(
+    code: &str,
+    strategy: Strategy,
+    budget: &Budget,
+) -> Result<Data, OutOfFuel> {
+    let ast = parse(code);
+    interpret::interpret_with_strategy(&ast, strategy, budget)
+}
+
+pub fn interpret_with_observer(
+    code: &str,
+    budget: &Budget,
+    observer: &mut dyn Observer,
+) -> Result<Data, OutOfFuel> {
     let ast = parse(code);
-    format::format(&ast);
+    interpret::interpret_with_observer(&ast, budget, observer)
+}
+
+/// Like `interpret`, but scopes variables lexically and evaluates a `resolve::Resolved` tree.
+pub fn interpret_resolved(code: &str) -> resolve::Value {
+    let ast = parse(code);
+    resolve::evaluate(&resolve::resolve(&ast))
+}
+
+pub fn steps(code: &str) -> Result<step::Steps, String> {
+    let ast = parse(code);
+    step::steps(&ast)
+}
+
+/// Formats `code`, keeping its comments.
+pub fn format(code: &str) -> String {
+    let ast = parse_commented(code);
+    format::format(&ast)
 }
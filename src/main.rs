@@ -1,10 +1,64 @@
-use clap::{App, Arg, SubCommand};
+use clap::{App, Arg, ArgMatches, SubCommand};
+use lamb::{
+    cache, codegen,
+    debug::Debugger,
+    dot,
+    format::WIDTH,
+    interpret::{Budget, Strategy},
+    json,
+    parse::AST,
+    sexp,
+    step::StepKind,
+    type_check::TypedAST,
+    vm, Syntax,
+};
+use std::io::Read;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+fn parse_limit<T: std::str::FromStr>(arg_matches: &ArgMatches, name: &str) -> Option<T> {
+    arg_matches.value_of(name).map(|value| {
+        value
+            .parse()
+            .unwrap_or_else(|_| panic!("Argument --{} must be a non-negative integer.", name))
+    })
+}
+
+const SYNTAXES: [&str; 4] = ["core", "ml", "sexp", "json"];
+
+fn parse_syntax(arg_matches: &ArgMatches, name: &str) -> Option<Syntax> {
+    match arg_matches.value_of(name) {
+        Some("core") => Some(Syntax::Core),
+        Some("ml") => Some(Syntax::Ml),
+        Some("sexp") => Some(Syntax::Sexp),
+        Some("json") => Some(Syntax::Json),
+        _ => None,
+    }
+}
+
+/// The syntax of `file`, which is `--syntax` if it is given and otherwise judged by the extension.
+fn syntax(arg_matches: &ArgMatches, file: &str) -> Syntax {
+    parse_syntax(arg_matches, "syntax").unwrap_or_else(|| Syntax::of_file(file))
+}
+
+/// The `AST` of the program in `file`.
+fn read_ast(arg_matches: &ArgMatches, file: &str) -> AST {
+    lamb::desugar(&lamb::read::build(file), syntax(arg_matches, file))
+}
 
 fn main() {
     let matches = App::new("Typed Lambda Calculus")
         .author("Justin Fargnoli <justinfargnoli@gmail.com>")
         .version("0.1")
         .about("A CLI for working with my implementation of the Typed Lambda Calculus.")
+        .arg(
+            Arg::with_name("syntax")
+                .long("syntax")
+                .takes_value(true)
+                .global(true)
+                .possible_values(&SYNTAXES)
+                .help("The syntax of the input. Defaults to 'ml' for '.lml' files, 'sexp' for '.sexp' files, 'json' for '.json' files and 'core' otherwise."),
+        )
         .subcommand(
             SubCommand::with_name("check")
                 .arg(
@@ -13,7 +67,7 @@ fn main() {
                         .takes_value(true)
                         .index(1),
                 )
-                .help("Lex, parse, and type check 'file'."),
+                .help("Lex, parse, and type check 'file', printing every syntax error in it."),
         )
         .subcommand(
             SubCommand::with_name("compile")
@@ -33,8 +87,194 @@ fn main() {
                         .takes_value(true)
                         .index(1),
                 )
+                .arg(
+                    Arg::with_name("max-steps")
+                        .long("max-steps")
+                        .takes_value(true)
+                        .help("Stop after this many evaluation steps."),
+                )
+                .arg(
+                    Arg::with_name("max-depth")
+                        .long("max-depth")
+                        .takes_value(true)
+                        .help("Stop when this many evaluation frames are pending."),
+                )
+                .arg(
+                    Arg::with_name("timeout")
+                        .long("timeout")
+                        .takes_value(true)
+                        .help("Stop after this many milliseconds."),
+                )
+                .arg(
+                    Arg::with_name("strategy")
+                        .long("strategy")
+                        .takes_value(true)
+                        .possible_values(&["by-value", "by-name", "by-need"])
+                        .default_value("by-value")
+                        .help("When the argument of appC is evaluated."),
+                )
                 .help("Lex, parse, type check, and interpret 'file'."),
         )
+        .subcommand(
+            SubCommand::with_name("run")
+                .arg(
+                    Arg::with_name("file")
+                        .required(true)
+                        .takes_value(true)
+                        .index(1),
+                )
+                .arg(
+                    Arg::with_name("backend")
+                        .long("backend")
+                        .takes_value(true)
+                        .possible_values(&["interpret", "vm", "llvm"])
+                        .default_value("vm")
+                        .help("How 'file' is executed."),
+                )
+                .help("Lex, parse, type check, and run 'file' with the chosen backend."),
+        )
+        .subcommand(
+            SubCommand::with_name("disassemble")
+                .arg(
+                    Arg::with_name("file")
+                        .required(true)
+                        .takes_value(true)
+                        .index(1),
+                )
+                .help("Lex, parse, type check, and print the bytecode of 'file'."),
+        )
+        .subcommand(
+            SubCommand::with_name("cache")
+                .subcommand(
+                    SubCommand::with_name("clean")
+                        .help("Delete every program cached in '.lamb-cache'."),
+                )
+                .help("Manage the type checked programs that 'run' caches in '.lamb-cache'."),
+        )
+        .subcommand(
+            SubCommand::with_name("fmt")
+                .arg(
+                    Arg::with_name("files")
+                        .takes_value(true)
+                        .multiple(true)
+                        .index(1)
+                        .help("The files to format in place, where '-' is stdin. Defaults to '-'."),
+                )
+                .arg(
+                    Arg::with_name("check")
+                        .long("check")
+                        .help("Print a diff and exit with 1 if a file is unformatted, without rewriting it."),
+                )
+                .help("Format lamb files, writing stdin's formatted code to stdout."),
+        )
+        .subcommand(
+            SubCommand::with_name("convert")
+                .arg(
+                    Arg::with_name("file")
+                        .required(true)
+                        .takes_value(true)
+                        .index(1),
+                )
+                .arg(
+                    Arg::with_name("to")
+                        .long("to")
+                        .takes_value(true)
+                        .possible_values(&SYNTAXES)
+                        .help("The syntax to print. Defaults to 'core', or 'ml' if 'file' is in the core syntax."),
+                )
+                .arg(
+                    Arg::with_name("typed")
+                        .long("typed")
+                        .help("Type check 'file' and print it as an s-expression with the type of every node."),
+                )
+                .help("Lex and parse 'file' and print it in another syntax."),
+        )
+        .subcommand(
+            SubCommand::with_name("dump")
+                .arg(
+                    Arg::with_name("file")
+                        .required(true)
+                        .takes_value(true)
+                        .index(1),
+                )
+                .arg(
+                    Arg::with_name("stage")
+                        .long("stage")
+                        .takes_value(true)
+                        .possible_values(&["tokens", "ast", "typed"])
+                        .default_value("ast")
+                        .help("The intermediate representation to print."),
+                )
+                .arg(
+                    Arg::with_name("format")
+                        .long("format")
+                        .takes_value(true)
+                        .possible_values(&["json"])
+                        .default_value("json")
+                        .help("How the representation is encoded."),
+                )
+                .help("Print an intermediate representation of 'file', such as its type checked AST."),
+        )
+        .subcommand(
+            SubCommand::with_name("dot")
+                .arg(
+                    Arg::with_name("file")
+                        .required(true)
+                        .takes_value(true)
+                        .index(1),
+                )
+                .arg(
+                    Arg::with_name("typed")
+                        .long("typed")
+                        .conflicts_with("call-graph")
+                        .help("Type check 'file' and label each node with its type."),
+                )
+                .arg(
+                    Arg::with_name("call-graph")
+                        .long("call-graph")
+                        .help("Print which recC functions call each other instead of the tree."),
+                )
+                .help("Lex and parse 'file' and print its tree as a Graphviz DOT graph."),
+        )
+        .subcommand(
+            SubCommand::with_name("step")
+                .arg(
+                    Arg::with_name("file")
+                        .required(true)
+                        .takes_value(true)
+                        .index(1),
+                )
+                .arg(
+                    Arg::with_name("beta-only")
+                        .long("beta-only")
+                        .help("Only print beta-reductions and the last term."),
+                )
+                .arg(
+                    Arg::with_name("max-steps")
+                        .long("max-steps")
+                        .takes_value(true)
+                        .help("Stop after this many steps."),
+                )
+                .help("Lex, parse, type check, and print each step of reducing 'file'."),
+        )
+        .subcommand(
+            SubCommand::with_name("debug")
+                .arg(
+                    Arg::with_name("file")
+                        .required(true)
+                        .takes_value(true)
+                        .index(1),
+                )
+                .arg(
+                    Arg::with_name("break")
+                        .long("break")
+                        .takes_value(true)
+                        .multiple(true)
+                        .number_of_values(1)
+                        .help("Stop when a function with this name is applied or this line is reached."),
+                )
+                .help("Lex, parse, type check, and interpret 'file' in an interactive debugger."),
+        )
         .get_matches();
 
     match matches.subcommand() {
@@ -42,18 +282,257 @@ fn main() {
             let file = arg_matches
                 .value_of("file")
                 .expect("Argument <file> not found.");
-            println!("'{}' returns the type '{}'.", file, lamb::check(file));
+            let code = lamb::read::build(file);
+            let directory = Path::new(cache::DIRECTORY);
+            let syntax = syntax(arg_matches, file);
+            let typed_ast = cache::cached(directory, file, &code, syntax).unwrap_or_else(|| {
+                let ast = match syntax {
+                    Syntax::Core => {
+                        let (ast, errors) = lamb::parse_recovering(&code);
+                        if !errors.is_empty() {
+                            for error in errors {
+                                match error.position {
+                                    Some(_) => eprintln!("{}:{}", file, error),
+                                    None => eprintln!("{}: {}", file, error),
+                                }
+                            }
+                            std::process::exit(1);
+                        }
+                        ast
+                    }
+                    syntax => lamb::desugar(&code, syntax),
+                };
+                let typed_ast = TypedAST::new(&ast);
+                cache::store(directory, file, &code, syntax, &typed_ast);
+                typed_ast
+            });
+            println!("'{}' returns the type '{}'.", file, typed_ast.ty);
         }
         ("compile", Some(arg_matches)) => {
             let file = arg_matches
                 .value_of("file")
                 .expect("Argument <file> not found.");
-            match lamb::compile(file) {
+            let typed_ast = cache::load(
+                Path::new(cache::DIRECTORY),
+                file,
+                &lamb::read::build(file),
+                syntax(arg_matches, file),
+            );
+            match codegen::run(&typed_ast) {
                 Ok(result) => println!("{}", result),
                 Err(error_string) => println!("{}", error_string),
             }
         }
-        ("interpret", Some(_)) => unimplemented!(),
+        ("interpret", Some(arg_matches)) => {
+            let file = arg_matches
+                .value_of("file")
+                .expect("Argument <file> not found.");
+            let ast = cache::load(
+                Path::new(cache::DIRECTORY),
+                file,
+                &lamb::read::build(file),
+                syntax(arg_matches, file),
+            )
+            .erase();
+            let budget = Budget {
+                max_steps: parse_limit(arg_matches, "max-steps"),
+                max_depth: parse_limit(arg_matches, "max-depth"),
+                deadline: parse_limit(arg_matches, "timeout")
+                    .map(|milliseconds| Instant::now() + Duration::from_millis(milliseconds)),
+            };
+            let strategy = match arg_matches.value_of("strategy") {
+                Some("by-name") => Strategy::ByName,
+                Some("by-need") => Strategy::ByNeed,
+                _ => Strategy::ByValue,
+            };
+            match lamb::interpret::interpret_with_strategy(&ast, strategy, &budget) {
+                Ok(data) => println!("{}", data),
+                Err(out_of_fuel) => {
+                    eprintln!("{}", out_of_fuel);
+                    std::process::exit(1);
+                }
+            }
+        }
+        ("run", Some(arg_matches)) => {
+            let file = arg_matches
+                .value_of("file")
+                .expect("Argument <file> not found.");
+            let code = lamb::read::build(file);
+            let syntax = syntax(arg_matches, file);
+            let directory = Path::new(cache::DIRECTORY);
+            match arg_matches.value_of("backend") {
+                Some("interpret") => {
+                    let ast = cache::load(directory, file, &code, syntax).erase();
+                    println!("{}", lamb::interpret::interpret(&ast))
+                }
+                Some("llvm") => match codegen::run(&cache::load(directory, file, &code, syntax)) {
+                    Ok(result) => println!("{}", result),
+                    Err(error_string) => println!("{}", error_string),
+                },
+                _ => {
+                    let typed_ast = cache::load(directory, file, &code, syntax);
+                    println!("{}", vm::run(&vm::compile(&typed_ast)))
+                }
+            }
+        }
+        ("disassemble", Some(arg_matches)) => {
+            let file = arg_matches
+                .value_of("file")
+                .expect("Argument <file> not found.");
+            let typed_ast = cache::load(
+                Path::new(cache::DIRECTORY),
+                file,
+                &lamb::read::build(file),
+                syntax(arg_matches, file),
+            );
+            print!("{}", vm::compile(&typed_ast));
+        }
+        ("cache", Some(arg_matches)) => match arg_matches.subcommand() {
+            ("clean", Some(_)) => cache::clean(Path::new(cache::DIRECTORY)),
+            _ => panic!("Unable to parse command line arguments."),
+        },
+        ("fmt", Some(arg_matches)) => {
+            let check = arg_matches.is_present("check");
+            let files: Vec<&str> = match arg_matches.values_of("files") {
+                Some(files) => files.collect(),
+                None => vec!["-"],
+            };
+            let mut unformatted = false;
+            for file in files {
+                if syntax(arg_matches, file) != Syntax::Core {
+                    panic!("Only the core syntax can be formatted, since the others don't keep comments.");
+                }
+                let code = if file == "-" {
+                    let mut code = String::new();
+                    std::io::stdin().read_to_string(&mut code).unwrap();
+                    code
+                } else {
+                    lamb::read::build(file)
+                };
+                let formatted = lamb::format(&code);
+                if check {
+                    if code != formatted {
+                        unformatted = true;
+                        let formatted_name = format!("{} (formatted)", file);
+                        print!(
+                            "{}",
+                            lamb::diff::unified(&code, &formatted, file, &formatted_name)
+                        );
+                    }
+                } else if file == "-" {
+                    print!("{}", formatted);
+                } else if code != formatted {
+                    std::fs::write(file, formatted)
+                        .unwrap_or_else(|error| panic!("Unable to write {}: {}", file, error));
+                }
+            }
+            if unformatted {
+                std::process::exit(1);
+            }
+        }
+        ("convert", Some(arg_matches)) => {
+            let file = arg_matches
+                .value_of("file")
+                .expect("Argument <file> not found.");
+            let code = lamb::read::build(file);
+            let from = syntax(arg_matches, file);
+            if arg_matches.is_present("typed") {
+                let typed_ast = TypedAST::new(&lamb::desugar(&code, from));
+                print!("{}", sexp::from_typed_ast(&typed_ast).pretty(WIDTH));
+                return;
+            }
+            let to = parse_syntax(arg_matches, "to").unwrap_or(match from {
+                Syntax::Core => Syntax::Ml,
+                _ => Syntax::Core,
+            });
+            print!("{}", lamb::convert(&code, from, to));
+        }
+        ("dump", Some(arg_matches)) => {
+            let file = arg_matches
+                .value_of("file")
+                .expect("Argument <file> not found.");
+            let code = lamb::read::build(file);
+            let from = syntax(arg_matches, file);
+            let document = match arg_matches.value_of("stage") {
+                Some("tokens") => {
+                    // The tokens are those of the core syntax, which other syntaxes are written in
+                    // first.
+                    let core = match from {
+                        Syntax::Core => code,
+                        _ => lamb::convert(&code, from, Syntax::Core),
+                    };
+                    let mut token_stream = lamb::tokenize(&core);
+                    json::tokens_document(&mut token_stream)
+                }
+                Some("typed") => {
+                    json::typed_ast_document(&TypedAST::new(&lamb::desugar(&code, from)))
+                }
+                _ => json::ast_document(&lamb::desugar_located(&code, from)),
+            };
+            println!("{}", document);
+        }
+        ("dot", Some(arg_matches)) => {
+            let file = arg_matches
+                .value_of("file")
+                .expect("Argument <file> not found.");
+            let ast = read_ast(arg_matches, file);
+            if arg_matches.is_present("call-graph") {
+                print!("{}", dot::call_graph(&ast));
+            } else if arg_matches.is_present("typed") {
+                print!("{}", dot::typed_ast(&TypedAST::new(&ast)));
+            } else {
+                print!("{}", dot::ast(&ast));
+            }
+        }
+        ("step", Some(arg_matches)) => {
+            let file = arg_matches
+                .value_of("file")
+                .expect("Argument <file> not found.");
+            let ast = read_ast(arg_matches, file);
+            lamb::type_check::type_of(&ast);
+            let beta_only = arg_matches.is_present("beta-only");
+            let max_steps: Option<usize> = parse_limit(arg_matches, "max-steps");
+
+            let steps = lamb::step::steps(&ast).unwrap_or_else(|error| {
+                eprintln!("{}: {}", file, error);
+                std::process::exit(1);
+            });
+
+            print!("{}", lamb::format::format(&ast));
+            let mut unprinted = None;
+            for step in steps.take(max_steps.unwrap_or(usize::MAX)) {
+                if !beta_only || step.kind == StepKind::Beta {
+                    println!("-->");
+                    print!("{}", lamb::format::format(&step.term));
+                    unprinted = None;
+                } else {
+                    unprinted = Some(step.term);
+                }
+            }
+            // The last term is always printed so the result of the reduction is visible.
+            if let Some(term) = unprinted {
+                println!("-->*");
+                print!("{}", lamb::format::format(&term));
+            }
+        }
+        ("debug", Some(arg_matches)) => {
+            let file = arg_matches
+                .value_of("file")
+                .expect("Argument <file> not found.");
+            let code = lamb::read::build(file);
+            let ast = lamb::desugar_located(&code, syntax(arg_matches, file));
+            lamb::type_check::type_of(&ast);
+
+            let stdin = std::io::stdin();
+            let mut debugger = Debugger::new(&code, stdin.lock(), std::io::stdout());
+            for breakpoint in arg_matches.values_of("break").into_iter().flatten() {
+                debugger.add_breakpoint(breakpoint);
+            }
+            let data =
+                lamb::interpret::interpret_with_observer(&ast, &Budget::default(), &mut debugger)
+                    .unwrap();
+            println!("{}", data);
+        }
         _ => panic!("Unable to parse command line arguments."),
     };
 }
@@ -1,5 +1,5 @@
 use super::type_check::Type;
-use crate::tokenize::{Token, TokenStream};
+use crate::tokenize::{Position, SyntaxError, Token, TokenStream};
 
 #[derive(Debug, PartialEq, Clone)]
 pub enum AST {
@@ -14,6 +14,49 @@ pub enum AST {
     FunctionApplication(FunctionApplication),
     FunctionDefinition(FunctionDefinition),
     RecursiveFunction(RecursiveFunction),
+    RecursiveFunctionGroup(RecursiveFunctionGroup),
+    DataDeclaration(DataDeclaration),
+    Construct(Construct),
+    Match(Match),
+    Reference(Box<AST>),
+    Dereference(Box<AST>),
+    Set(Box<AST>, Box<AST>),
+    PhysicalEquals(Box<AST>, Box<AST>),
+    /// `fixC(f)` is the fixed point of `f : (T -> T)`, where `T` must be a function type.
+    Fix(Box<AST>),
+    TypeDeclaration(TypeDeclaration),
+    /// Records where the node started in the source. Only produced from a stream built with
+    /// `TokenStream::build_located`.
+    Located(Position, Box<AST>),
+    /// The comments around the node. Only produced from a stream built with
+    /// `TokenStream::build_commented`.
+    Commented(Comments, Box<AST>),
+    /// Stands in for a node that failed to parse. Only produced from a stream built with
+    /// `TokenStream::build_recovering`.
+    Error,
+}
+
+/// The comments around a node or one of its arguments, which are the strings, types, lists and
+/// tuples that aren't nodes. Those before a closing bracket are trailing comments of the last node
+/// or argument inside it, and those after the last node are trailing comments of the root.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct Comments {
+    pub leading: Vec<String>,
+    /// The `;` comment after the node on the line it ends on, or after the `,` that follows it.
+    pub end_of_line: Option<String>,
+    pub trailing: Vec<String>,
+    /// The comments around the node's arguments, by the index of the argument in the order the
+    /// arguments start. Always empty for an argument.
+    pub arguments: Vec<(usize, Comments)>,
+}
+
+impl Comments {
+    pub fn is_empty(&self) -> bool {
+        self.leading.is_empty()
+            && self.end_of_line.is_none()
+            && self.trailing.is_empty()
+            && self.arguments.is_empty()
+    }
 }
 
 #[derive(Debug, PartialEq, Clone)]
@@ -47,197 +90,609 @@ pub struct RecursiveFunction {
     pub function_use: Box<AST>,
 }
 
+#[derive(Debug, PartialEq, Clone)]
+pub struct RecursiveBinding {
+    pub function_name: String,
+    pub argument_name: String,
+    pub argument_type: Type,
+    pub return_type: Type,
+    pub body: Box<AST>,
+}
+
+/// Every function in `functions` is in scope in the body of every other function, which is what
+/// allows `is_even`/`is_odd` style mutual recursion.
+#[derive(Debug, PartialEq, Clone)]
+pub struct RecursiveFunctionGroup {
+    pub functions: Vec<RecursiveBinding>,
+    pub function_use: Box<AST>,
+}
+
+/// Declares the data type `name`, whose values are built with `conC` from one of `constructors`,
+/// for use in `body`.
+#[derive(Debug, PartialEq, Clone)]
+pub struct DataDeclaration {
+    pub name: String,
+    pub constructors: Vec<Constructor>,
+    pub body: Box<AST>,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Constructor {
+    pub name: String,
+    pub fields: Vec<Type>,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Construct {
+    pub constructor: String,
+    pub arguments: Vec<AST>,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Match {
+    pub scrutinee: Box<AST>,
+    pub branches: Vec<MatchBranch>,
+}
+
+/// A `constructor` of `WILDCARD` matches any value and binds no variables.
+#[derive(Debug, PartialEq, Clone)]
+pub struct MatchBranch {
+    pub constructor: String,
+    pub variables: Vec<String>,
+    pub body: Box<AST>,
+}
+
+/// Makes `name` an alias of `ty` in `body`, where it is referred to as `namedT("name")`.
+#[derive(Debug, PartialEq, Clone)]
+pub struct TypeDeclaration {
+    pub name: String,
+    pub ty: Type,
+    pub body: Box<AST>,
+}
+
+pub const WILDCARD: &str = "_";
+
 impl AST {
     pub fn build(token_stream: &mut TokenStream) -> AST {
-        match token_stream.next() {
-            Some(token) => {
-                match token {
-                    Token::TrueLiteral => AST::TrueLiteral,
-                    Token::FalseLiteral => AST::FalseLiteral,
-                    Token::NumLiteral => {
-                        assert_eq!(Token::LeftParenthesis, token_stream.next().unwrap());
-                        if let Token::NumberLiteral(number) = token_stream.next().unwrap() {
-                            assert_eq!(Token::RightParenthesis, token_stream.next().unwrap());
-                            AST::NumberLiteral(number)
-                        } else {
-                            panic!("Number not found in NumC")
-                        }
-                    }
-                    Token::Plus => {
-                        assert_eq!(Token::LeftParenthesis, token_stream.next().unwrap());
-                        let ast1 = AST::build(token_stream);
-                        assert_eq!(Token::Comma, token_stream.next().unwrap());
-                        let ast2 = AST::build(token_stream);
-                        assert_eq!(Token::RightParenthesis, token_stream.next().unwrap());
-                        AST::Plus(Box::new(ast1), Box::new(ast2))
-                    }
-                    Token::Multiply => {
-                        assert_eq!(Token::LeftParenthesis, token_stream.next().unwrap());
-                        let ast1 = AST::build(token_stream);
-                        assert_eq!(Token::Comma, token_stream.next().unwrap());
-                        let ast2 = AST::build(token_stream);
-                        assert_eq!(Token::RightParenthesis, token_stream.next().unwrap());
-                        AST::Multiply(Box::new(ast1), Box::new(ast2))
-                    }
-                    Token::If => {
-                        assert_eq!(Token::LeftParenthesis, token_stream.next().unwrap());
-                        let ast1 = AST::build(token_stream);
-                        assert_eq!(Token::Comma, token_stream.next().unwrap());
-                        let ast2 = AST::build(token_stream);
-                        assert_eq!(Token::Comma, token_stream.next().unwrap());
-                        let ast3 = AST::build(token_stream);
-                        assert_eq!(Token::RightParenthesis, token_stream.next().unwrap());
-                        AST::If(If {
-                            condition: Box::new(ast1),
-                            then: Box::new(ast2),
-                            els: Box::new(ast3),
-                        })
-                    }
-                    Token::Identifier => {
-                        assert_eq!(Token::LeftParenthesis, token_stream.next().unwrap());
-                        assert_eq!(Token::Quote, token_stream.next().unwrap());
-                        let string_ast;
-                        match token_stream.next().unwrap() {
-                            Token::QuotedString(id) => {
-                                string_ast = AST::Identifier(id);
-                            }
-                            _ => panic!("String not found!"),
-                        }
-                        assert_eq!(Token::Quote, token_stream.next().unwrap());
-                        assert_eq!(Token::RightParenthesis, token_stream.next().unwrap());
-                        string_ast
-                    }
-                    Token::FunctionApplication => {
-                        assert_eq!(Token::LeftParenthesis, token_stream.next().unwrap());
-                        let ast1 = AST::build(token_stream);
-                        assert_eq!(Token::Comma, token_stream.next().unwrap());
-                        let ast2 = AST::build(token_stream);
-                        assert_eq!(Token::RightParenthesis, token_stream.next().unwrap());
-                        AST::FunctionApplication(FunctionApplication {
-                            function: Box::new(ast1),
-                            argument: Box::new(ast2),
-                        })
-                    }
-                    Token::FunctionDefinition => {
-                        //THE ARGUMENT NAME
-                        assert_eq!(Token::LeftParenthesis, token_stream.next().unwrap());
-                        assert_eq!(Token::Quote, token_stream.next().unwrap());
-                        let string_ast;
-                        match token_stream.next().unwrap() {
-                            Token::QuotedString(val) => {
-                                string_ast = val;
-                            }
-                            _ => panic!("String not found!"),
-                        }
-                        assert_eq!(Token::Quote, token_stream.next().unwrap());
-                        assert_eq!(Token::Comma, token_stream.next().unwrap());
-                        //THE ARGUMENT TYPE
-                        let arg_type = AST::parse_type(token_stream);
-
-                        assert_eq!(Token::Comma, token_stream.next().unwrap());
-                        //THE RETURN TYPE
-                        let ret_type = AST::parse_type(token_stream);
-
-                        assert_eq!(Token::Comma, token_stream.next().unwrap());
-
-                        //THE BODY
-                        let ast_body = AST::build(token_stream);
-
-                        assert_eq!(Token::RightParenthesis, token_stream.next().unwrap());
-
-                        AST::FunctionDefinition(FunctionDefinition {
-                            argument_name: string_ast,
-                            argument_type: arg_type,
-                            return_type: ret_type,
-                            body: Box::new(ast_body),
-                        })
-                    }
-                    Token::Equals => {
-                        assert_eq!(Token::LeftParenthesis, token_stream.next().unwrap());
-                        let ast1 = AST::build(token_stream);
-                        assert_eq!(Token::Comma, token_stream.next().unwrap());
-                        let ast2 = AST::build(token_stream);
-                        assert_eq!(Token::RightParenthesis, token_stream.next().unwrap());
-                        AST::Equals(Box::new(ast1), Box::new(ast2))
-                    }
-                    Token::RecursiveFunction => {
-                        assert_eq!(Token::LeftParenthesis, token_stream.next().unwrap());
-                        // 1st parameter
-                        assert_eq!(Token::Quote, token_stream.next().unwrap());
-                        let rec_func_name;
-                        match token_stream.next().unwrap() {
-                            Token::QuotedString(val) => {
-                                //Token::ID, not to be confused with idC
-                                rec_func_name = val;
-                            }
-                            _ => panic!("String not found!"),
-                        }
-                        if rec_func_name == "main" {
-                            panic!("'main' is a reserved function name");
-                        }
-                        assert_eq!(Token::Quote, token_stream.next().unwrap());
-                        assert_eq!(Token::Comma, token_stream.next().unwrap());
-                        // 2nd parameter
-                        assert_eq!(Token::Quote, token_stream.next().unwrap());
-                        let rec_arg_name;
-                        match token_stream.next().unwrap() {
-                            Token::QuotedString(val) => {
-                                //Token::ID, not to be confused with idC
-                                rec_arg_name = val;
-                            }
-                            _ => panic!("String not found!"),
-                        }
-                        assert_eq!(Token::Quote, token_stream.next().unwrap());
-                        assert_eq!(Token::Comma, token_stream.next().unwrap());
-                        // 3rd parameter
-                        let rec_arg_type = AST::parse_type(token_stream);
-                        assert_eq!(Token::Comma, token_stream.next().unwrap());
-                        // 4th parameter
-                        let rec_ret_type = AST::parse_type(token_stream);
-                        assert_eq!(Token::Comma, token_stream.next().unwrap());
-                        // 5th parameter
-                        let rec_body_ast = AST::build(token_stream);
-                        assert_eq!(Token::Comma, token_stream.next().unwrap());
-                        // 6th parameter
-                        let rec_func_use_ast = AST::build(token_stream);
-                        assert_eq!(Token::RightParenthesis, token_stream.next().unwrap());
-                        AST::RecursiveFunction(RecursiveFunction {
-                            function_name: rec_func_name,
-                            argument_name: rec_arg_name,
-                            argument_type: rec_arg_type,
-                            return_type: rec_ret_type,
-                            body: Box::new(rec_body_ast),
-                            function_use: Box::new(rec_func_use_ast),
-                        })
-                    }
-                    _ => panic!("Parsing error"), ////TODO: THIS should never happen
+        let depth = token_stream.depth();
+        let position = token_stream.next_position();
+        let leading = token_stream.take_comments();
+        let enclosing = token_stream.start_arguments();
+        let ast = match AST::build_node(token_stream) {
+            Ok(ast) => ast,
+            Err(mut error) => {
+                error.position = error.position.or(position);
+                if !token_stream.is_recovering() {
+                    panic!("{}", error);
+                }
+                // Text that isn't a token was already reported where the node went wrong.
+                if !token_stream.is_after_error() {
+                    token_stream.report(error);
                 }
+                AST::synchronize(token_stream, depth);
+                AST::Error
             }
-            None => panic!("No token found"),
+        };
+        let arguments = token_stream.end_arguments(enclosing);
+        let ast = match position {
+            Some(position) if token_stream.is_located() => AST::Located(position, Box::new(ast)),
+            _ => ast,
+        };
+        if !token_stream.is_commented() {
+            return ast;
+        }
+        let end_of_line = token_stream.take_end_of_line_comment();
+        // The comments before a closing bracket follow the last node inside it, and only the root
+        // is built with nothing left to consume.
+        let mut trailing = token_stream.take_closing_comments();
+        if token_stream.is_empty() {
+            trailing.append(&mut token_stream.take_trailing_comments());
+        }
+        let comments = Comments {
+            leading,
+            end_of_line,
+            trailing,
+            arguments,
+        };
+        if comments.is_empty() {
+            ast
+        } else {
+            AST::Commented(comments, Box::new(ast))
         }
     }
 
-    fn parse_type(token_stream: &mut TokenStream) -> Type {
-        match token_stream.next() {
-            Some(token) => match token {
-                Token::NumberType => Type::Number,
-                Token::BooleanType => Type::Boolean,
-                Token::FunctionType => {
-                    assert_eq!(Token::LeftParenthesis, token_stream.next().unwrap());
-                    let box1 = AST::parse_type(token_stream);
-                    assert_eq!(Token::Comma, token_stream.next().unwrap());
-                    let box2 = AST::parse_type(token_stream);
-                    assert_eq!(Token::RightParenthesis, token_stream.next().unwrap());
-                    Type::Function {
-                        argument: Box::new(box1),
-                        ret: Box::new(box2),
-                    }
+    /// Parses an argument of the node being built with `parse`, keeping the comments around it
+    /// for the node.
+    fn argument<T>(
+        token_stream: &mut TokenStream,
+        parse: impl FnOnce(&mut TokenStream) -> Result<T, SyntaxError>,
+    ) -> Result<T, SyntaxError> {
+        if !token_stream.is_commented() {
+            return parse(token_stream);
+        }
+        let index = token_stream.start_argument();
+        let leading = token_stream.take_comments();
+        let argument = parse(token_stream)?;
+        let comments = Comments {
+            leading,
+            end_of_line: token_stream.take_end_of_line_comment(),
+            trailing: token_stream.take_closing_comments(),
+            arguments: Vec::new(),
+        };
+        token_stream.comment_argument(index, comments);
+        Ok(argument)
+    }
+
+    fn string_argument(token_stream: &mut TokenStream) -> Result<String, SyntaxError> {
+        AST::argument(token_stream, AST::parse_quoted_string)
+    }
+
+    fn type_argument(token_stream: &mut TokenStream) -> Result<Type, SyntaxError> {
+        AST::argument(token_stream, AST::parse_type)
+    }
+
+    /// Skips the rest of a node that failed to parse, which started at `depth`, up to the `,` or
+    /// closing bracket after it.
+    fn synchronize(token_stream: &mut TokenStream, depth: isize) {
+        loop {
+            let at_depth = token_stream.depth() <= depth;
+            match token_stream.peek() {
+                Some(Token::Comma) | Some(Token::RightParenthesis) | Some(Token::RightBracket)
+                    if at_depth =>
+                {
+                    return
                 }
-                _ => panic!("Argument type not found!"),
-            },
-            None => panic!("No token found when parsing type"),
+                Some(_) => token_stream.next(),
+                None => return,
+            };
         }
     }
+
+    fn expect(token_stream: &mut TokenStream, expected: Token) -> Result<(), SyntaxError> {
+        if token_stream.peek() == Some(&expected) {
+            token_stream.next();
+            Ok(())
+        } else {
+            Err(AST::unexpected(token_stream, &format!("'{}'", expected)))
+        }
+    }
+
+    /// Consumes the keyword that starts a node and the `(` after it.
+    fn open(token_stream: &mut TokenStream) -> Result<(), SyntaxError> {
+        token_stream.next();
+        AST::expect(token_stream, Token::LeftParenthesis)
+    }
+
+    /// The error for finding the next token where `expected` should be. The token isn't
+    /// consumed, so that it can end the node that is skipped.
+    fn unexpected(token_stream: &mut TokenStream, expected: &str) -> SyntaxError {
+        let (found, position) = match token_stream.peek() {
+            Some(token) => (format!("'{}'", token), token_stream.next_position()),
+            None => (
+                String::from("the end of the input"),
+                Some(token_stream.end_position()),
+            ),
+        };
+        SyntaxError {
+            position,
+            message: format!("Expected {}, found {}", expected, found),
+        }
+    }
+
+    /// An error that isn't about a single token, which is reported at the start of the node.
+    fn invalid(message: String) -> SyntaxError {
+        SyntaxError {
+            position: None,
+            message,
+        }
+    }
+
+    fn build_node(token_stream: &mut TokenStream) -> Result<AST, SyntaxError> {
+        let ast = match token_stream.peek() {
+            Some(Token::TrueLiteral) => {
+                token_stream.next();
+                AST::TrueLiteral
+            }
+            Some(Token::FalseLiteral) => {
+                token_stream.next();
+                AST::FalseLiteral
+            }
+            Some(Token::NumLiteral) => {
+                AST::open(token_stream)?;
+                let number = match token_stream.peek() {
+                    Some(Token::NumberLiteral(number)) => *number,
+                    _ => return Err(AST::unexpected(token_stream, "a number")),
+                };
+                token_stream.next();
+                AST::expect(token_stream, Token::RightParenthesis)?;
+                AST::NumberLiteral(number)
+            }
+            Some(Token::Plus) => {
+                AST::open(token_stream)?;
+                let ast1 = AST::build(token_stream);
+                AST::expect(token_stream, Token::Comma)?;
+                let ast2 = AST::build(token_stream);
+                AST::expect(token_stream, Token::RightParenthesis)?;
+                AST::Plus(Box::new(ast1), Box::new(ast2))
+            }
+            Some(Token::Multiply) => {
+                AST::open(token_stream)?;
+                let ast1 = AST::build(token_stream);
+                AST::expect(token_stream, Token::Comma)?;
+                let ast2 = AST::build(token_stream);
+                AST::expect(token_stream, Token::RightParenthesis)?;
+                AST::Multiply(Box::new(ast1), Box::new(ast2))
+            }
+            Some(Token::If) => {
+                AST::open(token_stream)?;
+                let ast1 = AST::build(token_stream);
+                AST::expect(token_stream, Token::Comma)?;
+                let ast2 = AST::build(token_stream);
+                AST::expect(token_stream, Token::Comma)?;
+                let ast3 = AST::build(token_stream);
+                AST::expect(token_stream, Token::RightParenthesis)?;
+                AST::If(If {
+                    condition: Box::new(ast1),
+                    then: Box::new(ast2),
+                    els: Box::new(ast3),
+                })
+            }
+            Some(Token::Identifier) => {
+                AST::open(token_stream)?;
+                let id = AST::string_argument(token_stream)?;
+                AST::expect(token_stream, Token::RightParenthesis)?;
+                AST::Identifier(id)
+            }
+            Some(Token::FunctionApplication) => {
+                AST::open(token_stream)?;
+                let ast1 = AST::build(token_stream);
+                AST::expect(token_stream, Token::Comma)?;
+                let ast2 = AST::build(token_stream);
+                AST::expect(token_stream, Token::RightParenthesis)?;
+                AST::FunctionApplication(FunctionApplication {
+                    function: Box::new(ast1),
+                    argument: Box::new(ast2),
+                })
+            }
+            Some(Token::FunctionDefinition) => {
+                //THE ARGUMENT NAME
+                AST::open(token_stream)?;
+                let string_ast = AST::string_argument(token_stream)?;
+                AST::expect(token_stream, Token::Comma)?;
+                //THE ARGUMENT TYPE
+                let arg_type = AST::type_argument(token_stream)?;
+
+                AST::expect(token_stream, Token::Comma)?;
+                //THE RETURN TYPE
+                let ret_type = AST::type_argument(token_stream)?;
+
+                AST::expect(token_stream, Token::Comma)?;
+
+                //THE BODY
+                let ast_body = AST::build(token_stream);
+
+                AST::expect(token_stream, Token::RightParenthesis)?;
+
+                AST::FunctionDefinition(FunctionDefinition {
+                    argument_name: string_ast,
+                    argument_type: arg_type,
+                    return_type: ret_type,
+                    body: Box::new(ast_body),
+                })
+            }
+            Some(Token::Equals) => {
+                AST::open(token_stream)?;
+                let ast1 = AST::build(token_stream);
+                AST::expect(token_stream, Token::Comma)?;
+                let ast2 = AST::build(token_stream);
+                AST::expect(token_stream, Token::RightParenthesis)?;
+                AST::Equals(Box::new(ast1), Box::new(ast2))
+            }
+            Some(Token::Reference) => {
+                AST::open(token_stream)?;
+                let ast = AST::build(token_stream);
+                AST::expect(token_stream, Token::RightParenthesis)?;
+                AST::Reference(Box::new(ast))
+            }
+            Some(Token::Dereference) => {
+                AST::open(token_stream)?;
+                let ast = AST::build(token_stream);
+                AST::expect(token_stream, Token::RightParenthesis)?;
+                AST::Dereference(Box::new(ast))
+            }
+            Some(Token::Set) => {
+                AST::open(token_stream)?;
+                let ast1 = AST::build(token_stream);
+                AST::expect(token_stream, Token::Comma)?;
+                let ast2 = AST::build(token_stream);
+                AST::expect(token_stream, Token::RightParenthesis)?;
+                AST::Set(Box::new(ast1), Box::new(ast2))
+            }
+            Some(Token::PhysicalEquals) => {
+                AST::open(token_stream)?;
+                let ast1 = AST::build(token_stream);
+                AST::expect(token_stream, Token::Comma)?;
+                let ast2 = AST::build(token_stream);
+                AST::expect(token_stream, Token::RightParenthesis)?;
+                AST::PhysicalEquals(Box::new(ast1), Box::new(ast2))
+            }
+            Some(Token::Fix) => {
+                AST::open(token_stream)?;
+                let ast = AST::build(token_stream);
+                AST::expect(token_stream, Token::RightParenthesis)?;
+                AST::Fix(Box::new(ast))
+            }
+            Some(Token::TypeDeclaration) => {
+                AST::open(token_stream)?;
+                let name = AST::string_argument(token_stream)?;
+                AST::expect(token_stream, Token::Comma)?;
+                let ty = AST::type_argument(token_stream)?;
+                AST::expect(token_stream, Token::Comma)?;
+                let body = AST::build(token_stream);
+                AST::expect(token_stream, Token::RightParenthesis)?;
+                AST::TypeDeclaration(TypeDeclaration {
+                    name,
+                    ty,
+                    body: Box::new(body),
+                })
+            }
+            Some(Token::RecursiveFunction) => {
+                AST::open(token_stream)?;
+                // 1st parameter
+                let rec_func_name = AST::string_argument(token_stream)?;
+                if rec_func_name == "main" {
+                    return Err(AST::invalid(String::from(
+                        "'main' is a reserved function name",
+                    )));
+                }
+                AST::expect(token_stream, Token::Comma)?;
+                // 2nd parameter
+                let rec_arg_name = AST::string_argument(token_stream)?;
+                AST::expect(token_stream, Token::Comma)?;
+                // 3rd parameter
+                let rec_arg_type = AST::type_argument(token_stream)?;
+                AST::expect(token_stream, Token::Comma)?;
+                // 4th parameter
+                let rec_ret_type = AST::type_argument(token_stream)?;
+                AST::expect(token_stream, Token::Comma)?;
+                // 5th parameter
+                let rec_body_ast = AST::build(token_stream);
+                AST::expect(token_stream, Token::Comma)?;
+                // 6th parameter
+                let rec_func_use_ast = AST::build(token_stream);
+                AST::expect(token_stream, Token::RightParenthesis)?;
+                AST::RecursiveFunction(RecursiveFunction {
+                    function_name: rec_func_name,
+                    argument_name: rec_arg_name,
+                    argument_type: rec_arg_type,
+                    return_type: rec_ret_type,
+                    body: Box::new(rec_body_ast),
+                    function_use: Box::new(rec_func_use_ast),
+                })
+            }
+            Some(Token::RecursiveFunctionGroup) => {
+                AST::open(token_stream)?;
+                // 1st parameter
+                let functions = AST::parse_list(token_stream, AST::parse_recursive_binding)?;
+                if functions.is_empty() {
+                    return Err(AST::invalid(String::from(
+                        "recGroupC requires at least one function",
+                    )));
+                }
+                for (index, function) in functions.iter().enumerate() {
+                    if functions[..index]
+                        .iter()
+                        .any(|previous| previous.function_name == function.function_name)
+                    {
+                        return Err(AST::invalid(format!(
+                            "'{}' is bound more than once in recGroupC",
+                            function.function_name
+                        )));
+                    }
+                }
+                AST::expect(token_stream, Token::Comma)?;
+                // 2nd parameter
+                let function_use = AST::build(token_stream);
+                AST::expect(token_stream, Token::RightParenthesis)?;
+                AST::RecursiveFunctionGroup(RecursiveFunctionGroup {
+                    functions,
+                    function_use: Box::new(function_use),
+                })
+            }
+            Some(Token::DataDeclaration) => {
+                AST::open(token_stream)?;
+                // 1st parameter
+                let name = AST::string_argument(token_stream)?;
+                AST::expect(token_stream, Token::Comma)?;
+                // 2nd parameter
+                let constructors = AST::parse_list(token_stream, |token_stream| {
+                    AST::parse_tuple(token_stream, |token_stream| {
+                        let name = AST::string_argument(token_stream)?;
+                        AST::expect(token_stream, Token::Comma)?;
+                        let fields = AST::parse_list(token_stream, AST::type_argument)?;
+                        Ok(Constructor { name, fields })
+                    })
+                })?;
+                if constructors.is_empty() {
+                    return Err(AST::invalid(format!(
+                        "Data type '{}' has no constructors",
+                        name
+                    )));
+                }
+                AST::expect(token_stream, Token::Comma)?;
+                // 3rd parameter
+                let body = AST::build(token_stream);
+                AST::expect(token_stream, Token::RightParenthesis)?;
+                AST::DataDeclaration(DataDeclaration {
+                    name,
+                    constructors,
+                    body: Box::new(body),
+                })
+            }
+            Some(Token::Construct) => {
+                AST::open(token_stream)?;
+                let constructor = AST::string_argument(token_stream)?;
+                AST::expect(token_stream, Token::Comma)?;
+                let arguments =
+                    AST::parse_list(token_stream, |token_stream| Ok(AST::build(token_stream)))?;
+                AST::expect(token_stream, Token::RightParenthesis)?;
+                AST::Construct(Construct {
+                    constructor,
+                    arguments,
+                })
+            }
+            Some(Token::Match) => {
+                AST::open(token_stream)?;
+                let scrutinee = AST::build(token_stream);
+                AST::expect(token_stream, Token::Comma)?;
+                let branches = AST::parse_list(token_stream, |token_stream| {
+                    AST::parse_tuple(token_stream, |token_stream| {
+                        let constructor = AST::string_argument(token_stream)?;
+                        AST::expect(token_stream, Token::Comma)?;
+                        let variables = AST::parse_list(token_stream, AST::string_argument)?;
+                        AST::expect(token_stream, Token::Comma)?;
+                        let body = AST::build(token_stream);
+                        Ok(MatchBranch {
+                            constructor,
+                            variables,
+                            body: Box::new(body),
+                        })
+                    })
+                })?;
+                AST::expect(token_stream, Token::RightParenthesis)?;
+                AST::Match(Match {
+                    scrutinee: Box::new(scrutinee),
+                    branches,
+                })
+            }
+            _ => return Err(AST::unexpected(token_stream, "an expression")),
+        };
+        Ok(ast)
+    }
+
+    /// Parses `[element, element, ...]` as an argument, where each element is parsed by
+    /// `parse_element`.
+    fn parse_list<T>(
+        token_stream: &mut TokenStream,
+        mut parse_element: impl FnMut(&mut TokenStream) -> Result<T, SyntaxError>,
+    ) -> Result<Vec<T>, SyntaxError> {
+        AST::argument(token_stream, |token_stream| {
+            AST::expect(token_stream, Token::LeftBracket)?;
+            let mut elements = Vec::new();
+            if token_stream.peek() == Some(&Token::RightBracket) {
+                token_stream.next();
+                return Ok(elements);
+            }
+            loop {
+                elements.push(parse_element(token_stream)?);
+                match token_stream.peek() {
+                    Some(Token::Comma) => token_stream.next(),
+                    Some(Token::RightBracket) => {
+                        token_stream.next();
+                        return Ok(elements);
+                    }
+                    _ => return Err(AST::unexpected(token_stream, "',' or ']'")),
+                };
+            }
+        })
+    }
+
+    /// Parses `(fields)` as an argument, where the fields are parsed by `parse_fields`.
+    fn parse_tuple<T>(
+        token_stream: &mut TokenStream,
+        parse_fields: impl FnOnce(&mut TokenStream) -> Result<T, SyntaxError>,
+    ) -> Result<T, SyntaxError> {
+        AST::argument(token_stream, |token_stream| {
+            AST::expect(token_stream, Token::LeftParenthesis)?;
+            let fields = parse_fields(token_stream)?;
+            AST::expect(token_stream, Token::RightParenthesis)?;
+            Ok(fields)
+        })
+    }
+
+    /// Parses `("name", "argument", argument_type, return_type, body)`.
+    fn parse_recursive_binding(
+        token_stream: &mut TokenStream,
+    ) -> Result<RecursiveBinding, SyntaxError> {
+        AST::parse_tuple(token_stream, |token_stream| {
+            let function_name = AST::string_argument(token_stream)?;
+            if function_name == "main" {
+                return Err(AST::invalid(String::from(
+                    "'main' is a reserved function name",
+                )));
+            }
+            AST::expect(token_stream, Token::Comma)?;
+            let argument_name = AST::string_argument(token_stream)?;
+            AST::expect(token_stream, Token::Comma)?;
+            let argument_type = AST::type_argument(token_stream)?;
+            AST::expect(token_stream, Token::Comma)?;
+            let return_type = AST::type_argument(token_stream)?;
+            AST::expect(token_stream, Token::Comma)?;
+            let body = AST::build(token_stream);
+            Ok(RecursiveBinding {
+                function_name,
+                argument_name,
+                argument_type,
+                return_type,
+                body: Box::new(body),
+            })
+        })
+    }
+
+    fn parse_quoted_string(token_stream: &mut TokenStream) -> Result<String, SyntaxError> {
+        AST::expect(token_stream, Token::Quote)?;
+        let string = match token_stream.peek() {
+            Some(Token::QuotedString(string)) => string.clone(),
+            _ => return Err(AST::unexpected(token_stream, "a string")),
+        };
+        token_stream.next();
+        AST::expect(token_stream, Token::Quote)?;
+        Ok(string)
+    }
+
+    fn parse_type(token_stream: &mut TokenStream) -> Result<Type, SyntaxError> {
+        let ty = match token_stream.peek() {
+            Some(Token::NumberType) => {
+                token_stream.next();
+                Type::Number
+            }
+            Some(Token::BooleanType) => {
+                token_stream.next();
+                Type::Boolean
+            }
+            Some(Token::ReferenceType) => {
+                AST::open(token_stream)?;
+                let ty = AST::parse_type(token_stream)?;
+                AST::expect(token_stream, Token::RightParenthesis)?;
+                Type::Reference(Box::new(ty))
+            }
+            Some(Token::NamedType) => {
+                AST::open(token_stream)?;
+                let name = AST::parse_quoted_string(token_stream)?;
+                AST::expect(token_stream, Token::RightParenthesis)?;
+                Type::Named(name)
+            }
+            Some(Token::DataType) => {
+                AST::open(token_stream)?;
+                let name = AST::parse_quoted_string(token_stream)?;
+                AST::expect(token_stream, Token::RightParenthesis)?;
+                Type::Data(name)
+            }
+            Some(Token::FunctionType) => {
+                AST::open(token_stream)?;
+                let box1 = AST::parse_type(token_stream)?;
+                AST::expect(token_stream, Token::Comma)?;
+                let box2 = AST::parse_type(token_stream)?;
+                AST::expect(token_stream, Token::RightParenthesis)?;
+                Type::Function {
+                    argument: Box::new(box1),
+                    ret: Box::new(box2),
+                }
+            }
+            _ => return Err(AST::unexpected(token_stream, "a type")),
+        };
+        Ok(ty)
+    }
 }
 
 #[cfg(test)]
@@ -253,7 +708,7 @@ mod tests {
             Token::NumberLiteral(1),
             Token::RightParenthesis,
         ]);
-        let mut token_stream = TokenStream::build_test(tokens, 0);
+        let mut token_stream = TokenStream::build_test(tokens);
         assert_eq!(AST::build(&mut token_stream), AST::NumberLiteral(1));
     }
 
@@ -273,7 +728,7 @@ mod tests {
             Token::RightParenthesis,
             Token::RightParenthesis,
         ]);
-        let mut token_stream = TokenStream::build_test(tokens, 0);
+        let mut token_stream = TokenStream::build_test(tokens);
         assert_eq!(
             AST::build(&mut token_stream),
             AST::Plus(
@@ -297,7 +752,7 @@ mod tests {
             Token::RightParenthesis,
             Token::RightParenthesis,
         ]);
-        let mut token_stream = TokenStream::build_test(tokens, 0);
+        let mut token_stream = TokenStream::build_test(tokens);
         AST::build(&mut token_stream);
     }
 
@@ -317,7 +772,7 @@ mod tests {
             Token::RightParenthesis,
             Token::RightParenthesis,
         ]);
-        let mut token_stream = TokenStream::build_test(tokens, 0);
+        let mut token_stream = TokenStream::build_test(tokens);
         assert_eq!(
             AST::build(&mut token_stream),
             AST::Multiply(
@@ -345,7 +800,7 @@ mod tests {
             Token::RightParenthesis,
             // Token::RightParenthesis,
         ]);
-        let mut token_stream = TokenStream::build_test(tokens, 0);
+        let mut token_stream = TokenStream::build_test(tokens);
         AST::build(&mut token_stream);
     }
 
@@ -362,7 +817,7 @@ mod tests {
             Token::FalseLiteral,
             Token::RightParenthesis,
         ]);
-        let mut token_stream = TokenStream::build_test(tokens, 0);
+        let mut token_stream = TokenStream::build_test(tokens);
         assert_eq!(
             AST::build(&mut token_stream),
             AST::If(If {
@@ -385,7 +840,7 @@ mod tests {
             Token::FalseLiteral,
             Token::RightParenthesis,
         ]);
-        let mut token_stream = TokenStream::build_test(tokens, 0);
+        let mut token_stream = TokenStream::build_test(tokens);
         AST::build(&mut token_stream);
     }
     #[test]
@@ -398,7 +853,7 @@ mod tests {
             Token::Quote,
             Token::RightParenthesis,
         ]);
-        let mut token_stream = TokenStream::build_test(tokens, 0);
+        let mut token_stream = TokenStream::build_test(tokens);
         assert_eq!(
             AST::build(&mut token_stream),
             AST::Identifier("x".to_string())
@@ -415,7 +870,7 @@ mod tests {
             Token::QuotedString("x".to_string()),
             Token::RightParenthesis,
         ]);
-        let mut token_stream = TokenStream::build_test(tokens, 0);
+        let mut token_stream = TokenStream::build_test(tokens);
         AST::build(&mut token_stream);
     }
     //Tests fir appC and fdC omitted here, done externally.
@@ -436,7 +891,7 @@ mod tests {
             Token::RightParenthesis,
             Token::RightParenthesis,
         ]);
-        let mut token_stream = TokenStream::build_test(tokens, 0);
+        let mut token_stream = TokenStream::build_test(tokens);
         AST::build(&mut token_stream);
     }
 
@@ -456,7 +911,97 @@ mod tests {
             Token::RightParenthesis,
             Token::RightParenthesis,
         ]);
-        let mut token_stream = TokenStream::build_test(tokens, 0);
+        let mut token_stream = TokenStream::build_test(tokens);
         AST::build(&mut token_stream);
     }
+
+    fn build_recovering(code: &str) -> (AST, Vec<String>) {
+        let mut token_stream = TokenStream::build_recovering(code.chars());
+        let ast = AST::build(&mut token_stream);
+        let errors = token_stream.take_errors();
+        (ast, errors.iter().map(ToString::to_string).collect())
+    }
+
+    #[test]
+    fn recover_from_errors() {
+        let (ast, errors) = build_recovering("plusC(multC(numC(1) numC(2)), ifC(trueC, numC(3)))");
+        assert_eq!(ast, AST::Plus(Box::new(AST::Error), Box::new(AST::Error)));
+        assert_eq!(
+            errors,
+            vec![
+                "1:21: Expected ',', found 'numC'",
+                "1:49: Expected ',', found ')'",
+            ]
+        );
+    }
+
+    #[test]
+    fn recover_in_lists() {
+        let (ast, errors) = build_recovering(r#"conC("Pair", [numC(1), , fdC("x", [], trueC)])"#);
+        assert_eq!(
+            ast,
+            AST::Construct(Construct {
+                constructor: "Pair".to_string(),
+                arguments: vec![AST::NumberLiteral(1), AST::Error, AST::Error],
+            })
+        );
+        assert_eq!(
+            errors,
+            vec![
+                "1:24: Expected an expression, found ','",
+                "1:35: Expected a type, found '['",
+            ]
+        );
+    }
+
+    #[test]
+    fn recover_from_invalid_nodes() {
+        let (ast, errors) = build_recovering(
+            r#"appC(recC("main", "x", numT, numT, idC("x"), idC("main")), recGroupC([], numC(1)))"#,
+        );
+        assert_eq!(
+            ast,
+            AST::FunctionApplication(FunctionApplication {
+                function: Box::new(AST::Error),
+                argument: Box::new(AST::Error),
+            })
+        );
+        assert_eq!(
+            errors,
+            vec![
+                "1:6: 'main' is a reserved function name",
+                "1:60: recGroupC requires at least one function",
+            ]
+        );
+    }
+
+    #[test]
+    fn recover_without_cascades() {
+        let (_, errors) = build_recovering("plusC(plusX(numC(1)), numC(99999999999999999999))");
+        assert_eq!(
+            errors,
+            vec![
+                "1:7: Unknown keyword 'plusX'",
+                "1:27: Number '99999999999999999999' is out of range",
+            ]
+        );
+        let (_, errors) = build_recovering(")))");
+        assert_eq!(errors, vec!["1:1: Expected an expression, found ')'"]);
+    }
+
+    #[test]
+    fn recover_at_end_of_input() {
+        let (ast, errors) = build_recovering("plusC(numC(1),\n  ");
+        assert_eq!(ast, AST::Error);
+        assert_eq!(
+            errors,
+            vec!["2:3: Expected an expression, found the end of the input"]
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "1:15: Expected ',', found 'numC'")]
+    fn build_panics_at_first_error() {
+        AST::build(&mut TokenStream::build("plusC(numC(1) numC(2))".chars()));
+    }
 }
@@ -11,8 +11,53 @@ pub fn build(input_file: &str) -> String {
 }
 
 #[cfg(test)]
-mod tests {
+pub(crate) mod tests {
     use super::*;
+    use std::fs;
+
+    /// The inputs that fail to parse.
+    pub(crate) const SYNTAX_ERRORS: [&str; 3] = [
+        "function_application_parse_fail",
+        "function_application_parse_fail_2",
+        "syntax_errors",
+    ];
+
+    /// The inputs that parse but fail to type check.
+    pub(crate) const TYPE_ERRORS: [&str; 8] = [
+        "fail_function_two_plus_one",
+        "fix_non_function_fail",
+        "match_non_exhaustive",
+        "match_redundant",
+        "rec_c_fail",
+        "reference_equals_fail",
+        "type_alias_out_of_scope_fail",
+        "unbound_identifier",
+    ];
+
+    /// The name and code of every core syntax input in `tests/inputs`, in order of name, other
+    /// than those in `excluded`.
+    pub(crate) fn inputs(excluded: &[&str]) -> Vec<(String, String)> {
+        let mut inputs = Vec::new();
+        for entry in fs::read_dir("tests/inputs").unwrap() {
+            let path = entry.unwrap().path();
+            if !matches!(path.extension(), Some(extension) if extension == "txt") {
+                continue;
+            }
+            let name = path.file_stem().unwrap().to_string_lossy().into_owned();
+            if !excluded.contains(&name.as_str()) {
+                inputs.push((name, build(path.to_str().unwrap())));
+            }
+        }
+        for name in excluded {
+            assert!(
+                fs::metadata(format!("tests/inputs/{}.txt", name)).is_ok(),
+                "'{}' is excluded but isn't an input",
+                name
+            );
+        }
+        inputs.sort();
+        inputs
+    }
 
     fn test(input_file: &str, expected: &str) {
         let code_string = build(input_file);
@@ -0,0 +1,483 @@
+use crate::{
+    parse::{AST, WILDCARD},
+    value,
+};
+use std::{cell::RefCell, rc::Rc};
+
+/// An `AST` whose variables have been replaced by de Bruijn indices and whose types have been
+/// erased. Unlike `interpret::interpret`, which scopes variables dynamically, evaluating a
+/// `Resolved` scopes them lexically, as the type checker does.
+#[derive(Debug, PartialEq)]
+pub enum Resolved {
+    NumberLiteral(i64),
+    TrueLiteral,
+    FalseLiteral,
+    Plus(Box<Resolved>, Box<Resolved>),
+    Multiply(Box<Resolved>, Box<Resolved>),
+    Equals(Box<Resolved>, Box<Resolved>),
+    PhysicalEquals(Box<Resolved>, Box<Resolved>),
+    Set(Box<Resolved>, Box<Resolved>),
+    Reference(Box<Resolved>),
+    Dereference(Box<Resolved>),
+    Fix(Box<Resolved>),
+    If(Box<Resolved>, Box<Resolved>, Box<Resolved>),
+    /// The variable bound by the `index`th closest enclosing binder of the function it is in.
+    Variable(usize),
+    /// The variable at the index in the captures of the function it is in.
+    Captured(usize),
+    /// A function whose body binds its argument. The closure captures the value of each of
+    /// `captures`, which are the body's free variables.
+    Function {
+        captures: Vec<Resolved>,
+        body: Rc<Resolved>,
+    },
+    Application(Box<Resolved>, Box<Resolved>),
+    /// `recC` and `recGroupC`. Each body binds every function of the group, in order, and then its
+    /// argument, and the bodies share `captures`. The use binds every function of the group.
+    RecursiveFunctionGroup {
+        captures: Vec<Resolved>,
+        bodies: Rc<Vec<Resolved>>,
+        function_use: Box<Resolved>,
+    },
+    /// The tag of the constructor in its data type's declaration, its name and its arguments.
+    Construct(usize, String, Vec<Resolved>),
+    Match(Box<Resolved>, Vec<ResolvedBranch>),
+}
+
+/// A branch of `Resolved::Match`, whose body binds the constructor's fields in order.
+#[derive(Debug, PartialEq)]
+pub struct ResolvedBranch {
+    /// `None` for the wildcard.
+    pub constructor: Option<String>,
+    pub body: Resolved,
+}
+
+pub fn resolve(ast: &AST) -> Resolved {
+    let mut resolver = Resolver {
+        contexts: vec![Context::default()],
+        constructors: Vec::new(),
+    };
+    resolver.resolve(ast)
+}
+
+/// A function being resolved.
+#[derive(Default)]
+struct Context {
+    /// The name bound by every enclosing binder in the function, innermost last.
+    locals: Vec<String>,
+    /// The free variables of the function, with how each is found in the enclosing function.
+    captures: Vec<(String, Resolved)>,
+}
+
+struct Resolver {
+    contexts: Vec<Context>,
+    /// The name and tag of every constructor in scope, innermost last.
+    constructors: Vec<(String, usize)>,
+}
+
+impl Resolver {
+    fn resolve(&mut self, ast: &AST) -> Resolved {
+        let mut resolve_box = |ast: &AST| Box::new(self.resolve(ast));
+        match ast {
+            AST::NumberLiteral(number) => Resolved::NumberLiteral(*number),
+            AST::TrueLiteral => Resolved::TrueLiteral,
+            AST::FalseLiteral => Resolved::FalseLiteral,
+            AST::Plus(lhs, rhs) => Resolved::Plus(resolve_box(lhs), resolve_box(rhs)),
+            AST::Multiply(lhs, rhs) => Resolved::Multiply(resolve_box(lhs), resolve_box(rhs)),
+            AST::Equals(lhs, rhs) => Resolved::Equals(resolve_box(lhs), resolve_box(rhs)),
+            AST::PhysicalEquals(lhs, rhs) => {
+                Resolved::PhysicalEquals(resolve_box(lhs), resolve_box(rhs))
+            }
+            AST::Set(reference, value) => Resolved::Set(resolve_box(reference), resolve_box(value)),
+            AST::Reference(ast) => Resolved::Reference(resolve_box(ast)),
+            AST::Dereference(ast) => Resolved::Dereference(resolve_box(ast)),
+            AST::Fix(ast) => Resolved::Fix(resolve_box(ast)),
+            AST::If(if_struct) => Resolved::If(
+                resolve_box(&if_struct.condition),
+                resolve_box(&if_struct.then),
+                resolve_box(&if_struct.els),
+            ),
+            AST::Identifier(name) => self.variable(name, self.contexts.len() - 1),
+            AST::FunctionApplication(function_application) => Resolved::Application(
+                resolve_box(&function_application.function),
+                resolve_box(&function_application.argument),
+            ),
+            AST::FunctionDefinition(function_definition) => {
+                let (captures, mut bodies) = self.functions(
+                    &[],
+                    &[(
+                        &function_definition.argument_name,
+                        &*function_definition.body,
+                    )],
+                );
+                Resolved::Function {
+                    captures,
+                    body: Rc::new(bodies.remove(0)),
+                }
+            }
+            AST::RecursiveFunction(recursive_function) => {
+                let function_names = [&recursive_function.function_name];
+                let (captures, bodies) = self.functions(
+                    &function_names,
+                    &[(&recursive_function.argument_name, &*recursive_function.body)],
+                );
+                Resolved::RecursiveFunctionGroup {
+                    captures,
+                    bodies: Rc::new(bodies),
+                    function_use: Box::new(
+                        self.resolve_in(&recursive_function.function_use, &function_names),
+                    ),
+                }
+            }
+            AST::RecursiveFunctionGroup(recursive_function_group) => {
+                let function_names: Vec<&String> = recursive_function_group
+                    .functions
+                    .iter()
+                    .map(|function| &function.function_name)
+                    .collect();
+                let functions: Vec<(&String, &AST)> = recursive_function_group
+                    .functions
+                    .iter()
+                    .map(|function| (&function.argument_name, &*function.body))
+                    .collect();
+                let (captures, bodies) = self.functions(&function_names, &functions);
+                Resolved::RecursiveFunctionGroup {
+                    captures,
+                    bodies: Rc::new(bodies),
+                    function_use: Box::new(
+                        self.resolve_in(&recursive_function_group.function_use, &function_names),
+                    ),
+                }
+            }
+            AST::DataDeclaration(data_declaration) => {
+                let length = self.constructors.len();
+                self.constructors.extend(
+                    data_declaration
+                        .constructors
+                        .iter()
+                        .enumerate()
+                        .map(|(tag, constructor)| (constructor.name.clone(), tag)),
+                );
+                let body = self.resolve(&data_declaration.body);
+                self.constructors.truncate(length);
+                body
+            }
+            AST::TypeDeclaration(type_declaration) => self.resolve(&type_declaration.body),
+            AST::Located(_, ast) | AST::Commented(_, ast) => self.resolve(ast),
+            AST::Error => panic!("Can't resolve a node that failed to parse"),
+            AST::Construct(construct) => Resolved::Construct(
+                self.tag(&construct.constructor),
+                construct.constructor.clone(),
+                construct
+                    .arguments
+                    .iter()
+                    .map(|argument| self.resolve(argument))
+                    .collect(),
+            ),
+            AST::Match(match_struct) => Resolved::Match(
+                Box::new(self.resolve(&match_struct.scrutinee)),
+                match_struct
+                    .branches
+                    .iter()
+                    .map(|branch| {
+                        let variables: Vec<&String> = branch.variables.iter().collect();
+                        ResolvedBranch {
+                            constructor: if branch.constructor == WILDCARD {
+                                None
+                            } else {
+                                Some(branch.constructor.clone())
+                            },
+                            body: self.resolve_in(&branch.body, &variables),
+                        }
+                    })
+                    .collect(),
+            ),
+        }
+    }
+
+    fn tag(&self, constructor: &str) -> usize {
+        match self
+            .constructors
+            .iter()
+            .rev()
+            .find(|(name, _)| name == constructor)
+        {
+            Some((_, tag)) => *tag,
+            None => panic!("Constructor '{}' is not declared", constructor),
+        }
+    }
+
+    /// Resolves `ast` with `names` bound, in order, inside the current function.
+    fn resolve_in(&mut self, ast: &AST, names: &[&String]) -> Resolved {
+        let locals = &mut self.contexts.last_mut().unwrap().locals;
+        locals.extend(names.iter().map(|name| name.to_string()));
+        let resolved = self.resolve(ast);
+        let locals = &mut self.contexts.last_mut().unwrap().locals;
+        locals.truncate(locals.len() - names.len());
+        resolved
+    }
+
+    /// Resolves the body of each of `functions`, given with its argument, with
+    /// `recursive_names` and then the argument bound. Returns the variables that the bodies
+    /// capture between them and the bodies.
+    fn functions(
+        &mut self,
+        recursive_names: &[&String],
+        functions: &[(&String, &AST)],
+    ) -> (Vec<Resolved>, Vec<Resolved>) {
+        self.contexts.push(Context::default());
+        let bodies = functions
+            .iter()
+            .map(|(argument_name, body)| {
+                let mut names = recursive_names.to_vec();
+                names.push(argument_name);
+                self.resolve_in(body, &names)
+            })
+            .collect();
+        let context = self.contexts.pop().unwrap();
+        let captures = context
+            .captures
+            .into_iter()
+            .map(|(_, variable)| variable)
+            .collect();
+        (captures, bodies)
+    }
+
+    /// Finds `name` in the function being resolved at `depth`, capturing it from the enclosing
+    /// functions if necessary.
+    fn variable(&mut self, name: &str, depth: usize) -> Resolved {
+        let context = &self.contexts[depth];
+        if let Some(index) = context.locals.iter().rev().position(|local| local == name) {
+            return Resolved::Variable(index);
+        }
+        if let Some(index) = context
+            .captures
+            .iter()
+            .position(|(captured, _)| captured == name)
+        {
+            return Resolved::Captured(index);
+        }
+        if depth == 0 {
+            panic!("Unable to find identifier: {:?}", name)
+        }
+
+        let variable = self.variable(name, depth - 1);
+        let captures = &mut self.contexts[depth].captures;
+        captures.push((name.to_string(), variable));
+        Resolved::Captured(captures.len() - 1)
+    }
+}
+
+pub type Value = value::Value<Closure, RecursiveGroup>;
+pub type Constructed = value::Constructed<Closure, RecursiveGroup>;
+
+#[derive(Debug, PartialEq)]
+pub struct Closure {
+    body: Rc<Resolved>,
+    captures: Vec<Value>,
+}
+
+/// The functions of a `recC` or `recGroupC`. The captures don't hold the functions themselves,
+/// which are bound each time one of them is applied, so there is no reference cycle.
+#[derive(Debug, PartialEq)]
+pub struct RecursiveGroup {
+    bodies: Rc<Vec<Resolved>>,
+    captures: Vec<Value>,
+}
+
+/// Evaluates `resolved` by value. Evaluation recurses on the Rust stack, so programs that recurse
+/// very deeply should be run with `interpret::interpret` instead.
+pub fn evaluate(resolved: &Resolved) -> Value {
+    evaluator(resolved, &mut Vec::new(), &[])
+}
+
+/// `environment` holds the value of every variable bound in the function being evaluated,
+/// innermost last, and `captures` the values that its closure captured.
+fn evaluator(resolved: &Resolved, environment: &mut Vec<Value>, captures: &[Value]) -> Value {
+    match resolved {
+        Resolved::NumberLiteral(number) => Value::Number(*number),
+        Resolved::TrueLiteral => Value::Boolean(true),
+        Resolved::FalseLiteral => Value::Boolean(false),
+        Resolved::Plus(lhs, rhs) => Value::Number(
+            evaluator(lhs, environment, captures).number()
+                + evaluator(rhs, environment, captures).number(),
+        ),
+        Resolved::Multiply(lhs, rhs) => Value::Number(
+            evaluator(lhs, environment, captures).number()
+                * evaluator(rhs, environment, captures).number(),
+        ),
+        Resolved::Equals(lhs, rhs) => Value::Boolean(
+            evaluator(lhs, environment, captures) == evaluator(rhs, environment, captures),
+        ),
+        Resolved::PhysicalEquals(lhs, rhs) => Value::Boolean(Rc::ptr_eq(
+            evaluator(lhs, environment, captures).reference(),
+            evaluator(rhs, environment, captures).reference(),
+        )),
+        Resolved::Set(reference, value) => {
+            let reference = evaluator(reference, environment, captures);
+            let value = evaluator(value, environment, captures);
+            *reference.reference().borrow_mut() = value.clone();
+            value
+        }
+        Resolved::Reference(resolved) => Value::Reference(Rc::new(RefCell::new(evaluator(
+            resolved,
+            environment,
+            captures,
+        )))),
+        Resolved::Dereference(resolved) => {
+            let reference = evaluator(resolved, environment, captures);
+            let value = reference.reference().borrow().clone();
+            value
+        }
+        Resolved::Fix(resolved) => {
+            Value::Fixpoint(Rc::new(evaluator(resolved, environment, captures)))
+        }
+        Resolved::If(condition, then, els) => {
+            if evaluator(condition, environment, captures).boolean() {
+                evaluator(then, environment, captures)
+            } else {
+                evaluator(els, environment, captures)
+            }
+        }
+        Resolved::Variable(index) => environment[environment.len() - 1 - index].clone(),
+        Resolved::Captured(index) => captures[*index].clone(),
+        Resolved::Function {
+            captures: captured,
+            body,
+        } => Value::Closure(Rc::new(Closure {
+            body: body.clone(),
+            captures: captured
+                .iter()
+                .map(|variable| evaluator(variable, environment, captures))
+                .collect(),
+        })),
+        Resolved::Application(function, argument) => {
+            let function = evaluator(function, environment, captures);
+            let argument = evaluator(argument, environment, captures);
+            apply(&function, argument)
+        }
+        Resolved::RecursiveFunctionGroup {
+            captures: captured,
+            bodies,
+            function_use,
+        } => {
+            let group = Rc::new(RecursiveGroup {
+                bodies: bodies.clone(),
+                captures: captured
+                    .iter()
+                    .map(|variable| evaluator(variable, environment, captures))
+                    .collect(),
+            });
+            let count = bodies.len();
+            environment.extend((0..count).map(|index| Value::Recursive(group.clone(), index)));
+            let value = evaluator(function_use, environment, captures);
+            environment.truncate(environment.len() - count);
+            value
+        }
+        Resolved::Construct(tag, constructor, arguments) => {
+            Value::Constructed(Rc::new(Constructed {
+                tag: *tag,
+                constructor: constructor.clone(),
+                fields: arguments
+                    .iter()
+                    .map(|argument| evaluator(argument, environment, captures))
+                    .collect(),
+            }))
+        }
+        Resolved::Match(scrutinee, branches) => {
+            let constructed = match evaluator(scrutinee, environment, captures) {
+                Value::Constructed(constructed) => constructed,
+                _ => panic!("Trying to access a non-active variant of Value enum"),
+            };
+            let branch = branches
+                .iter()
+                .find(|branch| {
+                    branch.constructor.is_none()
+                        || branch.constructor.as_ref() == Some(&constructed.constructor)
+                })
+                .unwrap_or_else(|| panic!("No branch matches '{}'", constructed.constructor));
+
+            match branch.constructor {
+                Some(_) => {
+                    let count = constructed.fields.len();
+                    environment.extend(constructed.fields.iter().cloned());
+                    let value = evaluator(&branch.body, environment, captures);
+                    environment.truncate(environment.len() - count);
+                    value
+                }
+                // A wildcard branch has no variables, so nothing is bound for it.
+                None => evaluator(&branch.body, environment, captures),
+            }
+        }
+    }
+}
+
+fn apply(function: &Value, argument: Value) -> Value {
+    match function {
+        Value::Closure(closure) => evaluator(&closure.body, &mut vec![argument], &closure.captures),
+        Value::Recursive(group, index) => {
+            let mut environment: Vec<Value> = (0..group.bodies.len())
+                .map(|index| Value::Recursive(group.clone(), index))
+                .collect();
+            environment.push(argument);
+            evaluator(&group.bodies[*index], &mut environment, &group.captures)
+        }
+        // fixC(f)(argument) = f(fixC(f))(argument)
+        Value::Fixpoint(generator) => {
+            let unrolled = apply(generator, function.clone());
+            apply(&unrolled, argument)
+        }
+        _ => panic!("Trying to access a non-active variant of Value enum"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{read, tokenize::TokenStream};
+
+    fn parse_file(name: &str) -> AST {
+        let code = read::build(&format!("tests/inputs/{}.txt", name));
+        let mut token_stream = TokenStream::build(code.chars());
+        AST::build(&mut token_stream)
+    }
+
+    #[test]
+    fn resolve_shadowing() {
+        let code = r#"fdC("x", numT, funT(numT, numT), fdC("y", numT, numT, fdC("x", numT, numT, plusC(idC("x"), idC("y")))))"#;
+        let ast = AST::build(&mut TokenStream::build(code.chars()));
+        // The innermost function captures `y` from the function around it, which captures
+        // nothing since the outermost `x` is shadowed.
+        assert_eq!(
+            resolve(&ast),
+            Resolved::Function {
+                captures: Vec::new(),
+                body: Rc::new(Resolved::Function {
+                    captures: Vec::new(),
+                    body: Rc::new(Resolved::Function {
+                        captures: vec![Resolved::Variable(0)],
+                        body: Rc::new(Resolved::Plus(
+                            Box::new(Resolved::Variable(0)),
+                            Box::new(Resolved::Captured(0))
+                        )),
+                    }),
+                }),
+            }
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Unable to find identifier")]
+    fn resolve_unbound() {
+        resolve(&parse_file("unbound_identifier"));
+    }
+
+    #[test]
+    fn evaluate_matches_interpret() {
+        // `evaluate` recurses on the Rust stack, which a million nested calls overflow.
+        value::tests::assert_matches_interpret(
+            &["is_even_million", "summation_million", "tail_call_million"],
+            |ast| evaluate(&resolve(ast)),
+        );
+    }
+}
@@ -0,0 +1,688 @@
+//! Lisp-style s-expressions for exchanging programs with other tools:
+//!
+//! ```text
+//! (rec f x num num
+//!   (if (= x 0) 1 (* x (f (+ x -1))))
+//!   (f 5))
+//! ```
+//!
+//! Every form has a keyword at its head, except applications, which are `(f x)` and curried by
+//! `(f x y)`. The other forms are `(+ a b)`, `(* a b)`, `(= a b)`, `(eq? a b)`, `(if c t e)`,
+//! `(fun (x T) R body)`, `(rec f x T R body use)`, `(rec-group ((f x T R body) ...) use)`,
+//! `(data Name ((Constructor T ...) ...) body)`, `(con Constructor argument ...)`,
+//! `(match e (Constructor (x ...) body) ...)`, `(ref e)`, `(deref e)`, `(set! r e)`, `(fix e)` and
+//! `(type name T body)`, where types are `num`, `bool`, `(ref T)`, `(-> A R)`, `(data Name)` and
+//! `(named name)`. Names that would otherwise be read as numbers or keywords are written between
+//! bars, like `|if|`. A `TypedAST` is written with every node in a `(: T node)` form.
+
+use crate::{
+    format::{self, text, Doc},
+    parse::{
+        Construct, Constructor, DataDeclaration, FunctionApplication, FunctionDefinition, If,
+        Match, MatchBranch, RecursiveBinding, RecursiveFunction, RecursiveFunctionGroup,
+        TypeDeclaration, AST,
+    },
+    type_check::{Type, TypedAST, TypedASTEnum},
+};
+use std::{fmt, fmt::Display};
+
+/// The extension of files written as s-expressions.
+pub const EXTENSION: &str = "sexp";
+
+const KEYWORDS: [&str; 18] = [
+    "+",
+    "*",
+    "=",
+    "eq?",
+    "if",
+    "fun",
+    "rec",
+    "rec-group",
+    "data",
+    "con",
+    "match",
+    "ref",
+    "deref",
+    "set!",
+    "fix",
+    "type",
+    "true",
+    "false",
+];
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum Sexp {
+    /// A number, keyword or name.
+    Atom(String),
+    /// A name written between bars, which is never a number or keyword.
+    Quoted(String),
+    List(Vec<Sexp>),
+}
+
+fn is_delimiter(character: char) -> bool {
+    character.is_whitespace() || matches!(character, '(' | ')' | '|' | ';')
+}
+
+impl Sexp {
+    /// Reads the one s-expression in `code`, skipping comments from `;` to the end of the line.
+    pub fn read(code: &str) -> Sexp {
+        let mut characters = code.chars().peekable();
+        let mut stack: Vec<Vec<Sexp>> = vec![Vec::new()];
+        while let Some(character) = characters.next() {
+            match character {
+                ';' => {
+                    for character in characters.by_ref() {
+                        if character == '\n' {
+                            break;
+                        }
+                    }
+                }
+                '(' => stack.push(Vec::new()),
+                ')' => {
+                    let list = stack.pop().unwrap();
+                    stack
+                        .last_mut()
+                        .unwrap_or_else(|| panic!("Unbalanced ')'."))
+                        .push(Sexp::List(list));
+                }
+                '|' => {
+                    let name = characters
+                        .by_ref()
+                        .take_while(|next| *next != '|')
+                        .collect();
+                    stack.last_mut().unwrap().push(Sexp::Quoted(name));
+                }
+                character if character.is_whitespace() => (),
+                character => {
+                    let mut atom = character.to_string();
+                    while let Some(next) = characters.peek() {
+                        if is_delimiter(*next) {
+                            break;
+                        }
+                        atom.push(characters.next().unwrap());
+                    }
+                    stack.last_mut().unwrap().push(Sexp::Atom(atom));
+                }
+            }
+        }
+        if stack.len() != 1 {
+            panic!("Unbalanced '('.");
+        }
+        let mut sexps = stack.pop().unwrap();
+        if sexps.len() != 1 {
+            panic!("Expected one s-expression, found {}.", sexps.len());
+        }
+        sexps.pop().unwrap()
+    }
+
+    /// Lays the s-expression out within `width` columns, with the arguments of a list that
+    /// doesn't fit on one line each on their own line.
+    pub fn pretty(&self, width: usize) -> String {
+        let mut output = format::render(&self.doc(), width);
+        output.push('\n');
+        output
+    }
+
+    fn doc(&self) -> Doc {
+        match self {
+            Sexp::List(items) if !items.is_empty() => {
+                let mut rest = Vec::new();
+                for item in &items[1..] {
+                    rest.push(Doc::Break(" "));
+                    rest.push(item.doc());
+                }
+                Doc::Group(Box::new(Doc::Concat(vec![
+                    text("("),
+                    items[0].doc(),
+                    Doc::Nest(2, Box::new(Doc::Concat(rest))),
+                    text(")"),
+                ])))
+            }
+            sexp => Doc::Text(sexp.to_string()),
+        }
+    }
+}
+
+impl Display for Sexp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Sexp::Atom(atom) => write!(f, "{}", atom),
+            Sexp::Quoted(name) => write!(f, "|{}|", name),
+            Sexp::List(items) => {
+                write!(f, "(")?;
+                for (index, item) in items.iter().enumerate() {
+                    if index != 0 {
+                        write!(f, " ")?;
+                    }
+                    write!(f, "{}", item)?;
+                }
+                write!(f, ")")
+            }
+        }
+    }
+}
+
+fn atom(atom: &str) -> Sexp {
+    Sexp::Atom(atom.to_string())
+}
+
+fn list(items: Vec<Sexp>) -> Sexp {
+    Sexp::List(items)
+}
+
+fn name(name: &str) -> Sexp {
+    let plain = !name.is_empty()
+        && !name.chars().any(is_delimiter)
+        && name.parse::<i64>().is_err()
+        && !KEYWORDS.contains(&name);
+    if plain {
+        atom(name)
+    } else {
+        Sexp::Quoted(name.to_string())
+    }
+}
+
+fn from_type(ty: &Type) -> Sexp {
+    match ty {
+        Type::Number => atom("num"),
+        Type::Boolean => atom("bool"),
+        Type::Reference(ty) => list(vec![atom("ref"), from_type(ty)]),
+        Type::Function { argument, ret } => {
+            list(vec![atom("->"), from_type(argument), from_type(ret)])
+        }
+        Type::Data(data) => list(vec![atom("data"), name(data)]),
+        Type::Named(named) => list(vec![atom("named"), name(named)]),
+    }
+}
+
+fn from_constructors(constructors: &[Constructor]) -> Sexp {
+    list(
+        constructors
+            .iter()
+            .map(|constructor| {
+                let mut items = vec![name(&constructor.name)];
+                items.extend(constructor.fields.iter().map(from_type));
+                list(items)
+            })
+            .collect(),
+    )
+}
+
+/// The s-expression of `ast`, without its `AST::Located` and `AST::Commented` nodes.
+pub fn from_ast(ast: &AST) -> Sexp {
+    match ast {
+        AST::NumberLiteral(number) => atom(&number.to_string()),
+        AST::TrueLiteral => atom("true"),
+        AST::FalseLiteral => atom("false"),
+        AST::Identifier(id) => name(id),
+        AST::Plus(lhs, rhs) => list(vec![atom("+"), from_ast(lhs), from_ast(rhs)]),
+        AST::Multiply(lhs, rhs) => list(vec![atom("*"), from_ast(lhs), from_ast(rhs)]),
+        AST::Equals(lhs, rhs) => list(vec![atom("="), from_ast(lhs), from_ast(rhs)]),
+        AST::PhysicalEquals(lhs, rhs) => list(vec![atom("eq?"), from_ast(lhs), from_ast(rhs)]),
+        AST::Set(reference, value) => {
+            list(vec![atom("set!"), from_ast(reference), from_ast(value)])
+        }
+        AST::Reference(ast) => list(vec![atom("ref"), from_ast(ast)]),
+        AST::Dereference(ast) => list(vec![atom("deref"), from_ast(ast)]),
+        AST::Fix(ast) => list(vec![atom("fix"), from_ast(ast)]),
+        AST::If(if_struct) => list(vec![
+            atom("if"),
+            from_ast(&if_struct.condition),
+            from_ast(&if_struct.then),
+            from_ast(&if_struct.els),
+        ]),
+        AST::FunctionApplication(function_application) => {
+            // Curried applications are written as one list.
+            let mut items = match from_ast(&function_application.function) {
+                Sexp::List(items) if is_application(&function_application.function) => items,
+                function => vec![function],
+            };
+            items.push(from_ast(&function_application.argument));
+            list(items)
+        }
+        AST::FunctionDefinition(function_definition) => list(vec![
+            atom("fun"),
+            list(vec![
+                name(&function_definition.argument_name),
+                from_type(&function_definition.argument_type),
+            ]),
+            from_type(&function_definition.return_type),
+            from_ast(&function_definition.body),
+        ]),
+        AST::RecursiveFunction(recursive_function) => list(vec![
+            atom("rec"),
+            name(&recursive_function.function_name),
+            name(&recursive_function.argument_name),
+            from_type(&recursive_function.argument_type),
+            from_type(&recursive_function.return_type),
+            from_ast(&recursive_function.body),
+            from_ast(&recursive_function.function_use),
+        ]),
+        AST::RecursiveFunctionGroup(recursive_function_group) => list(vec![
+            atom("rec-group"),
+            list(
+                recursive_function_group
+                    .functions
+                    .iter()
+                    .map(|function| {
+                        list(vec![
+                            name(&function.function_name),
+                            name(&function.argument_name),
+                            from_type(&function.argument_type),
+                            from_type(&function.return_type),
+                            from_ast(&function.body),
+                        ])
+                    })
+                    .collect(),
+            ),
+            from_ast(&recursive_function_group.function_use),
+        ]),
+        AST::DataDeclaration(data_declaration) => list(vec![
+            atom("data"),
+            name(&data_declaration.name),
+            from_constructors(&data_declaration.constructors),
+            from_ast(&data_declaration.body),
+        ]),
+        AST::Construct(construct) => {
+            let mut items = vec![atom("con"), name(&construct.constructor)];
+            items.extend(construct.arguments.iter().map(from_ast));
+            list(items)
+        }
+        AST::Match(match_struct) => {
+            let mut items = vec![atom("match"), from_ast(&match_struct.scrutinee)];
+            items.extend(match_struct.branches.iter().map(|branch| {
+                list(vec![
+                    name(&branch.constructor),
+                    list(
+                        branch
+                            .variables
+                            .iter()
+                            .map(|variable| name(variable))
+                            .collect(),
+                    ),
+                    from_ast(&branch.body),
+                ])
+            }));
+            list(items)
+        }
+        AST::TypeDeclaration(type_declaration) => list(vec![
+            atom("type"),
+            name(&type_declaration.name),
+            from_type(&type_declaration.ty),
+            from_ast(&type_declaration.body),
+        ]),
+        AST::Located(_, ast) | AST::Commented(_, ast) => from_ast(ast),
+        AST::Error => panic!("Can't write a node that failed to parse"),
+    }
+}
+
+fn is_application(ast: &AST) -> bool {
+    match ast {
+        AST::FunctionApplication(_) => true,
+        AST::Located(_, ast) | AST::Commented(_, ast) => is_application(ast),
+        _ => false,
+    }
+}
+
+/// The s-expression of `typed_ast`, where each node is `(: T node)`. Applications aren't
+/// curried, since each of them has its own type.
+pub fn from_typed_ast(typed_ast: &TypedAST) -> Sexp {
+    let node = match &*typed_ast.ast {
+        TypedASTEnum::NumberLiteral(number) => atom(&number.to_string()),
+        TypedASTEnum::TrueLiteral => atom("true"),
+        TypedASTEnum::FalseLiteral => atom("false"),
+        TypedASTEnum::Identifier(id) => name(id),
+        TypedASTEnum::Plus(lhs, rhs) => {
+            list(vec![atom("+"), from_typed_ast(lhs), from_typed_ast(rhs)])
+        }
+        TypedASTEnum::Multiply(lhs, rhs) => {
+            list(vec![atom("*"), from_typed_ast(lhs), from_typed_ast(rhs)])
+        }
+        TypedASTEnum::Equals(lhs, rhs) => {
+            list(vec![atom("="), from_typed_ast(lhs), from_typed_ast(rhs)])
+        }
+        TypedASTEnum::PhysicalEquals(lhs, rhs) => {
+            list(vec![atom("eq?"), from_typed_ast(lhs), from_typed_ast(rhs)])
+        }
+        TypedASTEnum::Set(reference, value) => list(vec![
+            atom("set!"),
+            from_typed_ast(reference),
+            from_typed_ast(value),
+        ]),
+        TypedASTEnum::Reference(ast) => list(vec![atom("ref"), from_typed_ast(ast)]),
+        TypedASTEnum::Dereference(ast) => list(vec![atom("deref"), from_typed_ast(ast)]),
+        TypedASTEnum::Fix(ast) => list(vec![atom("fix"), from_typed_ast(ast)]),
+        TypedASTEnum::If(if_struct) => list(vec![
+            atom("if"),
+            from_typed_ast(&if_struct.condition),
+            from_typed_ast(&if_struct.then),
+            from_typed_ast(&if_struct.els),
+        ]),
+        TypedASTEnum::FunctionApplication(function_application) => list(vec![
+            from_typed_ast(&function_application.function),
+            from_typed_ast(&function_application.argument),
+        ]),
+        TypedASTEnum::FunctionDefinition(function_definition) => list(vec![
+            atom("fun"),
+            list(vec![
+                name(&function_definition.argument_name),
+                from_type(&function_definition.argument_type),
+            ]),
+            from_type(&function_definition.return_type),
+            from_typed_ast(&function_definition.body),
+        ]),
+        TypedASTEnum::RecursiveFunction(recursive_function) => list(vec![
+            atom("rec"),
+            name(&recursive_function.function_name),
+            name(&recursive_function.argument_name),
+            from_type(&recursive_function.argument_type),
+            from_type(&recursive_function.return_type),
+            from_typed_ast(&recursive_function.body),
+            from_typed_ast(&recursive_function.function_use),
+        ]),
+        TypedASTEnum::RecursiveFunctionGroup(recursive_function_group) => list(vec![
+            atom("rec-group"),
+            list(
+                recursive_function_group
+                    .functions
+                    .iter()
+                    .map(|function| {
+                        list(vec![
+                            name(&function.function_name),
+                            name(&function.argument_name),
+                            from_type(&function.argument_type),
+                            from_type(&function.return_type),
+                            from_typed_ast(&function.body),
+                        ])
+                    })
+                    .collect(),
+            ),
+            from_typed_ast(&recursive_function_group.function_use),
+        ]),
+        TypedASTEnum::DataDeclaration(data_declaration) => list(vec![
+            atom("data"),
+            name(&data_declaration.name),
+            from_constructors(&data_declaration.constructors),
+            from_typed_ast(&data_declaration.body),
+        ]),
+        TypedASTEnum::TypeDeclaration(type_declaration) => list(vec![
+            atom("type"),
+            name(&type_declaration.name),
+            from_type(&type_declaration.ty),
+            from_typed_ast(&type_declaration.body),
+        ]),
+        TypedASTEnum::Construct(construct) => {
+            let mut items = vec![atom("con"), name(&construct.constructor)];
+            items.extend(construct.arguments.iter().map(from_typed_ast));
+            list(items)
+        }
+        TypedASTEnum::Match(match_struct) => {
+            let mut items = vec![atom("match"), from_typed_ast(&match_struct.scrutinee)];
+            items.extend(match_struct.branches.iter().map(|branch| {
+                list(vec![
+                    name(&branch.constructor),
+                    list(
+                        branch
+                            .variables
+                            .iter()
+                            .map(|variable| name(variable))
+                            .collect(),
+                    ),
+                    from_typed_ast(&branch.body),
+                ])
+            }));
+            list(items)
+        }
+    };
+    list(vec![atom(":"), from_type(&typed_ast.ty), node])
+}
+
+/// Parses `code`, written as an s-expression, to an `AST`.
+pub fn parse(code: &str) -> AST {
+    to_ast(&Sexp::read(code))
+}
+
+fn to_name(sexp: &Sexp) -> String {
+    match sexp {
+        Sexp::Atom(atom) if !KEYWORDS.contains(&atom.as_str()) => atom.clone(),
+        Sexp::Quoted(name) => name.clone(),
+        sexp => panic!("Expected a name, found {}.", sexp),
+    }
+}
+
+fn to_list(sexp: &Sexp) -> &[Sexp] {
+    match sexp {
+        Sexp::List(items) => items,
+        sexp => panic!("Expected a list, found {}.", sexp),
+    }
+}
+
+fn to_type(sexp: &Sexp) -> Type {
+    match sexp {
+        Sexp::Atom(atom) if atom == "num" => Type::Number,
+        Sexp::Atom(atom) if atom == "bool" => Type::Boolean,
+        Sexp::List(items) => match items.as_slice() {
+            [Sexp::Atom(head), ty] if head == "ref" => Type::Reference(Box::new(to_type(ty))),
+            [Sexp::Atom(head), argument, ret] if head == "->" => Type::Function {
+                argument: Box::new(to_type(argument)),
+                ret: Box::new(to_type(ret)),
+            },
+            [Sexp::Atom(head), data] if head == "data" => Type::Data(to_name(data)),
+            [Sexp::Atom(head), named] if head == "named" => Type::Named(to_name(named)),
+            _ => panic!("Expected a type, found {}.", sexp),
+        },
+        sexp => panic!("Expected a type, found {}.", sexp),
+    }
+}
+
+fn boxed(sexp: &Sexp) -> Box<AST> {
+    Box::new(to_ast(sexp))
+}
+
+fn to_recursive_binding(sexp: &Sexp) -> RecursiveBinding {
+    match to_list(sexp) {
+        [function_name, argument_name, argument_type, return_type, body] => RecursiveBinding {
+            function_name: to_name(function_name),
+            argument_name: to_name(argument_name),
+            argument_type: to_type(argument_type),
+            return_type: to_type(return_type),
+            body: boxed(body),
+        },
+        _ => panic!("Expected (f x T R body), found {}.", sexp),
+    }
+}
+
+/// The `AST` of `sexp`, which is written in the forms of `from_ast`.
+pub fn to_ast(sexp: &Sexp) -> AST {
+    let items = match sexp {
+        Sexp::Atom(atom) if atom == "true" => return AST::TrueLiteral,
+        Sexp::Atom(atom) if atom == "false" => return AST::FalseLiteral,
+        Sexp::Atom(atom) => {
+            return match atom.parse() {
+                Ok(number) => AST::NumberLiteral(number),
+                Err(_) => AST::Identifier(to_name(sexp)),
+            }
+        }
+        Sexp::Quoted(name) => return AST::Identifier(name.clone()),
+        Sexp::List(items) => items,
+    };
+    let keyword = match items.first() {
+        Some(Sexp::Atom(atom)) if KEYWORDS.contains(&atom.as_str()) => atom.as_str(),
+        Some(function) => {
+            if items.len() < 2 {
+                panic!("Expected an argument in {}.", sexp);
+            }
+            return items[1..]
+                .iter()
+                .fold(to_ast(function), |function, argument| {
+                    AST::FunctionApplication(FunctionApplication {
+                        function: Box::new(function),
+                        argument: boxed(argument),
+                    })
+                });
+        }
+        None => panic!("Expected an expression, found ()."),
+    };
+    match (keyword, &items[1..]) {
+        ("+", [lhs, rhs]) => AST::Plus(boxed(lhs), boxed(rhs)),
+        ("*", [lhs, rhs]) => AST::Multiply(boxed(lhs), boxed(rhs)),
+        ("=", [lhs, rhs]) => AST::Equals(boxed(lhs), boxed(rhs)),
+        ("eq?", [lhs, rhs]) => AST::PhysicalEquals(boxed(lhs), boxed(rhs)),
+        ("set!", [reference, value]) => AST::Set(boxed(reference), boxed(value)),
+        ("ref", [ast]) => AST::Reference(boxed(ast)),
+        ("deref", [ast]) => AST::Dereference(boxed(ast)),
+        ("fix", [ast]) => AST::Fix(boxed(ast)),
+        ("if", [condition, then, els]) => AST::If(If {
+            condition: boxed(condition),
+            then: boxed(then),
+            els: boxed(els),
+        }),
+        ("fun", [argument, return_type, body]) => match to_list(argument) {
+            [argument_name, argument_type] => AST::FunctionDefinition(FunctionDefinition {
+                argument_name: to_name(argument_name),
+                argument_type: to_type(argument_type),
+                return_type: to_type(return_type),
+                body: boxed(body),
+            }),
+            _ => panic!("Expected (x T), found {}.", argument),
+        },
+        ("rec", [function_name, argument_name, argument_type, return_type, body, function_use]) => {
+            AST::RecursiveFunction(RecursiveFunction {
+                function_name: to_name(function_name),
+                argument_name: to_name(argument_name),
+                argument_type: to_type(argument_type),
+                return_type: to_type(return_type),
+                body: boxed(body),
+                function_use: boxed(function_use),
+            })
+        }
+        ("rec-group", [functions, function_use]) => {
+            AST::RecursiveFunctionGroup(RecursiveFunctionGroup {
+                functions: to_list(functions)
+                    .iter()
+                    .map(to_recursive_binding)
+                    .collect(),
+                function_use: boxed(function_use),
+            })
+        }
+        ("data", [data_name, constructors, body]) => AST::DataDeclaration(DataDeclaration {
+            name: to_name(data_name),
+            constructors: to_list(constructors)
+                .iter()
+                .map(|constructor| match to_list(constructor) {
+                    [constructor_name, fields @ ..] => Constructor {
+                        name: to_name(constructor_name),
+                        fields: fields.iter().map(to_type).collect(),
+                    },
+                    [] => panic!("Expected a constructor, found ()."),
+                })
+                .collect(),
+            body: boxed(body),
+        }),
+        ("con", [constructor, arguments @ ..]) => AST::Construct(Construct {
+            constructor: to_name(constructor),
+            arguments: arguments.iter().map(to_ast).collect(),
+        }),
+        ("match", [scrutinee, branches @ ..]) => AST::Match(Match {
+            scrutinee: boxed(scrutinee),
+            branches: branches
+                .iter()
+                .map(|branch| match to_list(branch) {
+                    [constructor, variables, body] => MatchBranch {
+                        constructor: to_name(constructor),
+                        variables: to_list(variables).iter().map(to_name).collect(),
+                        body: boxed(body),
+                    },
+                    _ => panic!("Expected (Constructor (x ...) body), found {}.", branch),
+                })
+                .collect(),
+        }),
+        ("type", [type_name, ty, body]) => AST::TypeDeclaration(TypeDeclaration {
+            name: to_name(type_name),
+            ty: to_type(ty),
+            body: boxed(body),
+        }),
+        _ => panic!("Malformed '{}' form {}.", keyword, sexp),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tokenize::TokenStream;
+    use std::{fs, panic};
+
+    fn parse_core(code: &str) -> AST {
+        AST::build(&mut TokenStream::build(code.chars()))
+    }
+
+    #[test]
+    fn sexp_read() {
+        let sexp = Sexp::read("(f |x y| ; comment\n (1 -2) ())");
+        assert_eq!(
+            sexp,
+            list(vec![
+                atom("f"),
+                Sexp::Quoted(String::from("x y")),
+                list(vec![atom("1"), atom("-2")]),
+                list(Vec::new()),
+            ])
+        );
+        assert_eq!(sexp.to_string(), "(f |x y| (1 -2) ())");
+    }
+
+    #[test]
+    fn sexp_parse() {
+        assert_eq!(parse("(+ 1 2)"), parse_core("plusC(numC(1), numC(2))"));
+        assert_eq!(
+            parse("(fun (x num) (-> num bool) (f x 1))"),
+            parse_core(
+                r#"fdC("x", numT, funT(numT, boolT), appC(appC(idC("f"), idC("x")), numC(1)))"#
+            )
+        );
+        assert_eq!(
+            parse("(rec f x num num (f x) (f 1))"),
+            parse_core(
+                r#"recC("f", "x", numT, numT, appC(idC("f"), idC("x")), appC(idC("f"), numC(1)))"#
+            )
+        );
+    }
+
+    #[test]
+    fn sexp_quoted_names() {
+        let ast = parse_core(r#"appC(idC("if"), idC("1"))"#);
+        assert_eq!(from_ast(&ast).to_string(), "(|if| |1|)");
+        assert_eq!(to_ast(&from_ast(&ast)), ast);
+    }
+
+    #[test]
+    fn sexp_typed() {
+        let typed_ast = TypedAST::new(&parse_core(
+            r#"appC(fdC("x", numT, numT, plusC(idC("x"), numC(1))), numC(2))"#,
+        ));
+        assert_eq!(
+            from_typed_ast(&typed_ast).to_string(),
+            "(: num ((: (-> num num) (fun (x num) num (: num (+ (: num x) (: num 1))))) (: num 2)))"
+        );
+    }
+
+    /// Every core input that parses is written as an s-expression that parses back to the same
+    /// `AST`, whether it is laid out on one line or many.
+    #[test]
+    fn sexp_round_trip() {
+        for entry in fs::read_dir("tests/inputs").unwrap() {
+            let path = entry.unwrap().path();
+            if matches!(path.extension(), Some(extension) if extension != "txt") {
+                continue;
+            }
+            let code = fs::read_to_string(&path).unwrap();
+            let ast = match panic::catch_unwind(|| parse_core(&code)) {
+                Ok(ast) => ast,
+                Err(_) => continue,
+            };
+            let sexp = from_ast(&ast);
+            assert_eq!(parse(&sexp.to_string()), ast, "{:?}", path);
+            assert_eq!(parse(&sexp.pretty(0)), ast, "{:?}", path);
+        }
+    }
+}
@@ -0,0 +1,605 @@
+use crate::parse::{
+    Construct, FunctionApplication, If, Match, RecursiveFunction, RecursiveFunctionGroup, AST,
+    WILDCARD,
+};
+
+/// Which rule a reduction used. Only applying a function to its argument is a beta-reduction.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum StepKind {
+    Beta,
+    Other,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Step {
+    pub kind: StepKind,
+    pub term: AST,
+}
+
+/// Reduces `ast` by one step of call-by-value, left to right evaluation. Returns `None` when `ast`
+/// is a value.
+///
+/// Functions are applied by substituting their argument into their body, so unlike
+/// `interpret::interpret` variables are scoped lexically. `recC(f, ..., idC(f))` and
+/// `recGroupC([...], idC(f))` are values standing for the recursive function `f`, and a `recC` or
+/// `recGroupC` whose use is anything else reduces by substituting those values for the names it
+/// binds. `ast` must not contain references, locations or comments; `steps` checks and strips them.
+pub fn step(ast: &AST) -> Option<Step> {
+    match ast {
+        AST::NumberLiteral(_) | AST::TrueLiteral | AST::FalseLiteral => None,
+        AST::FunctionDefinition(_) => None,
+        AST::Identifier(name) => panic!("Unable to find identifier: {:?}", name),
+        AST::Plus(lhs, rhs) => step_operands(lhs, rhs, AST::Plus)
+            .or_else(|| Some(other(AST::NumberLiteral(number(lhs) + number(rhs))))),
+        AST::Multiply(lhs, rhs) => step_operands(lhs, rhs, AST::Multiply)
+            .or_else(|| Some(other(AST::NumberLiteral(number(lhs) * number(rhs))))),
+        AST::Equals(lhs, rhs) => step_operands(lhs, rhs, AST::Equals).or_else(|| {
+            Some(other(if lhs == rhs {
+                AST::TrueLiteral
+            } else {
+                AST::FalseLiteral
+            }))
+        }),
+        AST::If(if_struct) => match step(&if_struct.condition) {
+            Some(step) => Some(Step {
+                kind: step.kind,
+                term: AST::If(If {
+                    condition: Box::new(step.term),
+                    then: if_struct.then.clone(),
+                    els: if_struct.els.clone(),
+                }),
+            }),
+            None => match *if_struct.condition {
+                AST::TrueLiteral => Some(other(*if_struct.then.clone())),
+                AST::FalseLiteral => Some(other(*if_struct.els.clone())),
+                _ => panic!("Condition of ifC is not a boolean"),
+            },
+        },
+        AST::FunctionApplication(function_application) => step_operands(
+            &function_application.function,
+            &function_application.argument,
+            |function, argument| {
+                AST::FunctionApplication(FunctionApplication { function, argument })
+            },
+        )
+        .or_else(|| {
+            Some(apply(
+                &function_application.function,
+                &function_application.argument,
+            ))
+        }),
+        AST::Fix(function) => step(function).map(|step| Step {
+            kind: step.kind,
+            term: AST::Fix(Box::new(step.term)),
+        }),
+        AST::RecursiveFunction(recursive_function) => {
+            if is_identifier(
+                &recursive_function.function_use,
+                &recursive_function.function_name,
+            ) {
+                None
+            } else {
+                Some(other(substitute(
+                    &recursive_function.function_use,
+                    &recursive_function.function_name,
+                    &recursive_function_value(recursive_function),
+                )))
+            }
+        }
+        AST::RecursiveFunctionGroup(recursive_function_group) => {
+            if recursive_function_group.functions.iter().any(|function| {
+                is_identifier(
+                    &recursive_function_group.function_use,
+                    &function.function_name,
+                )
+            }) {
+                None
+            } else {
+                Some(other(substitute_group(
+                    &recursive_function_group.function_use,
+                    recursive_function_group,
+                )))
+            }
+        }
+        AST::DataDeclaration(data_declaration) => Some(other(*data_declaration.body.clone())),
+        AST::TypeDeclaration(type_declaration) => Some(other(*type_declaration.body.clone())),
+        AST::Located(_, _) | AST::Commented(_, _) => {
+            panic!("Locations and comments must be stripped before stepping")
+        }
+        AST::Error => panic!("Can't step a node that failed to parse"),
+        AST::Construct(construct) => {
+            construct
+                .arguments
+                .iter()
+                .enumerate()
+                .find_map(|(index, argument)| {
+                    step(argument).map(|step| {
+                        let mut arguments = construct.arguments.clone();
+                        arguments[index] = step.term;
+                        Step {
+                            kind: step.kind,
+                            term: AST::Construct(Construct {
+                                constructor: construct.constructor.clone(),
+                                arguments,
+                            }),
+                        }
+                    })
+                })
+        }
+        AST::Match(match_struct) => match step(&match_struct.scrutinee) {
+            Some(step) => Some(Step {
+                kind: step.kind,
+                term: AST::Match(Match {
+                    scrutinee: Box::new(step.term),
+                    branches: match_struct.branches.clone(),
+                }),
+            }),
+            None => {
+                let construct = match &*match_struct.scrutinee {
+                    AST::Construct(construct) => construct,
+                    _ => panic!("Scrutinee of matchC is not a constructed value"),
+                };
+                let branch = match_struct
+                    .branches
+                    .iter()
+                    .find(|branch| {
+                        branch.constructor == construct.constructor
+                            || branch.constructor == WILDCARD
+                    })
+                    .unwrap_or_else(|| panic!("No branch matches '{}'", construct.constructor));
+
+                // A wildcard branch has no variables, so nothing is substituted for it.
+                let body = branch
+                    .variables
+                    .iter()
+                    .zip(&construct.arguments)
+                    .fold(*branch.body.clone(), |body, (variable, field)| {
+                        substitute(&body, variable, field)
+                    });
+                Some(other(body))
+            }
+        },
+        AST::Reference(_) | AST::Dereference(_) | AST::Set(_, _) | AST::PhysicalEquals(_, _) => {
+            panic!("References are not supported by the small-step evaluator")
+        }
+    }
+}
+
+/// Iterates over the steps taken to reduce a term to a value.
+pub struct Steps {
+    term: AST,
+}
+
+impl Iterator for Steps {
+    type Item = Step;
+
+    fn next(&mut self) -> Option<Step> {
+        let step = step(&self.term)?;
+        self.term = step.term.clone();
+        Some(step)
+    }
+}
+
+/// Returns an error if `ast` uses references, which the small-step evaluator doesn't support.
+pub fn steps(ast: &AST) -> Result<Steps, String> {
+    Ok(Steps { term: strip(ast)? })
+}
+
+/// Returns `ast` without its locations, which only matter to the debugger, and its comments, which
+/// only matter to the formatter, so that dropping them doesn't count as a step.
+fn strip(ast: &AST) -> Result<AST, String> {
+    let strip_box = |ast: &AST| strip(ast).map(Box::new);
+    Ok(match ast {
+        AST::NumberLiteral(_) | AST::TrueLiteral | AST::FalseLiteral | AST::Identifier(_) => {
+            ast.clone()
+        }
+        AST::Plus(lhs, rhs) => AST::Plus(strip_box(lhs)?, strip_box(rhs)?),
+        AST::Multiply(lhs, rhs) => AST::Multiply(strip_box(lhs)?, strip_box(rhs)?),
+        AST::Equals(lhs, rhs) => AST::Equals(strip_box(lhs)?, strip_box(rhs)?),
+        AST::Fix(ast) => AST::Fix(strip_box(ast)?),
+        AST::If(if_struct) => AST::If(If {
+            condition: strip_box(&if_struct.condition)?,
+            then: strip_box(&if_struct.then)?,
+            els: strip_box(&if_struct.els)?,
+        }),
+        AST::FunctionApplication(function_application) => {
+            AST::FunctionApplication(FunctionApplication {
+                function: strip_box(&function_application.function)?,
+                argument: strip_box(&function_application.argument)?,
+            })
+        }
+        AST::FunctionDefinition(function_definition) => {
+            let mut function_definition = function_definition.clone();
+            function_definition.body = strip_box(&function_definition.body)?;
+            AST::FunctionDefinition(function_definition)
+        }
+        AST::RecursiveFunction(recursive_function) => {
+            let mut recursive_function = recursive_function.clone();
+            recursive_function.body = strip_box(&recursive_function.body)?;
+            recursive_function.function_use = strip_box(&recursive_function.function_use)?;
+            AST::RecursiveFunction(recursive_function)
+        }
+        AST::RecursiveFunctionGroup(recursive_function_group) => {
+            let mut recursive_function_group = recursive_function_group.clone();
+            for function in &mut recursive_function_group.functions {
+                function.body = strip_box(&function.body)?;
+            }
+            recursive_function_group.function_use =
+                strip_box(&recursive_function_group.function_use)?;
+            AST::RecursiveFunctionGroup(recursive_function_group)
+        }
+        AST::DataDeclaration(data_declaration) => {
+            let mut data_declaration = data_declaration.clone();
+            data_declaration.body = strip_box(&data_declaration.body)?;
+            AST::DataDeclaration(data_declaration)
+        }
+        AST::TypeDeclaration(type_declaration) => {
+            let mut type_declaration = type_declaration.clone();
+            type_declaration.body = strip_box(&type_declaration.body)?;
+            AST::TypeDeclaration(type_declaration)
+        }
+        AST::Construct(construct) => AST::Construct(Construct {
+            constructor: construct.constructor.clone(),
+            arguments: construct
+                .arguments
+                .iter()
+                .map(strip)
+                .collect::<Result<_, _>>()?,
+        }),
+        AST::Match(match_struct) => {
+            let mut match_struct = match_struct.clone();
+            match_struct.scrutinee = strip_box(&match_struct.scrutinee)?;
+            for branch in &mut match_struct.branches {
+                branch.body = strip_box(&branch.body)?;
+            }
+            AST::Match(match_struct)
+        }
+        AST::Located(_, ast) | AST::Commented(_, ast) => strip(ast)?,
+        AST::Error => panic!("Can't step a node that failed to parse"),
+        AST::Reference(_) | AST::Dereference(_) | AST::Set(_, _) | AST::PhysicalEquals(_, _) => {
+            return Err(String::from(
+                "References are not supported by the small-step evaluator",
+            ))
+        }
+    })
+}
+
+fn other(term: AST) -> Step {
+    Step {
+        kind: StepKind::Other,
+        term,
+    }
+}
+
+fn number(ast: &AST) -> i64 {
+    if let AST::NumberLiteral(number) = ast {
+        *number
+    } else {
+        panic!("Expected a numC but found {:?}", ast)
+    }
+}
+
+fn is_identifier(ast: &AST, name: &str) -> bool {
+    matches!(ast, AST::Identifier(identifier) if identifier == name)
+}
+
+/// Steps the leftmost operand that is not yet a value, rebuilding the term around it with `build`.
+fn step_operands(lhs: &AST, rhs: &AST, build: impl Fn(Box<AST>, Box<AST>) -> AST) -> Option<Step> {
+    if let Some(step) = step(lhs) {
+        Some(Step {
+            kind: step.kind,
+            term: build(Box::new(step.term), Box::new(rhs.clone())),
+        })
+    } else {
+        step(rhs).map(|step| Step {
+            kind: step.kind,
+            term: build(Box::new(lhs.clone()), Box::new(step.term)),
+        })
+    }
+}
+
+/// Applies the value `function` to the value `argument`.
+fn apply(function: &AST, argument: &AST) -> Step {
+    match function {
+        AST::FunctionDefinition(function_definition) => Step {
+            kind: StepKind::Beta,
+            term: substitute(
+                &function_definition.body,
+                &function_definition.argument_name,
+                argument,
+            ),
+        },
+        AST::RecursiveFunction(recursive_function) => Step {
+            kind: StepKind::Beta,
+            term: substitute(
+                &substitute(
+                    &recursive_function.body,
+                    &recursive_function.argument_name,
+                    argument,
+                ),
+                &recursive_function.function_name,
+                function,
+            ),
+        },
+        AST::RecursiveFunctionGroup(recursive_function_group) => {
+            let function = recursive_function_group
+                .functions
+                .iter()
+                .find(|function| {
+                    is_identifier(
+                        &recursive_function_group.function_use,
+                        &function.function_name,
+                    )
+                })
+                .unwrap();
+            Step {
+                kind: StepKind::Beta,
+                term: substitute_group(
+                    &substitute(&function.body, &function.argument_name, argument),
+                    recursive_function_group,
+                ),
+            }
+        }
+        // fixC(f)(argument) = f(fixC(f))(argument)
+        AST::Fix(generator) => other(AST::FunctionApplication(FunctionApplication {
+            function: Box::new(AST::FunctionApplication(FunctionApplication {
+                function: generator.clone(),
+                argument: Box::new(function.clone()),
+            })),
+            argument: Box::new(argument.clone()),
+        })),
+        _ => panic!("Not a function in appC"),
+    }
+}
+
+/// The value standing for the function bound by `recursive_function`.
+fn recursive_function_value(recursive_function: &RecursiveFunction) -> AST {
+    AST::RecursiveFunction(RecursiveFunction {
+        function_use: Box::new(AST::Identifier(recursive_function.function_name.clone())),
+        ..recursive_function.clone()
+    })
+}
+
+/// Substitutes the value of every function in `recursive_function_group` for its name in `ast`.
+fn substitute_group(ast: &AST, recursive_function_group: &RecursiveFunctionGroup) -> AST {
+    recursive_function_group
+        .functions
+        .iter()
+        .fold(ast.clone(), |ast, function| {
+            let value = AST::RecursiveFunctionGroup(RecursiveFunctionGroup {
+                functions: recursive_function_group.functions.clone(),
+                function_use: Box::new(AST::Identifier(function.function_name.clone())),
+            });
+            substitute(&ast, &function.function_name, &value)
+        })
+}
+
+/// Replaces the free occurrences of `name` in `ast` with `value`. `value` is always closed, so no
+/// variable can be captured.
+fn substitute(ast: &AST, name: &str, value: &AST) -> AST {
+    let substitute_box = |ast: &AST| Box::new(substitute(ast, name, value));
+    match ast {
+        AST::NumberLiteral(_) | AST::TrueLiteral | AST::FalseLiteral => ast.clone(),
+        AST::Identifier(identifier) => {
+            if identifier == name {
+                value.clone()
+            } else {
+                ast.clone()
+            }
+        }
+        AST::Plus(lhs, rhs) => AST::Plus(substitute_box(lhs), substitute_box(rhs)),
+        AST::Multiply(lhs, rhs) => AST::Multiply(substitute_box(lhs), substitute_box(rhs)),
+        AST::Equals(lhs, rhs) => AST::Equals(substitute_box(lhs), substitute_box(rhs)),
+        AST::PhysicalEquals(lhs, rhs) => {
+            AST::PhysicalEquals(substitute_box(lhs), substitute_box(rhs))
+        }
+        AST::Set(reference, assigned) => {
+            AST::Set(substitute_box(reference), substitute_box(assigned))
+        }
+        AST::Reference(ast) => AST::Reference(substitute_box(ast)),
+        AST::Dereference(ast) => AST::Dereference(substitute_box(ast)),
+        AST::Fix(ast) => AST::Fix(substitute_box(ast)),
+        AST::If(if_struct) => AST::If(If {
+            condition: substitute_box(&if_struct.condition),
+            then: substitute_box(&if_struct.then),
+            els: substitute_box(&if_struct.els),
+        }),
+        AST::FunctionApplication(function_application) => {
+            AST::FunctionApplication(FunctionApplication {
+                function: substitute_box(&function_application.function),
+                argument: substitute_box(&function_application.argument),
+            })
+        }
+        AST::FunctionDefinition(function_definition) => {
+            let mut function_definition = function_definition.clone();
+            if function_definition.argument_name != name {
+                function_definition.body = substitute_box(&function_definition.body);
+            }
+            AST::FunctionDefinition(function_definition)
+        }
+        AST::RecursiveFunction(recursive_function) => {
+            let mut recursive_function = recursive_function.clone();
+            if recursive_function.function_name != name {
+                if recursive_function.argument_name != name {
+                    recursive_function.body = substitute_box(&recursive_function.body);
+                }
+                recursive_function.function_use = substitute_box(&recursive_function.function_use);
+            }
+            AST::RecursiveFunction(recursive_function)
+        }
+        AST::RecursiveFunctionGroup(recursive_function_group) => {
+            let mut recursive_function_group = recursive_function_group.clone();
+            if recursive_function_group
+                .functions
+                .iter()
+                .all(|function| function.function_name != name)
+            {
+                for function in &mut recursive_function_group.functions {
+                    if function.argument_name != name {
+                        function.body = substitute_box(&function.body);
+                    }
+                }
+                recursive_function_group.function_use =
+                    substitute_box(&recursive_function_group.function_use);
+            }
+            AST::RecursiveFunctionGroup(recursive_function_group)
+        }
+        AST::DataDeclaration(data_declaration) => {
+            let mut data_declaration = data_declaration.clone();
+            data_declaration.body = substitute_box(&data_declaration.body);
+            AST::DataDeclaration(data_declaration)
+        }
+        AST::Located(position, ast) => AST::Located(*position, substitute_box(ast)),
+        AST::Commented(comments, ast) => AST::Commented(comments.clone(), substitute_box(ast)),
+        AST::Error => AST::Error,
+        AST::TypeDeclaration(type_declaration) => {
+            let mut type_declaration = type_declaration.clone();
+            type_declaration.body = substitute_box(&type_declaration.body);
+            AST::TypeDeclaration(type_declaration)
+        }
+        AST::Construct(construct) => AST::Construct(Construct {
+            constructor: construct.constructor.clone(),
+            arguments: construct
+                .arguments
+                .iter()
+                .map(|argument| substitute(argument, name, value))
+                .collect(),
+        }),
+        AST::Match(match_struct) => {
+            let mut match_struct = match_struct.clone();
+            match_struct.scrutinee = substitute_box(&match_struct.scrutinee);
+            for branch in &mut match_struct.branches {
+                if !branch.variables.iter().any(|variable| variable == name) {
+                    branch.body = substitute_box(&branch.body);
+                }
+            }
+            AST::Match(match_struct)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse::FunctionDefinition, read, tokenize::TokenStream, type_check::Type};
+
+    fn parse_file(name: &str) -> AST {
+        let code = read::build(&format!("tests/inputs/{}.txt", name));
+        let mut token_stream = TokenStream::build(code.chars());
+        AST::build(&mut token_stream)
+    }
+
+    fn value(ast: &AST) -> AST {
+        steps(ast)
+            .unwrap()
+            .last()
+            .map_or(ast.clone(), |step| step.term)
+    }
+
+    #[test]
+    fn step_value() {
+        assert_eq!(step(&AST::NumberLiteral(3)), None)
+    }
+
+    #[test]
+    fn step_beta() {
+        let input = AST::FunctionApplication(FunctionApplication {
+            function: Box::new(AST::FunctionDefinition(FunctionDefinition {
+                argument_name: "x".to_string(),
+                argument_type: Type::Number,
+                return_type: Type::Number,
+                body: Box::new(AST::Plus(
+                    Box::new(AST::Identifier("x".to_string())),
+                    Box::new(AST::Identifier("x".to_string())),
+                )),
+            })),
+            argument: Box::new(AST::NumberLiteral(4)),
+        });
+        let trace: Vec<Step> = steps(&input).unwrap().collect();
+        assert_eq!(
+            trace,
+            vec![
+                Step {
+                    kind: StepKind::Beta,
+                    term: AST::Plus(
+                        Box::new(AST::NumberLiteral(4)),
+                        Box::new(AST::NumberLiteral(4))
+                    ),
+                },
+                Step {
+                    kind: StepKind::Other,
+                    term: AST::NumberLiteral(8),
+                },
+            ]
+        )
+    }
+
+    #[test]
+    fn step_shadowing() {
+        let input = AST::FunctionApplication(FunctionApplication {
+            function: Box::new(AST::FunctionDefinition(FunctionDefinition {
+                argument_name: "x".to_string(),
+                argument_type: Type::Number,
+                return_type: Type::Function {
+                    argument: Box::new(Type::Number),
+                    ret: Box::new(Type::Number),
+                },
+                body: Box::new(AST::FunctionDefinition(FunctionDefinition {
+                    argument_name: "x".to_string(),
+                    argument_type: Type::Number,
+                    return_type: Type::Number,
+                    body: Box::new(AST::Identifier("x".to_string())),
+                })),
+            })),
+            argument: Box::new(AST::NumberLiteral(4)),
+        });
+        assert_eq!(
+            step(&input).unwrap().term,
+            AST::FunctionDefinition(FunctionDefinition {
+                argument_name: "x".to_string(),
+                argument_type: Type::Number,
+                return_type: Type::Number,
+                body: Box::new(AST::Identifier("x".to_string())),
+            })
+        )
+    }
+
+    #[test]
+    fn step_summation() {
+        assert_eq!(value(&parse_file("summation")), AST::NumberLiteral(55))
+    }
+
+    #[test]
+    fn step_mutual_recursion() {
+        assert_eq!(value(&parse_file("mutual_recursion")), AST::FalseLiteral)
+    }
+
+    #[test]
+    fn step_tree_sum() {
+        assert_eq!(value(&parse_file("tree_sum")), AST::NumberLiteral(10))
+    }
+
+    #[test]
+    fn step_fix_factorial() {
+        assert_eq!(value(&parse_file("fix_factorial")), AST::NumberLiteral(24))
+    }
+
+    #[test]
+    fn step_skips_locations() {
+        let code = "plusC(numC(1), multC(numC(2), numC(3)))";
+        let ast = AST::build(&mut TokenStream::build_located(code.chars()));
+        let trace: Vec<Step> = steps(&ast).unwrap().collect();
+        assert_eq!(trace.len(), 2);
+        assert_eq!(trace[1].term, AST::NumberLiteral(7));
+    }
+
+    #[test]
+    fn step_rejects_references() {
+        assert_eq!(
+            steps(&parse_file("reference_set")).err(),
+            Some(String::from(
+                "References are not supported by the small-step evaluator"
+            ))
+        );
+    }
+}
@@ -0,0 +1,1166 @@
+//! An ML-like syntax for the language, which desugars to the same `AST` as the core syntax:
+//!
+//! ```text
+//! data Tree = Leaf | Node(Tree, num, Tree) in
+//! rec sum (tree : Tree) : num =
+//!   match tree with
+//!   | Leaf -> 0
+//!   | Node(left, value, right) -> sum left + value + sum right
+//!   end
+//! in
+//! let (double : num -> num) : num = fun (x : num) : num -> x + x in
+//! if sum Leaf == 0 then double 2 else 0
+//! ```
+//!
+//! `let (x : T) : R = e in body` is `appC(fdC("x", T, R, body), e)`, and a group of mutually
+//! recursive functions is written `mutual f (x : T) : R = ... and g (y : T) : R = ... in ...`.
+//! A function type is in parentheses when it is the return type of a binder such as
+//! `fun (x : num) : (num -> num) -> ...`. Like `fdC`, every binder has both of its types written.
+//!
+//! Names are written between backticks when they would otherwise be read as keywords. Inside a
+//! `data` expression, its name is a data type and its constructors build values.
+
+use crate::{
+    format::{self, text, Doc},
+    parse::{
+        Construct, Constructor, DataDeclaration, FunctionApplication, FunctionDefinition, If,
+        Match, MatchBranch, RecursiveBinding, RecursiveFunction, RecursiveFunctionGroup,
+        TypeDeclaration, AST,
+    },
+    tokenize::Position,
+    type_check::Type,
+};
+
+/// The extension of files written in the ML-like syntax.
+pub const EXTENSION: &str = "lml";
+
+const KEYWORDS: [&str; 18] = [
+    "let", "in", "fun", "rec", "mutual", "and", "if", "then", "else", "data", "type", "match",
+    "with", "end", "ref", "fix", "true", "false",
+];
+
+/// Words that are only keywords where a type is expected.
+const TYPE_KEYWORDS: [&str; 3] = ["num", "bool", "ref"];
+
+/// Longer symbols come first so that they are preferred to their prefixes.
+const SYMBOLS: [&str; 13] = [
+    "===", "==", ":=", "->", "(", ")", ",", ":", "=", "|", "+", "*", "!",
+];
+
+#[derive(Debug, PartialEq, Clone)]
+enum Token {
+    Number(i64),
+    /// A name or a keyword.
+    Word(String),
+    /// A name written between backticks, which is never a keyword or a constructor.
+    Quoted(String),
+    Symbol(&'static str),
+}
+
+fn is_word(name: &str) -> bool {
+    let mut characters = name.chars();
+    matches!(characters.next(), Some(first) if first.is_alphabetic() || first == '_')
+        && characters
+            .all(|character| character.is_alphanumeric() || character == '_' || character == '\'')
+}
+
+/// Splits `code` into tokens, skipping whitespace and the comments of the core syntax.
+fn lex(code: &str) -> Vec<(Token, Position)> {
+    let characters: Vec<char> = code.chars().collect();
+    let mut index = 0;
+    let mut position = Position { line: 1, column: 1 };
+    let mut tokens = Vec::new();
+    let advance = |index: &mut usize, position: &mut Position, count: usize| {
+        for character in &characters[*index..*index + count] {
+            if *character == '\n' {
+                position.line += 1;
+                position.column = 1;
+            } else {
+                position.column += 1;
+            }
+        }
+        *index += count;
+    };
+    while index < characters.len() {
+        let rest: String = characters[index..].iter().take(3).collect();
+        let start = position;
+        let length = match characters[index] {
+            character if character.is_whitespace() => {
+                advance(&mut index, &mut position, 1);
+                continue;
+            }
+            ';' => {
+                let length = characters[index..]
+                    .iter()
+                    .position(|character| *character == '\n')
+                    .unwrap_or(characters.len() - index);
+                advance(&mut index, &mut position, length);
+                continue;
+            }
+            '#' if rest.starts_with("#|") => {
+                let length = (index + 3..characters.len())
+                    .find(|end| characters[*end - 1] == '|' && characters[*end] == '#')
+                    .unwrap_or_else(|| panic!("Unterminated comment at {}.", start))
+                    - index
+                    + 1;
+                advance(&mut index, &mut position, length);
+                continue;
+            }
+            '`' => {
+                let length = characters[index + 1..]
+                    .iter()
+                    .position(|character| *character == '`')
+                    .unwrap_or_else(|| panic!("Unterminated name at {}.", start));
+                let name = characters[index + 1..index + 1 + length].iter().collect();
+                tokens.push((Token::Quoted(name), start));
+                length + 2
+            }
+            character
+                if character.is_ascii_digit()
+                    || (character == '-'
+                        && matches!(characters.get(index + 1), Some(next) if next.is_ascii_digit())) =>
+            {
+                let length = 1 + characters[index + 1..]
+                    .iter()
+                    .take_while(|character| character.is_ascii_digit())
+                    .count();
+                let number: String = characters[index..index + length].iter().collect();
+                let number = number
+                    .parse()
+                    .unwrap_or_else(|_| panic!("Number {} at {} is too large.", number, start));
+                tokens.push((Token::Number(number), start));
+                length
+            }
+            character if character.is_alphabetic() || character == '_' => {
+                let length = characters[index..]
+                    .iter()
+                    .take_while(|character| {
+                        character.is_alphanumeric() || **character == '_' || **character == '\''
+                    })
+                    .count();
+                let word = characters[index..index + length].iter().collect();
+                tokens.push((Token::Word(word), start));
+                length
+            }
+            _ => {
+                let symbol = SYMBOLS
+                    .iter()
+                    .find(|symbol| rest.starts_with(*symbol))
+                    .unwrap_or_else(|| {
+                        panic!("Unexpected character '{}' at {}.", characters[index], start)
+                    });
+                tokens.push((Token::Symbol(symbol), start));
+                symbol.len()
+            }
+        };
+        advance(&mut index, &mut position, length);
+    }
+    tokens
+}
+
+/// Parses `code`, written in the ML-like syntax, to the `AST` it desugars to.
+pub fn parse(code: &str) -> AST {
+    parse_with(code, false)
+}
+
+/// Like `parse`, but every node is wrapped in an `AST::Located` with where it starts in `code`.
+pub fn parse_located(code: &str) -> AST {
+    parse_with(code, true)
+}
+
+fn parse_with(code: &str, located: bool) -> AST {
+    let mut parser = Parser {
+        tokens: lex(code),
+        index: 0,
+        located,
+        data_types: Vec::new(),
+        constructors: Vec::new(),
+    };
+    let ast = parser.expression();
+    if parser.index != parser.tokens.len() {
+        panic!("Expected the end of the input at {}.", parser.location());
+    }
+    ast
+}
+
+struct Parser {
+    tokens: Vec<(Token, Position)>,
+    index: usize,
+    located: bool,
+    /// The data types declared by the enclosing `data` expressions.
+    data_types: Vec<String>,
+    /// The constructors declared by the enclosing `data` expressions.
+    constructors: Vec<String>,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.index).map(|(token, _)| token)
+    }
+
+    fn peek_word(&self) -> Option<String> {
+        match self.peek() {
+            Some(Token::Word(word)) => Some(word.clone()),
+            _ => None,
+        }
+    }
+
+    /// The position of the next token.
+    fn position(&self) -> Option<Position> {
+        self.tokens.get(self.index).map(|(_, position)| *position)
+    }
+
+    /// `ast`, which starts at `start`, wrapped in an `AST::Located` if the parser is located and
+    /// it isn't wrapped already.
+    fn locate(&self, start: Option<Position>, ast: AST) -> AST {
+        match start {
+            Some(start) if self.located && !matches!(ast, AST::Located(_, _)) => {
+                AST::Located(start, Box::new(ast))
+            }
+            _ => ast,
+        }
+    }
+
+    fn location(&self) -> String {
+        match self.tokens.get(self.index) {
+            Some((token, position)) => format!("{} ({:?})", position, token),
+            None => String::from("the end of the input"),
+        }
+    }
+
+    fn next(&mut self) -> Token {
+        let token = self
+            .peek()
+            .cloned()
+            .unwrap_or_else(|| panic!("Unexpected end of the input."));
+        self.index += 1;
+        token
+    }
+
+    fn is_symbol(&self, symbol: &str) -> bool {
+        matches!(self.peek(), Some(Token::Symbol(next)) if *next == symbol)
+    }
+
+    fn is_keyword(&self, keyword: &str) -> bool {
+        matches!(self.peek(), Some(Token::Word(word)) if word == keyword)
+    }
+
+    fn expect_symbol(&mut self, symbol: &str) {
+        if !self.is_symbol(symbol) {
+            panic!("Expected '{}' at {}.", symbol, self.location());
+        }
+        self.index += 1;
+    }
+
+    fn expect_keyword(&mut self, keyword: &str) {
+        if !self.is_keyword(keyword) {
+            panic!("Expected '{}' at {}.", keyword, self.location());
+        }
+        self.index += 1;
+    }
+
+    /// A variable, function or constructor name.
+    fn name(&mut self) -> String {
+        match self.peek() {
+            Some(Token::Word(word)) if !KEYWORDS.contains(&word.as_str()) => match self.next() {
+                Token::Word(word) => word,
+                _ => unreachable!(),
+            },
+            Some(Token::Quoted(_)) => match self.next() {
+                Token::Quoted(name) => name,
+                _ => unreachable!(),
+            },
+            _ => panic!("Expected a name at {}.", self.location()),
+        }
+    }
+
+    fn type_name(&mut self) -> String {
+        if let Some(word) = self.peek_word() {
+            if TYPE_KEYWORDS.contains(&word.as_str()) {
+                panic!("Expected a type name at {}.", self.location());
+            }
+        }
+        self.name()
+    }
+
+    /// `(x : T) : R`, where `R` is in parentheses if it is a function type so that the `->` of
+    /// `fun` ends it.
+    fn binder(&mut self) -> (String, Type, Type) {
+        if !self.is_symbol("(") {
+            panic!(
+                "Expected '(' at {}, since a binder is written '(x : T) : R' with both types.",
+                self.location()
+            );
+        }
+        self.index += 1;
+        let argument_name = self.name();
+        self.expect_symbol(":");
+        let argument_type = self.ty();
+        self.expect_symbol(")");
+        self.expect_symbol(":");
+        let return_type = self.type_atom();
+        (argument_name, argument_type, return_type)
+    }
+
+    /// `f (x : T) : R = body`
+    fn recursive_binding(&mut self) -> RecursiveBinding {
+        let function_name = self.name();
+        let (argument_name, argument_type, return_type) = self.binder();
+        self.expect_symbol("=");
+        let body = Box::new(self.expression());
+        RecursiveBinding {
+            function_name,
+            argument_name,
+            argument_type,
+            return_type,
+            body,
+        }
+    }
+
+    /// Items separated by commas between parentheses.
+    fn parenthesized<T>(&mut self, item: fn(&mut Parser) -> T) -> Vec<T> {
+        self.expect_symbol("(");
+        let mut items = Vec::new();
+        if !self.is_symbol(")") {
+            items.push(item(self));
+            while self.is_symbol(",") {
+                self.index += 1;
+                items.push(item(self));
+            }
+        }
+        self.expect_symbol(")");
+        items
+    }
+
+    fn expression(&mut self) -> AST {
+        let start = self.position();
+        let ast = match self.peek_word().as_deref() {
+            Some("let") => {
+                self.index += 1;
+                let (argument_name, argument_type, return_type) = self.binder();
+                self.expect_symbol("=");
+                let argument = Box::new(self.expression());
+                self.expect_keyword("in");
+                let body = Box::new(self.expression());
+                AST::FunctionApplication(FunctionApplication {
+                    function: Box::new(AST::FunctionDefinition(FunctionDefinition {
+                        argument_name,
+                        argument_type,
+                        return_type,
+                        body,
+                    })),
+                    argument,
+                })
+            }
+            Some("fun") => {
+                self.index += 1;
+                let (argument_name, argument_type, return_type) = self.binder();
+                self.expect_symbol("->");
+                AST::FunctionDefinition(FunctionDefinition {
+                    argument_name,
+                    argument_type,
+                    return_type,
+                    body: Box::new(self.expression()),
+                })
+            }
+            Some("rec") => {
+                self.index += 1;
+                let binding = self.recursive_binding();
+                self.expect_keyword("in");
+                AST::RecursiveFunction(RecursiveFunction {
+                    function_name: binding.function_name,
+                    argument_name: binding.argument_name,
+                    argument_type: binding.argument_type,
+                    return_type: binding.return_type,
+                    body: binding.body,
+                    function_use: Box::new(self.expression()),
+                })
+            }
+            Some("mutual") => {
+                self.index += 1;
+                let mut functions = Vec::new();
+                if !self.is_keyword("in") {
+                    functions.push(self.recursive_binding());
+                    while self.is_keyword("and") {
+                        self.index += 1;
+                        functions.push(self.recursive_binding());
+                    }
+                }
+                self.expect_keyword("in");
+                AST::RecursiveFunctionGroup(RecursiveFunctionGroup {
+                    functions,
+                    function_use: Box::new(self.expression()),
+                })
+            }
+            Some("if") => {
+                self.index += 1;
+                let condition = Box::new(self.expression());
+                self.expect_keyword("then");
+                let then = Box::new(self.expression());
+                self.expect_keyword("else");
+                let els = Box::new(self.expression());
+                AST::If(If {
+                    condition,
+                    then,
+                    els,
+                })
+            }
+            Some("data") => {
+                self.index += 1;
+                let name = self.type_name();
+                self.expect_symbol("=");
+                let (data_types, constructors) = (self.data_types.len(), self.constructors.len());
+                self.data_types.push(name.clone());
+                let mut declared = Vec::new();
+                if !self.is_keyword("in") {
+                    declared.push(self.constructor());
+                    while self.is_symbol("|") {
+                        self.index += 1;
+                        declared.push(self.constructor());
+                    }
+                }
+                self.expect_keyword("in");
+                self.constructors
+                    .extend(declared.iter().map(|constructor| constructor.name.clone()));
+                let body = Box::new(self.expression());
+                self.data_types.truncate(data_types);
+                self.constructors.truncate(constructors);
+                AST::DataDeclaration(DataDeclaration {
+                    name,
+                    constructors: declared,
+                    body,
+                })
+            }
+            Some("type") => {
+                self.index += 1;
+                let name = self.type_name();
+                self.expect_symbol("=");
+                let ty = self.ty();
+                self.expect_keyword("in");
+                AST::TypeDeclaration(TypeDeclaration {
+                    name,
+                    ty,
+                    body: Box::new(self.expression()),
+                })
+            }
+            Some("match") => {
+                self.index += 1;
+                let scrutinee = Box::new(self.expression());
+                self.expect_keyword("with");
+                let mut branches = Vec::new();
+                while self.is_symbol("|") {
+                    self.index += 1;
+                    let constructor = self.name();
+                    let variables = if self.is_symbol("(") {
+                        self.parenthesized(Parser::name)
+                    } else {
+                        Vec::new()
+                    };
+                    self.expect_symbol("->");
+                    branches.push(MatchBranch {
+                        constructor,
+                        variables,
+                        body: Box::new(self.expression()),
+                    });
+                }
+                self.expect_keyword("end");
+                AST::Match(Match {
+                    scrutinee,
+                    branches,
+                })
+            }
+            _ => self.assignment(),
+        };
+        self.locate(start, ast)
+    }
+
+    /// `Name` or `Name(T, ...)`
+    fn constructor(&mut self) -> Constructor {
+        let name = self.name();
+        let fields = if self.is_symbol("(") {
+            self.parenthesized(Parser::ty)
+        } else {
+            Vec::new()
+        };
+        Constructor { name, fields }
+    }
+
+    fn assignment(&mut self) -> AST {
+        let start = self.position();
+        let reference = self.equality();
+        if self.is_symbol(":=") {
+            self.index += 1;
+            let set = AST::Set(Box::new(reference), Box::new(self.assignment()));
+            self.locate(start, set)
+        } else {
+            reference
+        }
+    }
+
+    fn equality(&mut self) -> AST {
+        let start = self.position();
+        let lhs = self.sum();
+        if self.is_symbol("==") {
+            self.index += 1;
+            let equals = AST::Equals(Box::new(lhs), Box::new(self.sum()));
+            self.locate(start, equals)
+        } else if self.is_symbol("===") {
+            self.index += 1;
+            let equals = AST::PhysicalEquals(Box::new(lhs), Box::new(self.sum()));
+            self.locate(start, equals)
+        } else {
+            lhs
+        }
+    }
+
+    fn sum(&mut self) -> AST {
+        let start = self.position();
+        let mut lhs = self.product();
+        while self.is_symbol("+") {
+            self.index += 1;
+            let plus = AST::Plus(Box::new(lhs), Box::new(self.product()));
+            lhs = self.locate(start, plus);
+        }
+        lhs
+    }
+
+    fn product(&mut self) -> AST {
+        let start = self.position();
+        let mut lhs = self.prefix();
+        while self.is_symbol("*") {
+            self.index += 1;
+            let multiply = AST::Multiply(Box::new(lhs), Box::new(self.prefix()));
+            lhs = self.locate(start, multiply);
+        }
+        lhs
+    }
+
+    fn prefix(&mut self) -> AST {
+        let start = self.position();
+        let ast = if self.is_symbol("!") {
+            self.index += 1;
+            AST::Dereference(Box::new(self.prefix()))
+        } else if self.is_keyword("ref") {
+            self.index += 1;
+            AST::Reference(Box::new(self.prefix()))
+        } else if self.is_keyword("fix") {
+            self.index += 1;
+            AST::Fix(Box::new(self.prefix()))
+        } else {
+            self.application()
+        };
+        self.locate(start, ast)
+    }
+
+    fn application(&mut self) -> AST {
+        let start = self.position();
+        let mut function = self.atom();
+        while self.starts_atom() {
+            let application = AST::FunctionApplication(FunctionApplication {
+                function: Box::new(function),
+                argument: Box::new(self.atom()),
+            });
+            function = self.locate(start, application);
+        }
+        function
+    }
+
+    fn starts_atom(&self) -> bool {
+        match self.peek() {
+            Some(Token::Number(_)) | Some(Token::Quoted(_)) | Some(Token::Symbol("(")) => true,
+            Some(Token::Word(word)) => {
+                !KEYWORDS.contains(&word.as_str()) || word == "true" || word == "false"
+            }
+            _ => false,
+        }
+    }
+
+    fn atom(&mut self) -> AST {
+        let start = self.position();
+        let ast = match self.peek().cloned() {
+            Some(Token::Number(number)) => {
+                self.index += 1;
+                AST::NumberLiteral(number)
+            }
+            Some(Token::Word(word)) if word == "true" || word == "false" => {
+                self.index += 1;
+                if word == "true" {
+                    AST::TrueLiteral
+                } else {
+                    AST::FalseLiteral
+                }
+            }
+            Some(Token::Symbol("(")) => {
+                self.index += 1;
+                let ast = self.expression();
+                self.expect_symbol(")");
+                ast
+            }
+            Some(Token::Word(word)) if self.constructors.contains(&word) => {
+                self.index += 1;
+                let arguments = if self.is_symbol("(") {
+                    self.parenthesized(Parser::expression)
+                } else {
+                    Vec::new()
+                };
+                AST::Construct(Construct {
+                    constructor: word,
+                    arguments,
+                })
+            }
+            _ => AST::Identifier(self.name()),
+        };
+        self.locate(start, ast)
+    }
+
+    fn ty(&mut self) -> Type {
+        let argument = self.type_atom();
+        if self.is_symbol("->") {
+            self.index += 1;
+            Type::Function {
+                argument: Box::new(argument),
+                ret: Box::new(self.ty()),
+            }
+        } else {
+            argument
+        }
+    }
+
+    fn type_atom(&mut self) -> Type {
+        match self.peek_word().as_deref() {
+            Some("num") => {
+                self.index += 1;
+                Type::Number
+            }
+            Some("bool") => {
+                self.index += 1;
+                Type::Boolean
+            }
+            Some("ref") => {
+                self.index += 1;
+                Type::Reference(Box::new(self.type_atom()))
+            }
+            _ if self.is_symbol("(") => {
+                self.index += 1;
+                let ty = self.ty();
+                self.expect_symbol(")");
+                ty
+            }
+            _ => {
+                let name = self.type_name();
+                if self.data_types.contains(&name) {
+                    Type::Data(name)
+                } else {
+                    Type::Named(name)
+                }
+            }
+        }
+    }
+}
+
+/// How tightly an expression binds, from the keyword expressions that extend as far right as
+/// possible to atoms.
+const EXPRESSION: usize = 0;
+const ASSIGNMENT: usize = 1;
+const EQUALITY: usize = 2;
+const SUM: usize = 3;
+const PRODUCT: usize = 4;
+const PREFIX: usize = 5;
+const APPLICATION: usize = 6;
+const ATOM: usize = 7;
+
+pub fn format(ast: &AST) -> String {
+    format_with_width(ast, format::WIDTH)
+}
+
+/// Writes `ast` in the ML-like syntax, so that `parse` reads it back to `ast` without its
+/// `AST::Located` and `AST::Commented` nodes.
+pub fn format_with_width(ast: &AST, width: usize) -> String {
+    let mut printer = Printer {
+        data_types: Vec::new(),
+        constructors: Vec::new(),
+    };
+    let mut output = format::render(&printer.expression(ast, EXPRESSION), width);
+    output.push('\n');
+    output
+}
+
+fn strip(ast: &AST) -> &AST {
+    match ast {
+        AST::Located(_, ast) | AST::Commented(_, ast) => strip(ast),
+        ast => ast,
+    }
+}
+
+fn name(name: &str) -> String {
+    if is_word(name) && !KEYWORDS.contains(&name) {
+        name.to_string()
+    } else {
+        format!("`{}`", name)
+    }
+}
+
+fn type_name(type_name: &str) -> String {
+    if TYPE_KEYWORDS.contains(&type_name) {
+        format!("`{}`", type_name)
+    } else {
+        name(type_name)
+    }
+}
+
+fn type_text(ty: &Type, atom: bool) -> String {
+    match ty {
+        Type::Boolean => String::from("bool"),
+        Type::Number => String::from("num"),
+        Type::Reference(ty) => format!("ref {}", type_text(ty, true)),
+        Type::Data(name) | Type::Named(name) => type_name(name),
+        Type::Function { argument, ret } if atom => {
+            format!(
+                "({} -> {})",
+                type_text(argument, true),
+                type_text(ret, false)
+            )
+        }
+        Type::Function { argument, ret } => {
+            format!("{} -> {}", type_text(argument, true), type_text(ret, false))
+        }
+    }
+}
+
+fn binder(argument_name: &str, argument_type: &Type, return_type: &Type) -> String {
+    format!(
+        "({} : {}) : {}",
+        name(argument_name),
+        type_text(argument_type, false),
+        type_text(return_type, true)
+    )
+}
+
+/// `head`, then `body` indented on the next line if they don't fit on one.
+fn hang(head: String, body: Doc) -> Doc {
+    Doc::Group(Box::new(Doc::Concat(vec![
+        Doc::Text(head),
+        Doc::Nest(2, Box::new(Doc::Concat(vec![Doc::Break(" "), body]))),
+    ])))
+}
+
+/// `definition in`, then `body` on the next line if they don't fit on one.
+fn scoped(definition: Doc, body: Doc) -> Doc {
+    Doc::Group(Box::new(Doc::Concat(vec![
+        Doc::Group(Box::new(Doc::Concat(vec![
+            definition,
+            Doc::Break(" "),
+            text("in"),
+        ]))),
+        Doc::Break(" "),
+        body,
+    ])))
+}
+
+fn binary(lhs: Doc, operator: &str, rhs: Doc) -> Doc {
+    Doc::Group(Box::new(Doc::Concat(vec![
+        lhs,
+        Doc::Text(format!(" {}", operator)),
+        Doc::Nest(2, Box::new(Doc::Concat(vec![Doc::Break(" "), rhs]))),
+    ])))
+}
+
+/// Mirrors the scopes of the `Parser`, so that names are printed the way they are read back.
+struct Printer {
+    data_types: Vec<String>,
+    constructors: Vec<String>,
+}
+
+impl Printer {
+    /// `ast`, in parentheses if it binds less tightly than `level`.
+    fn expression(&mut self, ast: &AST, level: usize) -> Doc {
+        let (ast_level, doc) = self.unparenthesized(strip(ast));
+        if ast_level >= level {
+            return doc;
+        }
+        Doc::Group(Box::new(Doc::Concat(vec![
+            text("("),
+            Doc::Nest(1, Box::new(doc)),
+            text(")"),
+        ])))
+    }
+
+    fn unparenthesized(&mut self, ast: &AST) -> (usize, Doc) {
+        match ast {
+            AST::NumberLiteral(number) => (ATOM, Doc::Text(number.to_string())),
+            AST::TrueLiteral => (ATOM, text("true")),
+            AST::FalseLiteral => (ATOM, text("false")),
+            AST::Identifier(id) if self.constructors.contains(id) => {
+                (ATOM, Doc::Text(format!("`{}`", id)))
+            }
+            AST::Identifier(id) => (ATOM, Doc::Text(name(id))),
+            AST::Plus(lhs, rhs) => (
+                SUM,
+                binary(
+                    self.expression(lhs, SUM),
+                    "+",
+                    self.expression(rhs, PRODUCT),
+                ),
+            ),
+            AST::Multiply(lhs, rhs) => (
+                PRODUCT,
+                binary(
+                    self.expression(lhs, PRODUCT),
+                    "*",
+                    self.expression(rhs, PREFIX),
+                ),
+            ),
+            AST::Equals(lhs, rhs) => (
+                EQUALITY,
+                binary(self.expression(lhs, SUM), "==", self.expression(rhs, SUM)),
+            ),
+            AST::PhysicalEquals(lhs, rhs) => (
+                EQUALITY,
+                binary(self.expression(lhs, SUM), "===", self.expression(rhs, SUM)),
+            ),
+            AST::Set(reference, value) => (
+                ASSIGNMENT,
+                binary(
+                    self.expression(reference, EQUALITY),
+                    ":=",
+                    self.expression(value, ASSIGNMENT),
+                ),
+            ),
+            AST::Reference(ast) => (PREFIX, self.prefix("ref ", ast)),
+            AST::Dereference(ast) => (PREFIX, self.prefix("!", ast)),
+            AST::Fix(ast) => (PREFIX, self.prefix("fix ", ast)),
+            AST::FunctionApplication(function_application) => {
+                if let AST::FunctionDefinition(function_definition) =
+                    strip(&function_application.function)
+                {
+                    let definition = hang(
+                        format!(
+                            "let {} =",
+                            binder(
+                                &function_definition.argument_name,
+                                &function_definition.argument_type,
+                                &function_definition.return_type
+                            )
+                        ),
+                        self.expression(&function_application.argument, EXPRESSION),
+                    );
+                    let body = self.expression(&function_definition.body, EXPRESSION);
+                    return (EXPRESSION, scoped(definition, body));
+                }
+                let function = self.expression(&function_application.function, APPLICATION);
+                // A constructor without arguments is parenthesized so that a parenthesized
+                // argument after it isn't read as its arguments.
+                let argument = match strip(&function_application.argument) {
+                    AST::Construct(construct) if construct.arguments.is_empty() => {
+                        Doc::Text(format!("({})", name(&construct.constructor)))
+                    }
+                    argument => self.expression(argument, ATOM),
+                };
+                (
+                    APPLICATION,
+                    Doc::Group(Box::new(Doc::Concat(vec![
+                        function,
+                        Doc::Nest(2, Box::new(Doc::Concat(vec![Doc::Break(" "), argument]))),
+                    ]))),
+                )
+            }
+            AST::FunctionDefinition(function_definition) => (
+                EXPRESSION,
+                hang(
+                    format!(
+                        "fun {} ->",
+                        binder(
+                            &function_definition.argument_name,
+                            &function_definition.argument_type,
+                            &function_definition.return_type
+                        )
+                    ),
+                    self.expression(&function_definition.body, EXPRESSION),
+                ),
+            ),
+            AST::If(if_struct) => (
+                EXPRESSION,
+                Doc::Group(Box::new(Doc::Concat(vec![
+                    text("if "),
+                    self.expression(&if_struct.condition, EXPRESSION),
+                    text(" then"),
+                    Doc::Nest(
+                        2,
+                        Box::new(Doc::Concat(vec![
+                            Doc::Break(" "),
+                            self.expression(&if_struct.then, EXPRESSION),
+                        ])),
+                    ),
+                    Doc::Break(" "),
+                    text("else"),
+                    Doc::Nest(
+                        2,
+                        Box::new(Doc::Concat(vec![
+                            Doc::Break(" "),
+                            self.expression(&if_struct.els, EXPRESSION),
+                        ])),
+                    ),
+                ]))),
+            ),
+            AST::RecursiveFunction(recursive_function) => {
+                let definition = hang(
+                    format!(
+                        "rec {} {} =",
+                        name(&recursive_function.function_name),
+                        binder(
+                            &recursive_function.argument_name,
+                            &recursive_function.argument_type,
+                            &recursive_function.return_type
+                        )
+                    ),
+                    self.expression(&recursive_function.body, EXPRESSION),
+                );
+                let function_use = self.expression(&recursive_function.function_use, EXPRESSION);
+                (EXPRESSION, scoped(definition, function_use))
+            }
+            AST::RecursiveFunctionGroup(recursive_function_group) => {
+                let mut definitions = Vec::new();
+                for (index, function) in recursive_function_group.functions.iter().enumerate() {
+                    if index != 0 {
+                        definitions.push(Doc::Break(" "));
+                    }
+                    let keyword = if index == 0 { "mutual" } else { "and" };
+                    definitions.push(hang(
+                        format!(
+                            "{} {} {} =",
+                            keyword,
+                            name(&function.function_name),
+                            binder(
+                                &function.argument_name,
+                                &function.argument_type,
+                                &function.return_type
+                            )
+                        ),
+                        self.expression(&function.body, EXPRESSION),
+                    ));
+                }
+                if definitions.is_empty() {
+                    definitions.push(text("mutual"));
+                }
+                let function_use =
+                    self.expression(&recursive_function_group.function_use, EXPRESSION);
+                (EXPRESSION, scoped(Doc::Concat(definitions), function_use))
+            }
+            AST::DataDeclaration(data_declaration) => {
+                let (data_types, constructors) = (self.data_types.len(), self.constructors.len());
+                self.data_types.push(data_declaration.name.clone());
+                let declared: Vec<String> = data_declaration
+                    .constructors
+                    .iter()
+                    .map(|constructor| {
+                        if constructor.fields.is_empty() {
+                            name(&constructor.name)
+                        } else {
+                            let fields: Vec<String> = constructor
+                                .fields
+                                .iter()
+                                .map(|field| type_text(field, false))
+                                .collect();
+                            format!("{}({})", name(&constructor.name), fields.join(", "))
+                        }
+                    })
+                    .collect();
+                let mut definition = format!("data {} =", type_name(&data_declaration.name));
+                if !declared.is_empty() {
+                    definition.push(' ');
+                    definition.push_str(&declared.join(" | "));
+                }
+                self.constructors.extend(
+                    data_declaration
+                        .constructors
+                        .iter()
+                        .map(|constructor| constructor.name.clone()),
+                );
+                let body = self.expression(&data_declaration.body, EXPRESSION);
+                self.data_types.truncate(data_types);
+                self.constructors.truncate(constructors);
+                (EXPRESSION, scoped(Doc::Text(definition), body))
+            }
+            AST::TypeDeclaration(type_declaration) => (
+                EXPRESSION,
+                scoped(
+                    Doc::Text(format!(
+                        "type {} = {}",
+                        type_name(&type_declaration.name),
+                        type_text(&type_declaration.ty, false)
+                    )),
+                    self.expression(&type_declaration.body, EXPRESSION),
+                ),
+            ),
+            AST::Construct(construct) if construct.arguments.is_empty() => {
+                (ATOM, Doc::Text(name(&construct.constructor)))
+            }
+            AST::Construct(construct) => {
+                let arguments = construct
+                    .arguments
+                    .iter()
+                    .map(|argument| self.expression(argument, EXPRESSION))
+                    .collect();
+                (
+                    ATOM,
+                    format::bracket(
+                        &format!("{}(", name(&construct.constructor)),
+                        arguments,
+                        ")",
+                    ),
+                )
+            }
+            AST::Match(match_struct) => {
+                let mut docs = vec![
+                    text("match "),
+                    self.expression(&match_struct.scrutinee, EXPRESSION),
+                    text(" with"),
+                ];
+                for branch in &match_struct.branches {
+                    let mut pattern = name(&branch.constructor);
+                    if !branch.variables.is_empty() {
+                        let variables: Vec<String> = branch
+                            .variables
+                            .iter()
+                            .map(|variable| name(variable))
+                            .collect();
+                        pattern.push_str(&format!("({})", variables.join(", ")));
+                    }
+                    docs.push(Doc::Break(" "));
+                    docs.push(hang(
+                        format!("| {} ->", pattern),
+                        self.expression(&branch.body, EXPRESSION),
+                    ));
+                }
+                docs.push(Doc::Break(" "));
+                docs.push(text("end"));
+                (EXPRESSION, Doc::Group(Box::new(Doc::Concat(docs))))
+            }
+            AST::Located(_, ast) | AST::Commented(_, ast) => self.unparenthesized(ast),
+            AST::Error => panic!("Can't write a node that failed to parse"),
+        }
+    }
+
+    fn prefix(&mut self, operator: &str, ast: &AST) -> Doc {
+        Doc::Concat(vec![text(operator), self.expression(ast, PREFIX)])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        read::{self, tests::SYNTAX_ERRORS},
+        tokenize::TokenStream,
+    };
+
+    fn parse_core(code: &str) -> AST {
+        AST::build(&mut TokenStream::build(code.chars()))
+    }
+
+    #[test]
+    fn surface_precedence() {
+        assert_eq!(
+            parse("f 1 + 2 * !r == 3"),
+            parse_core(
+                r#"eqC(plusC(appC(idC("f"), numC(1)), multC(numC(2), derefC(idC("r")))), numC(3))"#
+            )
+        );
+        assert_eq!(
+            parse("r := ref -1"),
+            parse_core(r#"setC(idC("r"), refC(numC(-1)))"#)
+        );
+    }
+
+    #[test]
+    fn surface_let() {
+        assert_eq!(
+            parse("let (x : num) : bool = 1 in if x == 1 then true else false"),
+            parse_core(
+                r#"appC(fdC("x", numT, boolT, ifC(eqC(idC("x"), numC(1)), trueC, falseC)), numC(1))"#
+            )
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Expected '(' at 1:5 (Word(\"x\")), since a binder is written")]
+    fn surface_requires_types() {
+        parse("fun x : num -> x + 1");
+    }
+
+    #[test]
+    fn surface_located() {
+        let ast = parse_located("let (x : num) : num = 1 in\nx + 2");
+        let body = match strip(&ast) {
+            AST::FunctionApplication(function_application) => {
+                match strip(&function_application.function) {
+                    AST::FunctionDefinition(function_definition) => {
+                        function_definition.body.clone()
+                    }
+                    _ => unreachable!(),
+                }
+            }
+            _ => unreachable!(),
+        };
+        assert!(matches!(
+            ast,
+            AST::Located(Position { line: 1, column: 1 }, _)
+        ));
+        assert!(matches!(
+            *body,
+            AST::Located(Position { line: 2, column: 1 }, _)
+        ));
+    }
+
+    #[test]
+    fn surface_data() {
+        let code = "data Tree = Leaf | Node(Tree, num, Tree) in
+match Node(Leaf, 1, Leaf) with
+| Leaf -> 0
+| Node(left, value, right) -> value
+| _ -> `Leaf`
+end";
+        assert_eq!(
+            parse(code),
+            parse_core(
+                r#"dataC("Tree", [("Leaf", []), ("Node", [dataT("Tree"), numT, dataT("Tree")])],
+                    matchC(conC("Node", [conC("Leaf", []), numC(1), conC("Leaf", [])]), [
+                        ("Leaf", [], numC(0)),
+                        ("Node", ["left", "value", "right"], idC("value")),
+                        ("_", [], idC("Leaf"))
+                    ]))"#
+            )
+        );
+    }
+
+    #[test]
+    fn surface_format() {
+        let ast = parse_core(
+            r#"recC("f", "num", numT, numT, ifC(eqC(idC("num"), numC(0)), numC(1), multC(idC("num"), appC(idC("f"), plusC(idC("num"), numC(-1))))), appC(idC("f"), numC(5)))"#,
+        );
+        assert_eq!(
+            format_with_width(&ast, 40),
+            "rec f (num : num) : num =
+  if num == 0 then
+    1
+  else
+    num * f (num + -1)
+in
+f 5
+"
+        );
+    }
+
+    /// Every core input without a syntax error is written in the ML-like syntax, at several widths,
+    /// so that it parses back to the same `AST`.
+    #[test]
+    fn surface_round_trip() {
+        for (name, code) in read::tests::inputs(&SYNTAX_ERRORS) {
+            let ast = parse_core(&code);
+            for width in [0, 40, format::WIDTH, usize::MAX / 2] {
+                let formatted = format_with_width(&ast, width);
+                assert_eq!(parse(&formatted), ast, "{} at width {}", name, width);
+            }
+        }
+    }
+}
@@ -1,9 +1,12 @@
-use std::collections::VecDeque;
+use crate::parse::Comments;
+use std::{collections::VecDeque, fmt, fmt::Display};
 
 #[derive(Debug, PartialEq, Clone)]
 pub enum Token {
     LeftParenthesis,
     RightParenthesis,
+    LeftBracket,
+    RightBracket,
     Comma,
     Quote,
     NumberType,
@@ -22,172 +25,592 @@ pub enum Token {
     FunctionApplication,
     FunctionDefinition,
     RecursiveFunction,
+    RecursiveFunctionGroup,
+    DataDeclaration,
+    DataType,
+    Construct,
+    Match,
+    ReferenceType,
+    Reference,
+    Dereference,
+    Set,
+    PhysicalEquals,
+    Fix,
+    TypeDeclaration,
+    NamedType,
 }
 
+/// Where a token starts in the source. Both fields count from 1.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
+pub struct Position {
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Display for Position {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.line, self.column)
+    }
+}
+
+/// Every keyword with the token it is lexed as. A keyword is a word of letters, digits and `_`
+/// that doesn't start with a digit.
+pub const KEYWORDS: &[(&str, Token)] = &[
+    ("numT", Token::NumberType),
+    ("boolT", Token::BooleanType),
+    ("funT", Token::FunctionType),
+    ("refT", Token::ReferenceType),
+    ("dataT", Token::DataType),
+    ("namedT", Token::NamedType),
+    ("numC", Token::NumLiteral),
+    ("trueC", Token::TrueLiteral),
+    ("falseC", Token::FalseLiteral),
+    ("plusC", Token::Plus),
+    ("multC", Token::Multiply),
+    ("eqC", Token::Equals),
+    ("ifC", Token::If),
+    ("idC", Token::Identifier),
+    ("appC", Token::FunctionApplication),
+    ("fdC", Token::FunctionDefinition),
+    ("recC", Token::RecursiveFunction),
+    ("recGroupC", Token::RecursiveFunctionGroup),
+    ("dataC", Token::DataDeclaration),
+    ("conC", Token::Construct),
+    ("matchC", Token::Match),
+    ("refC", Token::Reference),
+    ("derefC", Token::Dereference),
+    ("setC", Token::Set),
+    ("physEqC", Token::PhysicalEquals),
+    ("fixC", Token::Fix),
+    ("typeC", Token::TypeDeclaration),
+];
+
+/// The source text of the token, or of its contents for a number or a quoted string.
+impl Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::LeftParenthesis => f.write_str("("),
+            Token::RightParenthesis => f.write_str(")"),
+            Token::LeftBracket => f.write_str("["),
+            Token::RightBracket => f.write_str("]"),
+            Token::Comma => f.write_str(","),
+            Token::Quote => f.write_str("\""),
+            Token::QuotedString(string) => f.write_str(string),
+            Token::NumberLiteral(number) => write!(f, "{}", number),
+            keyword => {
+                let (text, _) = KEYWORDS
+                    .iter()
+                    .find(|(_, token)| token == keyword)
+                    .expect("Every other token is a keyword");
+                f.write_str(text)
+            }
+        }
+    }
+}
+
+/// A syntax error, at the position of the token that caused it if it is known.
+#[derive(Debug, PartialEq, Clone)]
+pub struct SyntaxError {
+    pub position: Option<Position>,
+    pub message: String,
+}
+
+impl Display for SyntaxError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.position {
+            Some(position) => write!(f, "{}: {}", position, self.message),
+            None => f.write_str(&self.message),
+        }
+    }
+}
+
+/// A token with where it starts and the comments before it.
 #[derive(Debug, PartialEq)]
-pub struct TokenStream {
-    stream: VecDeque<Token>,
-    current_index: usize,
+struct Lexed {
+    token: Token,
+    position: Option<Position>,
+    comments: Vec<String>,
+    /// The `;` comment on the line of the last token, after it.
+    end_of_line: Option<String>,
+    /// Whether a line break was skipped since the last token.
+    line_break: bool,
+    /// Whether text that isn't a token was skipped since the last token.
+    after_error: bool,
 }
 
-impl TokenStream {
-    pub fn build(character_stream: VecDeque<char>) -> TokenStream {
-        TokenStream {
-            stream: TokenStream::tokenize(character_stream),
-            current_index: 0,
+/// Lexes characters into tokens as they are asked for.
+struct Lexer<'a> {
+    characters: Box<dyn Iterator<Item = char> + 'a>,
+    /// The characters that have been looked at but not consumed yet.
+    lookahead: VecDeque<char>,
+    /// The position of the next character.
+    position: Position,
+    /// The comments since the last token, verbatim and including their delimiters.
+    comments: Vec<String>,
+    /// The `;` comment on the line of the last token, after it, which isn't in `comments`.
+    end_of_line: Option<String>,
+    /// Whether a line break was skipped since the last token, or there was no last token.
+    line_break: bool,
+    errors: Vec<SyntaxError>,
+}
+
+impl<'a> Lexer<'a> {
+    fn new(characters: impl Iterator<Item = char> + 'a) -> Lexer<'a> {
+        Lexer {
+            characters: Box::new(characters),
+            lookahead: VecDeque::new(),
+            position: Position { line: 1, column: 1 },
+            comments: Vec::new(),
+            end_of_line: None,
+            line_break: true,
+            errors: Vec::new(),
         }
     }
 
-    #[allow(dead_code)] // used for tests in 'parse.rs'
-    pub fn build_test(token_stream: VecDeque<Token>, current_index: usize) -> TokenStream {
-        TokenStream {
-            stream: token_stream,
-            current_index,
+    /// The character after the next `n` characters, without consuming any of them.
+    fn peek_nth(&mut self, n: usize) -> Option<char> {
+        while self.lookahead.len() <= n {
+            self.lookahead.push_back(self.characters.next()?);
+        }
+        Some(self.lookahead[n])
+    }
+
+    fn pop(&mut self) -> Option<char> {
+        let character = match self.lookahead.pop_front() {
+            Some(character) => character,
+            None => self.characters.next()?,
+        };
+        if character == '\n' {
+            self.position.line += 1;
+            self.position.column = 1;
+        } else {
+            self.position.column += 1;
         }
+        Some(character)
     }
 
-    fn tokenize(mut char_stream: VecDeque<char>) -> VecDeque<Token> {
-        let mut tokens = VecDeque::new();
-        while !char_stream.is_empty() {
-            match char_stream.pop_front().unwrap() {
+    fn pop_while(&mut self, mut predicate: impl FnMut(char) -> bool) -> String {
+        let mut text = String::new();
+        while let Some(character) = self.peek_nth(0) {
+            if !predicate(character) {
+                break;
+            }
+            text.push(character);
+            self.pop();
+        }
+        text
+    }
+
+    /// The tokens that start at the next token's first character, the first with the comments
+    /// since the last token, or nothing once every character is lexed. Comments run from `;` to
+    /// the end of the line or from `#|` to `|#`, and a `;` comment right after a token on its line
+    /// is kept apart as its end-of-line comment. Text that can't be lexed is skipped after its
+    /// error, up to the end of the word or character that caused it.
+    fn lex(&mut self) -> Vec<Lexed> {
+        let mut after_error = false;
+        loop {
+            let position = self.position;
+            let character = match self.pop() {
+                Some(character) => character,
+                None => return Vec::new(),
+            };
+            let mut tokens = Vec::new();
+            let mut error = None;
+            match character {
                 '(' => {
-                    tokens.push_back(Token::LeftParenthesis);
-                    let mut num_str = String::new();
-                    let mut negative = false;
-                    if *char_stream.front().unwrap() == '-' {
-                        negative = true;
-                        char_stream.pop_front().unwrap();
-                    }
-                    loop {
-                        if char_stream.front().unwrap().is_digit(10) {
-                            num_str.push(char_stream.pop_front().unwrap());
-                        } else if !num_str.is_empty() {
-                            let mut num = num_str.parse::<i64>().unwrap();
-                            if negative {
-                                num *= -1;
-                            }
-                            tokens.push_back(Token::NumberLiteral(num));
-                            break;
-                        } else {
-                            break;
+                    tokens.push(Token::LeftParenthesis);
+                    // Numbers are only written right after a parenthesis, as in `numC(-2)`.
+                    let sign = usize::from(self.peek_nth(0) == Some('-'));
+                    if matches!(self.peek_nth(sign), Some(character) if character.is_ascii_digit())
+                    {
+                        let mut number: String = self.lookahead.iter().take(sign).collect();
+                        if sign == 1 {
+                            self.pop();
                         }
-                    }
-                }
-                ')' => tokens.push_back(Token::RightParenthesis),
-                ',' => tokens.push_back(Token::Comma),
-                '\"' => {
-                    tokens.push_back(Token::Quote);
-                    let mut id = String::new();
-                    loop {
-                        let next_char = char_stream.pop_front().unwrap();
-                        if next_char == '\"' {
-                            tokens.push_back(Token::QuotedString(id));
-                            tokens.push_back(Token::Quote);
-                            break;
+                        number.push_str(&self.pop_while(|character| character.is_ascii_digit()));
+                        match number.parse() {
+                            Ok(number) => tokens.push(Token::NumberLiteral(number)),
+                            Err(_) => error = Some(format!("Number '{}' is out of range", number)),
                         }
-                        id.push(next_char);
                     }
                 }
-                'n' => {
-                    assert_eq!(char_stream.pop_front().unwrap(), 'u');
-                    assert_eq!(char_stream.pop_front().unwrap(), 'm');
-                    let next_char = char_stream.pop_front().unwrap();
-                    if next_char == 'T' {
-                        tokens.push_back(Token::NumberType);
-                    } else if next_char == 'C' {
-                        tokens.push_back(Token::NumLiteral);
+                ')' => tokens.push(Token::RightParenthesis),
+                '[' => tokens.push(Token::LeftBracket),
+                ']' => tokens.push(Token::RightBracket),
+                ',' => tokens.push(Token::Comma),
+                '"' => {
+                    let string = self.pop_while(|character| character != '"');
+                    if self.pop().is_some() {
+                        tokens.push(Token::Quote);
+                        tokens.push(Token::QuotedString(string));
+                        tokens.push(Token::Quote);
+                    } else {
+                        error = Some(String::from("Unterminated string"));
                     }
                 }
-                'b' => {
-                    assert_eq!(char_stream.pop_front().unwrap(), 'o');
-                    assert_eq!(char_stream.pop_front().unwrap(), 'o');
-                    assert_eq!(char_stream.pop_front().unwrap(), 'l');
-                    assert_eq!(char_stream.pop_front().unwrap(), 'T');
-                    tokens.push_back(Token::BooleanType);
+                character if character.is_alphabetic() || character == '_' => {
+                    let word = format!(
+                        "{}{}",
+                        character,
+                        self.pop_while(|character| character.is_alphanumeric() || character == '_')
+                    );
+                    match KEYWORDS.iter().find(|(keyword, _)| *keyword == word) {
+                        Some((_, token)) => tokens.push(token.clone()),
+                        None => error = Some(format!("Unknown keyword '{}'", word)),
+                    }
                 }
-                'f' => {
-                    let next_char = char_stream.pop_front().unwrap();
-                    if next_char == 'u' {
-                        assert_eq!(char_stream.pop_front().unwrap(), 'n');
-                        assert_eq!(char_stream.pop_front().unwrap(), 'T');
-                        tokens.push_back(Token::FunctionType);
-                    } else if next_char == 'a' {
-                        assert_eq!(char_stream.pop_front().unwrap(), 'l');
-                        assert_eq!(char_stream.pop_front().unwrap(), 's');
-                        assert_eq!(char_stream.pop_front().unwrap(), 'e');
-                        assert_eq!(char_stream.pop_front().unwrap(), 'C');
-                        tokens.push_back(Token::FalseLiteral);
-                    } else if next_char == 'd' {
-                        assert_eq!(char_stream.pop_front().unwrap(), 'C');
-                        tokens.push_back(Token::FunctionDefinition);
+                ';' => {
+                    let comment = format!(";{}", self.pop_while(|character| character != '\n'));
+                    let comment = comment.trim_end().to_string();
+                    if self.line_break || !self.comments.is_empty() {
+                        self.comments.push(comment);
                     } else {
-                        panic!()
+                        self.end_of_line = Some(comment);
                     }
                 }
-                'p' => {
-                    assert_eq!(char_stream.pop_front().unwrap(), 'l');
-                    assert_eq!(char_stream.pop_front().unwrap(), 'u');
-                    assert_eq!(char_stream.pop_front().unwrap(), 's');
-                    assert_eq!(char_stream.pop_front().unwrap(), 'C');
-                    tokens.push_back(Token::Plus);
-                }
-                'm' => {
-                    assert_eq!(char_stream.pop_front().unwrap(), 'u');
-                    assert_eq!(char_stream.pop_front().unwrap(), 'l');
-                    assert_eq!(char_stream.pop_front().unwrap(), 't');
-                    assert_eq!(char_stream.pop_front().unwrap(), 'C');
-                    tokens.push_back(Token::Multiply);
-                }
-                'a' => {
-                    assert_eq!(char_stream.pop_front().unwrap(), 'p');
-                    assert_eq!(char_stream.pop_front().unwrap(), 'p');
-                    assert_eq!(char_stream.pop_front().unwrap(), 'C');
-                    tokens.push_back(Token::FunctionApplication);
-                }
-                'i' => {
-                    let next_char = char_stream.pop_front().unwrap();
-                    if next_char == 'f' {
-                        assert_eq!(char_stream.pop_front().unwrap(), 'C');
-                        tokens.push_back(Token::If);
-                    } else if next_char == 'd' {
-                        assert_eq!(char_stream.pop_front().unwrap(), 'C');
-                        tokens.push_back(Token::Identifier);
+                '#' if self.peek_nth(0) == Some('|') => {
+                    self.pop();
+                    let mut comment = String::from("#|");
+                    while !comment.ends_with("|#") || comment.len() < 4 {
+                        match self.pop() {
+                            Some(character) => comment.push(character),
+                            None => break,
+                        }
+                    }
+                    if comment.ends_with("|#") && comment.len() >= 4 {
+                        self.line_break |= comment.contains('\n');
+                        self.comments.push(comment);
                     } else {
-                        panic!()
+                        error = Some(String::from("Unterminated comment"));
                     }
                 }
-                't' => {
-                    assert_eq!(char_stream.pop_front().unwrap(), 'r');
-                    assert_eq!(char_stream.pop_front().unwrap(), 'u');
-                    assert_eq!(char_stream.pop_front().unwrap(), 'e');
-                    assert_eq!(char_stream.pop_front().unwrap(), 'C');
-                    tokens.push_back(Token::TrueLiteral);
-                }
-                'e' => {
-                    assert_eq!(char_stream.pop_front().unwrap(), 'q');
-                    assert_eq!(char_stream.pop_front().unwrap(), 'C');
-                    tokens.push_back(Token::Equals);
-                }
-                'r' => {
-                    // todo: write tests for this
-                    assert_eq!(char_stream.pop_front().unwrap(), 'e');
-                    assert_eq!(char_stream.pop_front().unwrap(), 'c');
-                    assert_eq!(char_stream.pop_front().unwrap(), 'C');
-                    tokens.push_back(Token::RecursiveFunction);
-                }
-                ' ' | '\t' | '\n' | '\r' => continue,
-                _ => panic!("Your input wasn't able to be converted into a token stream."),
+                '\n' => self.line_break = true,
+                ' ' | '\t' | '\r' => (),
+                character => error = Some(format!("Unexpected character '{}'", character)),
+            }
+            if let Some(message) = error {
+                after_error = true;
+                self.errors.push(SyntaxError {
+                    position: Some(position),
+                    message,
+                });
+            }
+            if !tokens.is_empty() {
+                let mut comments = std::mem::take(&mut self.comments);
+                let mut end_of_line = self.end_of_line.take();
+                let mut line_break = std::mem::replace(&mut self.line_break, false);
+                return tokens
+                    .into_iter()
+                    .map(|token| Lexed {
+                        token,
+                        position: Some(position),
+                        comments: std::mem::take(&mut comments),
+                        end_of_line: end_of_line.take(),
+                        line_break: std::mem::replace(&mut line_break, false),
+                        after_error,
+                    })
+                    .collect();
             }
         }
-        tokens
     }
 }
 
-impl Iterator for TokenStream {
+/// The comments around the strings, types, lists and tuples of a node, which are its arguments
+/// and are numbered in the order they start.
+#[derive(Default)]
+pub struct Arguments {
+    comments: Vec<(usize, Comments)>,
+    started: usize,
+}
+
+/// The tokens of a program, which are lexed only as far as they are looked at.
+pub struct TokenStream<'a> {
+    lexer: Lexer<'a>,
+    /// The tokens that have been lexed but not consumed yet.
+    lookahead: VecDeque<Lexed>,
+    located: bool,
+    /// The comments of tokens consumed by `next` that no node has taken yet.
+    pending_comments: Vec<String>,
+    /// The comments around the arguments of the node being built.
+    arguments: Arguments,
+    /// Whether text that isn't a token was skipped right before the last token consumed.
+    last_after_error: bool,
+    commented: bool,
+    /// How many more opening than closing parentheses and brackets have been consumed.
+    depth: isize,
+    recovering: bool,
+    /// The errors that were lexed past or that `AST::build` recovered from.
+    errors: Vec<SyntaxError>,
+}
+
+impl<'a> TokenStream<'a> {
+    /// Panics when it reaches text in `character_stream` that isn't a token.
+    pub fn build(character_stream: impl IntoIterator<Item = char> + 'a) -> TokenStream<'a> {
+        TokenStream {
+            lexer: Lexer::new(character_stream.into_iter()),
+            lookahead: VecDeque::new(),
+            located: false,
+            pending_comments: Vec::new(),
+            arguments: Arguments::default(),
+            last_after_error: false,
+            commented: false,
+            depth: 0,
+            recovering: false,
+            errors: Vec::new(),
+        }
+    }
+
+    /// The tokens of `character_stream`, or the first text in it that isn't a token. Unlike the
+    /// other streams, this lexes all of `character_stream` up front.
+    pub fn try_build(
+        character_stream: impl IntoIterator<Item = char> + 'a,
+    ) -> Result<TokenStream<'a>, SyntaxError> {
+        let mut token_stream = TokenStream::build_recovering(character_stream);
+        while token_stream.lex() {}
+        if token_stream.errors.is_empty() {
+            token_stream.recovering = false;
+            Ok(token_stream)
+        } else {
+            Err(token_stream.errors.swap_remove(0))
+        }
+    }
+
+    /// Like `build`, but `AST::build` wraps every node parsed from the stream in `AST::Located`.
+    pub fn build_located(character_stream: impl IntoIterator<Item = char> + 'a) -> TokenStream<'a> {
+        TokenStream {
+            located: true,
+            ..TokenStream::build(character_stream)
+        }
+    }
+
+    /// Like `build`, but `AST::build` wraps nodes that have comments around them or their
+    /// arguments in `AST::Commented`.
+    pub fn build_commented(
+        character_stream: impl IntoIterator<Item = char> + 'a,
+    ) -> TokenStream<'a> {
+        TokenStream {
+            commented: true,
+            ..TokenStream::build(character_stream)
+        }
+    }
+
+    /// Like `build`, but instead of panicking at a syntax error, `AST::build` reports it to the
+    /// stream, skips the rest of the node and puts an `AST::Error` in its place. Text that isn't a
+    /// token is reported and skipped too.
+    pub fn build_recovering(
+        character_stream: impl IntoIterator<Item = char> + 'a,
+    ) -> TokenStream<'a> {
+        TokenStream {
+            recovering: true,
+            ..TokenStream::build(character_stream)
+        }
+    }
+
+    #[allow(dead_code)] // used for tests in 'parse.rs'
+    pub fn build_test(token_stream: VecDeque<Token>) -> TokenStream<'a> {
+        TokenStream {
+            lookahead: token_stream
+                .into_iter()
+                .map(|token| Lexed {
+                    token,
+                    position: None,
+                    comments: Vec::new(),
+                    end_of_line: None,
+                    line_break: false,
+                    after_error: false,
+                })
+                .collect(),
+            ..TokenStream::build(std::iter::empty())
+        }
+    }
+
+    /// Lexes the tokens that start at the next character into `lookahead`, returning whether
+    /// there were any.
+    fn lex(&mut self) -> bool {
+        let tokens = self.lexer.lex();
+        for error in self.lexer.errors.drain(..) {
+            if !self.recovering {
+                panic!("{}", error);
+            }
+            self.errors.push(error);
+        }
+        let lexed = !tokens.is_empty();
+        self.lookahead.extend(tokens);
+        lexed
+    }
+
+    /// The token after the next `n` tokens, without consuming any of them.
+    pub fn peek_nth(&mut self, n: usize) -> Option<&Token> {
+        while self.lookahead.len() <= n {
+            if !self.lex() {
+                return None;
+            }
+        }
+        Some(&self.lookahead[n].token)
+    }
+
+    /// The token that the next call to `next` yields, without consuming it.
+    pub fn peek(&mut self) -> Option<&Token> {
+        self.peek_nth(0)
+    }
+
+    /// The comments before the next token, including those of consumed tokens that no node has
+    /// taken, which belong to the node that starts at the next token.
+    pub fn take_comments(&mut self) -> Vec<String> {
+        self.peek();
+        let mut comments = std::mem::take(&mut self.pending_comments);
+        if let Some(next) = self.lookahead.front_mut() {
+            comments.extend(next.end_of_line.take());
+            comments.append(&mut next.comments);
+        }
+        comments
+    }
+
+    /// The `;` comment on the line of the last token consumed, after it or after a `,` right
+    /// after it, which belongs to the node or argument that was built last.
+    pub fn take_end_of_line_comment(&mut self) -> Option<String> {
+        self.peek();
+        let index = match self.lookahead.front() {
+            Some(next)
+                if next.token == Token::Comma
+                    && next.end_of_line.is_none()
+                    && next.comments.is_empty()
+                    && !next.line_break =>
+            {
+                1
+            }
+            _ => 0,
+        };
+        self.peek_nth(index);
+        match self.lookahead.get_mut(index) {
+            Some(next) => next.end_of_line.take(),
+            None => self.lexer.end_of_line.take(),
+        }
+    }
+
+    /// The comments before the next token if it closes a node, which belong to the node or
+    /// argument that was built last.
+    pub fn take_closing_comments(&mut self) -> Vec<String> {
+        self.peek();
+        match self.lookahead.front_mut() {
+            Some(next) if matches!(next.token, Token::RightParenthesis | Token::RightBracket) => {
+                let mut comments: Vec<String> = next.end_of_line.take().into_iter().collect();
+                comments.append(&mut next.comments);
+                comments
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    /// The comments that no node has taken once every token has been consumed.
+    pub fn take_trailing_comments(&mut self) -> Vec<String> {
+        while self.lex() {}
+        let mut comments = std::mem::take(&mut self.pending_comments);
+        comments.extend(self.lexer.end_of_line.take());
+        comments.append(&mut self.lexer.comments);
+        comments
+    }
+
+    /// Starts numbering the arguments of a node, returning those of the enclosing node to give
+    /// back to `end_arguments`.
+    pub fn start_arguments(&mut self) -> Arguments {
+        std::mem::take(&mut self.arguments)
+    }
+
+    /// The comments around the arguments of the node started last, by their index.
+    pub fn end_arguments(&mut self, enclosing: Arguments) -> Vec<(usize, Comments)> {
+        std::mem::replace(&mut self.arguments, enclosing).comments
+    }
+
+    /// The index of the next argument of the node being built.
+    pub fn start_argument(&mut self) -> usize {
+        self.arguments.started += 1;
+        self.arguments.started - 1
+    }
+
+    /// Keeps `comments` for the argument at `index` of the node being built.
+    pub fn comment_argument(&mut self, index: usize, comments: Comments) {
+        if !comments.is_empty() {
+            self.arguments.comments.push((index, comments));
+        }
+    }
+
+    /// The position of the token that the next call to `next` yields.
+    pub fn next_position(&mut self) -> Option<Position> {
+        self.peek();
+        self.lookahead.front().and_then(|lexed| lexed.position)
+    }
+
+    /// The position right after the last character.
+    pub fn end_position(&mut self) -> Position {
+        while self.lex() {}
+        self.lexer.position
+    }
+
+    /// Whether text that isn't a token was skipped right before the next token or the last one
+    /// consumed, so that an error there would only repeat the error reported for the text.
+    pub fn is_after_error(&mut self) -> bool {
+        self.peek();
+        self.last_after_error || matches!(self.lookahead.front(), Some(lexed) if lexed.after_error)
+    }
+
+    pub fn is_located(&self) -> bool {
+        self.located
+    }
+
+    pub fn is_commented(&self) -> bool {
+        self.commented
+    }
+
+    pub fn is_empty(&mut self) -> bool {
+        self.peek().is_none()
+    }
+
+    pub fn is_recovering(&self) -> bool {
+        self.recovering
+    }
+
+    pub fn depth(&self) -> isize {
+        self.depth
+    }
+
+    /// Reports `error`, unless an error was already reported at its position.
+    pub fn report(&mut self, error: SyntaxError) {
+        if error.position.is_none()
+            || self
+                .errors
+                .iter()
+                .all(|reported| reported.position != error.position)
+        {
+            self.errors.push(error);
+        }
+    }
+
+    /// The errors reported so far, in the order they were found.
+    pub fn take_errors(&mut self) -> Vec<SyntaxError> {
+        std::mem::take(&mut self.errors)
+    }
+
+    fn nesting(token: &Token) -> isize {
+        match token {
+            Token::LeftParenthesis | Token::LeftBracket => 1,
+            Token::RightParenthesis | Token::RightBracket => -1,
+            _ => 0,
+        }
+    }
+}
+
+impl Iterator for TokenStream<'_> {
     type Item = Token;
 
     fn next(&mut self) -> Option<Token> {
-        self.stream.pop_front()
+        self.peek();
+        let lexed = self.lookahead.pop_front()?;
+        self.last_after_error = lexed.after_error;
+        self.pending_comments.extend(lexed.end_of_line);
+        self.pending_comments.extend(lexed.comments);
+        self.depth += TokenStream::nesting(&lexed.token);
+        Some(lexed.token)
     }
 }
 
@@ -295,7 +718,7 @@ mod tests {
 
     #[test]
     fn eq_c() {
-        let characters = String::from("eqC(numC(1), numC(3))").chars().collect();
+        let characters = "eqC(numC(1), numC(3))".chars();
         let mut token_stream = TokenStream::build(characters);
 
         assert_eq!(token_stream.next(), Some(Token::Equals));
@@ -314,9 +737,7 @@ mod tests {
 
     #[test]
     fn if_c() {
-        let characters = String::from("ifC(falseC, numC(1), numC(3))")
-            .chars()
-            .collect();
+        let characters = "ifC(falseC, numC(1), numC(3))".chars();
         let mut token_stream = TokenStream::build(characters);
 
         assert_eq!(token_stream.next(), Some(Token::If));
@@ -337,9 +758,7 @@ mod tests {
 
     #[test]
     fn fd_c() {
-        let characters = String::from("fdC(\"x\", boolT, boolT, idC(\"x\"))")
-            .chars()
-            .collect();
+        let characters = "fdC(\"x\", boolT, boolT, idC(\"x\"))".chars();
         let mut token_stream = TokenStream::build(characters);
 
         assert_eq!(token_stream.next(), Some(Token::FunctionDefinition));
@@ -369,9 +788,7 @@ mod tests {
 
     #[test]
     fn app_c() {
-        let characters = String::from("appC(fdC(\"x\", boolT, boolT, idC(\"x\")), falseC)")
-            .chars()
-            .collect();
+        let characters = "appC(fdC(\"x\", boolT, boolT, idC(\"x\")), falseC)".chars();
         let mut token_stream = TokenStream::build(characters);
 
         assert_eq!(token_stream.next(), Some(Token::FunctionApplication));
@@ -403,4 +820,361 @@ mod tests {
         assert_eq!(token_stream.next(), Some(Token::FalseLiteral));
         assert_eq!(token_stream.next(), Some(Token::RightParenthesis));
     }
+
+    #[test]
+    fn rec_group_c() {
+        let characters = "recGroupC([(\"f\", \"x\", numT, numT, idC(\"x\"))], numC(1))".chars();
+        let mut token_stream = TokenStream::build(characters);
+
+        assert_eq!(token_stream.next(), Some(Token::RecursiveFunctionGroup));
+        assert_eq!(token_stream.next(), Some(Token::LeftParenthesis));
+        assert_eq!(token_stream.next(), Some(Token::LeftBracket));
+        assert_eq!(token_stream.next(), Some(Token::LeftParenthesis));
+        assert_eq!(token_stream.next(), Some(Token::Quote));
+        assert_eq!(
+            token_stream.next(),
+            Some(Token::QuotedString(String::from("f")))
+        );
+        assert_eq!(token_stream.next(), Some(Token::Quote));
+        assert_eq!(token_stream.next(), Some(Token::Comma));
+        assert_eq!(token_stream.next(), Some(Token::Quote));
+        assert_eq!(
+            token_stream.next(),
+            Some(Token::QuotedString(String::from("x")))
+        );
+        assert_eq!(token_stream.next(), Some(Token::Quote));
+        assert_eq!(token_stream.next(), Some(Token::Comma));
+        assert_eq!(token_stream.next(), Some(Token::NumberType));
+        assert_eq!(token_stream.next(), Some(Token::Comma));
+        assert_eq!(token_stream.next(), Some(Token::NumberType));
+        assert_eq!(token_stream.next(), Some(Token::Comma));
+        assert_eq!(token_stream.next(), Some(Token::Identifier));
+        assert_eq!(token_stream.next(), Some(Token::LeftParenthesis));
+        assert_eq!(token_stream.next(), Some(Token::Quote));
+        assert_eq!(
+            token_stream.next(),
+            Some(Token::QuotedString(String::from("x")))
+        );
+        assert_eq!(token_stream.next(), Some(Token::Quote));
+        assert_eq!(token_stream.next(), Some(Token::RightParenthesis));
+        assert_eq!(token_stream.next(), Some(Token::RightParenthesis));
+        assert_eq!(token_stream.next(), Some(Token::RightBracket));
+        assert_eq!(token_stream.next(), Some(Token::Comma));
+        assert_eq!(token_stream.next(), Some(Token::NumLiteral));
+        assert_eq!(token_stream.next(), Some(Token::LeftParenthesis));
+        assert_eq!(token_stream.next(), Some(Token::NumberLiteral(1)));
+        assert_eq!(token_stream.next(), Some(Token::RightParenthesis));
+        assert_eq!(token_stream.next(), Some(Token::RightParenthesis));
+    }
+
+    #[test]
+    fn data_c() {
+        let characters =
+            "dataC(\"T\", [(\"L\", [dataT(\"T\")])], matchC(conC(\"L\", []), []))".chars();
+        let mut token_stream = TokenStream::build(characters);
+
+        assert_eq!(token_stream.next(), Some(Token::DataDeclaration));
+        assert_eq!(token_stream.next(), Some(Token::LeftParenthesis));
+        assert_eq!(token_stream.next(), Some(Token::Quote));
+        assert_eq!(
+            token_stream.next(),
+            Some(Token::QuotedString(String::from("T")))
+        );
+        assert_eq!(token_stream.next(), Some(Token::Quote));
+        assert_eq!(token_stream.next(), Some(Token::Comma));
+        assert_eq!(token_stream.next(), Some(Token::LeftBracket));
+        assert_eq!(token_stream.next(), Some(Token::LeftParenthesis));
+        assert_eq!(token_stream.next(), Some(Token::Quote));
+        assert_eq!(
+            token_stream.next(),
+            Some(Token::QuotedString(String::from("L")))
+        );
+        assert_eq!(token_stream.next(), Some(Token::Quote));
+        assert_eq!(token_stream.next(), Some(Token::Comma));
+        assert_eq!(token_stream.next(), Some(Token::LeftBracket));
+        assert_eq!(token_stream.next(), Some(Token::DataType));
+        assert_eq!(token_stream.next(), Some(Token::LeftParenthesis));
+        assert_eq!(token_stream.next(), Some(Token::Quote));
+        assert_eq!(
+            token_stream.next(),
+            Some(Token::QuotedString(String::from("T")))
+        );
+        assert_eq!(token_stream.next(), Some(Token::Quote));
+        assert_eq!(token_stream.next(), Some(Token::RightParenthesis));
+        assert_eq!(token_stream.next(), Some(Token::RightBracket));
+        assert_eq!(token_stream.next(), Some(Token::RightParenthesis));
+        assert_eq!(token_stream.next(), Some(Token::RightBracket));
+        assert_eq!(token_stream.next(), Some(Token::Comma));
+        assert_eq!(token_stream.next(), Some(Token::Match));
+        assert_eq!(token_stream.next(), Some(Token::LeftParenthesis));
+        assert_eq!(token_stream.next(), Some(Token::Construct));
+        assert_eq!(token_stream.next(), Some(Token::LeftParenthesis));
+        assert_eq!(token_stream.next(), Some(Token::Quote));
+        assert_eq!(
+            token_stream.next(),
+            Some(Token::QuotedString(String::from("L")))
+        );
+        assert_eq!(token_stream.next(), Some(Token::Quote));
+        assert_eq!(token_stream.next(), Some(Token::Comma));
+        assert_eq!(token_stream.next(), Some(Token::LeftBracket));
+        assert_eq!(token_stream.next(), Some(Token::RightBracket));
+        assert_eq!(token_stream.next(), Some(Token::RightParenthesis));
+        assert_eq!(token_stream.next(), Some(Token::Comma));
+        assert_eq!(token_stream.next(), Some(Token::LeftBracket));
+        assert_eq!(token_stream.next(), Some(Token::RightBracket));
+        assert_eq!(token_stream.next(), Some(Token::RightParenthesis));
+        assert_eq!(token_stream.next(), Some(Token::RightParenthesis));
+        assert_eq!(token_stream.next(), None);
+    }
+
+    #[test]
+    fn ref_c() {
+        let characters = "physEqC(refC(numC(1)), setC(derefC(trueC), falseC)) refT".chars();
+        let mut token_stream = TokenStream::build(characters);
+
+        assert_eq!(token_stream.next(), Some(Token::PhysicalEquals));
+        assert_eq!(token_stream.next(), Some(Token::LeftParenthesis));
+        assert_eq!(token_stream.next(), Some(Token::Reference));
+        assert_eq!(token_stream.next(), Some(Token::LeftParenthesis));
+        assert_eq!(token_stream.next(), Some(Token::NumLiteral));
+        assert_eq!(token_stream.next(), Some(Token::LeftParenthesis));
+        assert_eq!(token_stream.next(), Some(Token::NumberLiteral(1)));
+        assert_eq!(token_stream.next(), Some(Token::RightParenthesis));
+        assert_eq!(token_stream.next(), Some(Token::RightParenthesis));
+        assert_eq!(token_stream.next(), Some(Token::Comma));
+        assert_eq!(token_stream.next(), Some(Token::Set));
+        assert_eq!(token_stream.next(), Some(Token::LeftParenthesis));
+        assert_eq!(token_stream.next(), Some(Token::Dereference));
+        assert_eq!(token_stream.next(), Some(Token::LeftParenthesis));
+        assert_eq!(token_stream.next(), Some(Token::TrueLiteral));
+        assert_eq!(token_stream.next(), Some(Token::RightParenthesis));
+        assert_eq!(token_stream.next(), Some(Token::Comma));
+        assert_eq!(token_stream.next(), Some(Token::FalseLiteral));
+        assert_eq!(token_stream.next(), Some(Token::RightParenthesis));
+        assert_eq!(token_stream.next(), Some(Token::RightParenthesis));
+        assert_eq!(token_stream.next(), Some(Token::ReferenceType));
+        assert_eq!(token_stream.next(), None);
+    }
+
+    #[test]
+    fn fix_c() {
+        let characters = "fixC(idC(\"f\"))".chars();
+        let mut token_stream = TokenStream::build(characters);
+
+        assert_eq!(token_stream.next(), Some(Token::Fix));
+        assert_eq!(token_stream.next(), Some(Token::LeftParenthesis));
+        assert_eq!(token_stream.next(), Some(Token::Identifier));
+        assert_eq!(token_stream.next(), Some(Token::LeftParenthesis));
+        assert_eq!(token_stream.next(), Some(Token::Quote));
+        assert_eq!(
+            token_stream.next(),
+            Some(Token::QuotedString(String::from("f")))
+        );
+        assert_eq!(token_stream.next(), Some(Token::Quote));
+        assert_eq!(token_stream.next(), Some(Token::RightParenthesis));
+        assert_eq!(token_stream.next(), Some(Token::RightParenthesis));
+        assert_eq!(token_stream.next(), None);
+    }
+
+    #[test]
+    fn type_c() {
+        let characters = "typeC(\"N\", numT, namedT(\"N\"))".chars();
+        let mut token_stream = TokenStream::build(characters);
+
+        assert_eq!(token_stream.next(), Some(Token::TypeDeclaration));
+        assert_eq!(token_stream.next(), Some(Token::LeftParenthesis));
+        assert_eq!(token_stream.next(), Some(Token::Quote));
+        assert_eq!(
+            token_stream.next(),
+            Some(Token::QuotedString(String::from("N")))
+        );
+        assert_eq!(token_stream.next(), Some(Token::Quote));
+        assert_eq!(token_stream.next(), Some(Token::Comma));
+        assert_eq!(token_stream.next(), Some(Token::NumberType));
+        assert_eq!(token_stream.next(), Some(Token::Comma));
+        assert_eq!(token_stream.next(), Some(Token::NamedType));
+        assert_eq!(token_stream.next(), Some(Token::LeftParenthesis));
+        assert_eq!(token_stream.next(), Some(Token::Quote));
+        assert_eq!(
+            token_stream.next(),
+            Some(Token::QuotedString(String::from("N")))
+        );
+        assert_eq!(token_stream.next(), Some(Token::Quote));
+        assert_eq!(token_stream.next(), Some(Token::RightParenthesis));
+        assert_eq!(token_stream.next(), Some(Token::RightParenthesis));
+        assert_eq!(token_stream.next(), None);
+    }
+
+    #[test]
+    fn positions() {
+        let characters = "appC(\n  idC(\"f\"),\n  numC(1))".chars();
+        let mut token_stream = TokenStream::build(characters);
+
+        assert_eq!(
+            token_stream.next_position(),
+            Some(Position { line: 1, column: 1 })
+        );
+        assert_eq!(token_stream.next(), Some(Token::FunctionApplication));
+        assert_eq!(
+            token_stream.next_position(),
+            Some(Position { line: 1, column: 5 })
+        );
+        assert_eq!(token_stream.next(), Some(Token::LeftParenthesis));
+        assert_eq!(
+            token_stream.next_position(),
+            Some(Position { line: 2, column: 3 })
+        );
+        assert_eq!(token_stream.peek(), Some(&Token::Identifier));
+        assert_eq!(
+            token_stream.next_position(),
+            Some(Position { line: 2, column: 3 })
+        );
+        assert_eq!(token_stream.next(), Some(Token::Identifier));
+        assert_eq!(token_stream.next(), Some(Token::LeftParenthesis));
+        assert_eq!(
+            token_stream.next_position(),
+            Some(Position { line: 2, column: 7 })
+        );
+        assert_eq!(token_stream.next(), Some(Token::Quote));
+        assert_eq!(
+            token_stream.next_position(),
+            Some(Position { line: 2, column: 7 })
+        );
+        assert_eq!(token_stream.nth(4), Some(Token::NumLiteral));
+        assert_eq!(
+            token_stream.next_position(),
+            Some(Position { line: 3, column: 7 })
+        );
+        assert_eq!(token_stream.next(), Some(Token::LeftParenthesis));
+        assert_eq!(
+            token_stream.next_position(),
+            Some(Position { line: 3, column: 7 })
+        );
+        assert_eq!(token_stream.next(), Some(Token::NumberLiteral(1)));
+    }
+
+    #[test]
+    fn comments() {
+        let characters = "; a\nplusC( #| b\n c |# numC(1), ; d\n numC(2)) ; e".chars();
+        let mut token_stream = TokenStream::build_commented(characters);
+
+        assert_eq!(token_stream.take_comments(), vec![String::from("; a")]);
+        assert_eq!(token_stream.next(), Some(Token::Plus));
+        assert_eq!(token_stream.next(), Some(Token::LeftParenthesis));
+        assert_eq!(
+            token_stream.take_comments(),
+            vec![String::from("#| b\n c |#")]
+        );
+        assert_eq!(token_stream.nth(3), Some(Token::RightParenthesis));
+        assert_eq!(token_stream.peek(), Some(&Token::Comma));
+        assert_eq!(token_stream.take_comments(), Vec::<String>::new());
+        assert_eq!(token_stream.next(), Some(Token::Comma));
+        assert_eq!(token_stream.take_comments(), vec![String::from("; d")]);
+        assert_eq!(token_stream.nth(5), None);
+        assert_eq!(
+            token_stream.take_trailing_comments(),
+            vec![String::from("; e")]
+        );
+    }
+
+    #[test]
+    fn end_of_line_comments() {
+        let characters = "numC(1), ; a\n; b\nnumC(2)\n, ; c\nnumC(3) ; d".chars();
+        let mut token_stream = TokenStream::build_commented(characters);
+
+        assert_eq!(token_stream.nth(3), Some(Token::RightParenthesis));
+        assert_eq!(
+            token_stream.take_end_of_line_comment(),
+            Some(String::from("; a"))
+        );
+        assert_eq!(token_stream.next(), Some(Token::Comma));
+        assert_eq!(token_stream.take_comments(), vec![String::from("; b")]);
+        assert_eq!(token_stream.nth(3), Some(Token::RightParenthesis));
+        assert_eq!(token_stream.take_end_of_line_comment(), None);
+        assert_eq!(token_stream.next(), Some(Token::Comma));
+        assert_eq!(token_stream.take_comments(), vec![String::from("; c")]);
+        assert_eq!(token_stream.nth(3), Some(Token::RightParenthesis));
+        assert_eq!(
+            token_stream.take_end_of_line_comment(),
+            Some(String::from("; d"))
+        );
+        assert_eq!(token_stream.take_trailing_comments(), Vec::<String>::new());
+    }
+
+    #[test]
+    fn keywords() {
+        for (keyword, token) in KEYWORDS {
+            let mut token_stream = TokenStream::build(keyword.chars());
+            assert_eq!(token_stream.next().as_ref(), Some(token));
+            assert_eq!(token.to_string(), *keyword);
+        }
+    }
+
+    #[test]
+    fn unknown_keywords() {
+        let error = |code: &str| {
+            TokenStream::try_build(code.chars())
+                .err()
+                .unwrap()
+                .to_string()
+        };
+        assert_eq!(
+            error("plusC(numZ(1), numC(2))"),
+            "1:7: Unknown keyword 'numZ'"
+        );
+        assert_eq!(error("\n  plusX(numC(1))"), "2:3: Unknown keyword 'plusX'");
+        assert_eq!(error("idC(x_1)"), "1:5: Unknown keyword 'x_1'");
+        assert_eq!(error("numC(1) + numC(2)"), "1:9: Unexpected character '+'");
+        assert_eq!(error("idC(\"x)"), "1:5: Unterminated string");
+    }
+
+    #[test]
+    fn recover_from_unknown_keywords() {
+        let mut token_stream = TokenStream::build_recovering("numZ(1) numC(2C)".chars());
+        assert_eq!(token_stream.next(), Some(Token::LeftParenthesis));
+        assert_eq!(
+            token_stream.take_errors(),
+            vec![SyntaxError {
+                position: Some(Position { line: 1, column: 1 }),
+                message: String::from("Unknown keyword 'numZ'"),
+            }]
+        );
+        assert_eq!(token_stream.nth(5), Some(Token::RightParenthesis));
+        assert_eq!(
+            token_stream.take_errors(),
+            vec![SyntaxError {
+                position: Some(Position {
+                    line: 1,
+                    column: 15
+                }),
+                message: String::from("Unknown keyword 'C'"),
+            }]
+        );
+    }
+
+    #[test]
+    fn peek() {
+        let mut token_stream = TokenStream::build("plusC(numC(1), numC(2))".chars());
+        assert_eq!(token_stream.peek(), Some(&Token::Plus));
+        assert_eq!(token_stream.peek_nth(3), Some(&Token::LeftParenthesis));
+        assert_eq!(token_stream.peek_nth(4), Some(&Token::NumberLiteral(1)));
+        assert_eq!(token_stream.next(), Some(Token::Plus));
+        assert_eq!(token_stream.peek_nth(12), None);
+        assert_eq!(token_stream.nth(10), Some(Token::RightParenthesis));
+        assert_eq!(token_stream.peek(), None);
+    }
+
+    #[test]
+    fn lex_lazily() {
+        // The unknown keyword is never reached, so it isn't an error.
+        let mut token_stream = TokenStream::build("numC(1) plusX(".chars());
+        assert_eq!(token_stream.nth(3), Some(Token::RightParenthesis));
+    }
+
+    #[test]
+    #[should_panic(expected = "1:9: Unknown keyword 'plusX'")]
+    fn lex_error_when_reached() {
+        let mut token_stream = TokenStream::build("numC(1) plusX(".chars());
+        token_stream.peek_nth(4);
+    }
 }
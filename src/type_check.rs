@@ -1,4 +1,8 @@
-use crate::parse::AST;
+use crate::parse::{
+    Construct, Constructor, DataDeclaration, FunctionApplication, FunctionDefinition, If, Match,
+    MatchBranch, RecursiveBinding, RecursiveFunction, RecursiveFunctionGroup, TypeDeclaration, AST,
+    WILDCARD,
+};
 use std::{collections::HashMap, fmt, fmt::Display};
 
 #[derive(Debug, PartialEq, Clone, Eq)]
@@ -6,6 +10,35 @@ pub enum Type {
     Number,
     Boolean,
     Function { argument: Box<Type>, ret: Box<Type> },
+    Data(String),
+    Reference(Box<Type>),
+    Named(String),
+}
+
+impl Type {
+    /// Replaces `namedT(name)` in `ty` with `definition`.
+    fn substitute(&self, name: &str, definition: &Type) -> Type {
+        match self {
+            Type::Named(named) if named == name => definition.clone(),
+            Type::Function { argument, ret } => Type::Function {
+                argument: Box::new(argument.substitute(name, definition)),
+                ret: Box::new(ret.substitute(name, definition)),
+            },
+            Type::Reference(ty) => Type::Reference(Box::new(ty.substitute(name, definition))),
+            ty => ty.clone(),
+        }
+    }
+
+    fn mentions_data(&self, name: &str) -> bool {
+        match self {
+            Type::Number | Type::Boolean | Type::Named(_) => false,
+            Type::Function { argument, ret } => {
+                argument.mentions_data(name) || ret.mentions_data(name)
+            }
+            Type::Data(data_name) => data_name == name,
+            Type::Reference(ty) => ty.mentions_data(name),
+        }
+    }
 }
 
 impl Display for Type {
@@ -14,6 +47,9 @@ impl Display for Type {
             Type::Number => write!(f, "NumberType"),
             Type::Boolean => write!(f, "BooleanType"),
             Type::Function { argument: arg, ret } => write!(f, "FunctionType({}, {})", arg, ret),
+            Type::Data(name) => write!(f, "DataType({})", name),
+            Type::Reference(ty) => write!(f, "ReferenceType({})", ty),
+            Type::Named(name) => write!(f, "{}", name),
         }
     }
 }
@@ -31,6 +67,16 @@ pub enum TypedASTEnum {
     FunctionApplication(TypedFunctionApplication),
     FunctionDefinition(TypedFunctionDefinition),
     RecursiveFunction(TypedRecursiveFunction),
+    RecursiveFunctionGroup(TypedRecursiveFunctionGroup),
+    DataDeclaration(TypedDataDeclaration),
+    TypeDeclaration(TypedTypeDeclaration),
+    Construct(TypedConstruct),
+    Match(TypedMatch),
+    Reference(TypedAST),
+    Dereference(TypedAST),
+    Set(TypedAST, TypedAST),
+    PhysicalEquals(TypedAST, TypedAST),
+    Fix(TypedAST),
 }
 
 #[derive(Debug, PartialEq)]
@@ -64,18 +110,244 @@ pub struct TypedRecursiveFunction {
     pub function_use: TypedAST,
 }
 
+#[derive(Debug, PartialEq)]
+pub struct TypedRecursiveBinding {
+    pub function_name: String,
+    pub argument_name: String,
+    pub argument_type: Type,
+    pub return_type: Type,
+    pub body: TypedAST,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct TypedRecursiveFunctionGroup {
+    pub functions: Vec<TypedRecursiveBinding>,
+    pub function_use: TypedAST,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct TypedDataDeclaration {
+    pub name: String,
+    pub constructors: Vec<Constructor>,
+    pub body: TypedAST,
+}
+
+/// `namedT(name)` stands for `ty`, in which every alias is expanded, in `body`.
+#[derive(Debug, PartialEq)]
+pub struct TypedTypeDeclaration {
+    pub name: String,
+    pub ty: Type,
+    pub body: TypedAST,
+}
+
+/// `tag` is the index of `constructor` in its data type's declaration.
+#[derive(Debug, PartialEq)]
+pub struct TypedConstruct {
+    pub constructor: String,
+    pub tag: usize,
+    pub arguments: Vec<TypedAST>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct TypedMatch {
+    pub scrutinee: TypedAST,
+    pub branches: Vec<TypedMatchBranch>,
+}
+
+/// `tag` is `None` for the wildcard branch. `field_types` holds the type of each variable.
+#[derive(Debug, PartialEq)]
+pub struct TypedMatchBranch {
+    pub constructor: String,
+    pub tag: Option<usize>,
+    pub variables: Vec<String>,
+    pub field_types: Vec<Type>,
+    pub body: TypedAST,
+}
+
 #[derive(Debug, PartialEq)]
 pub struct TypedAST {
     pub ty: Type,
     pub ast: Box<TypedASTEnum>,
 }
 
+#[derive(Default)]
+struct TypeEnvironment {
+    variables: HashMap<String, Type>,
+    data_types: HashMap<String, Vec<Constructor>>,
+    aliases: HashMap<String, Type>,
+}
+
+impl TypeEnvironment {
+    /// Returns the name of the data type that declares `constructor`, the constructor's tag and
+    /// the constructor itself.
+    fn constructor(&self, constructor: &str) -> Option<(&String, usize, &Constructor)> {
+        self.data_types
+            .iter()
+            .find_map(|(data_name, constructors)| {
+                constructors
+                    .iter()
+                    .enumerate()
+                    .find(|(_, declared)| declared.name == constructor)
+                    .map(|(tag, declared)| (data_name, tag, declared))
+            })
+    }
+
+    /// Replaces every alias in `ty` with the type it names, checking that all of the data types
+    /// it mentions are declared.
+    fn expand(&self, ty: &Type) -> Type {
+        match ty {
+            Type::Number | Type::Boolean => ty.clone(),
+            Type::Function { argument, ret } => Type::Function {
+                argument: Box::new(self.expand(argument)),
+                ret: Box::new(self.expand(ret)),
+            },
+            Type::Data(name) => {
+                if !self.data_types.contains_key(name) {
+                    panic!("Data type '{}' is not declared", name)
+                }
+                ty.clone()
+            }
+            Type::Reference(ty) => Type::Reference(Box::new(self.expand(ty))),
+            Type::Named(name) => match self.aliases.get(name) {
+                Some(ty) => ty.clone(),
+                None => panic!("Type '{}' is not declared", name),
+            },
+        }
+    }
+
+    /// The type that `ty` names if it is an alias, and otherwise `ty` itself.
+    fn unalias<'a>(&'a self, ty: &'a Type) -> &'a Type {
+        match ty {
+            Type::Named(name) => self
+                .aliases
+                .get(name)
+                .unwrap_or_else(|| panic!("Type '{}' is not declared", name)),
+            ty => ty,
+        }
+    }
+
+    /// Whether `ty1` and `ty2` are the same once their aliases are expanded.
+    fn same(&self, ty1: &Type, ty2: &Type) -> bool {
+        self.expand(ty1) == self.expand(ty2)
+    }
+
+    /// Checks that every type that the annotation `ty` mentions is declared, and returns it with
+    /// its aliases, which are only expanded when types are compared.
+    fn annotation(&self, ty: &Type) -> Type {
+        self.expand(ty);
+        ty.clone()
+    }
+
+    fn check_type_name_is_available(&self, name: &str) {
+        if self.data_types.contains_key(name) || self.aliases.contains_key(name) {
+            panic!("Type '{}' is already declared", name)
+        }
+    }
+}
+
 impl TypedAST {
     pub fn new(ast: &AST) -> TypedAST {
-        TypedAST::typer(ast, &mut HashMap::new())
+        TypedAST::typer(ast, &mut TypeEnvironment::default())
+    }
+
+    /// The program that `self` was checked from, without its locations and comments, and with
+    /// the aliases in the type of each `typeC` expanded.
+    pub fn erase(&self) -> AST {
+        let erase_box = |typed_ast: &TypedAST| Box::new(typed_ast.erase());
+        match &*self.ast {
+            TypedASTEnum::NumberLiteral(number) => AST::NumberLiteral(*number),
+            TypedASTEnum::Plus(lhs, rhs) => AST::Plus(erase_box(lhs), erase_box(rhs)),
+            TypedASTEnum::Multiply(lhs, rhs) => AST::Multiply(erase_box(lhs), erase_box(rhs)),
+            TypedASTEnum::TrueLiteral => AST::TrueLiteral,
+            TypedASTEnum::FalseLiteral => AST::FalseLiteral,
+            TypedASTEnum::Equals(lhs, rhs) => AST::Equals(erase_box(lhs), erase_box(rhs)),
+            TypedASTEnum::If(if_struct) => AST::If(If {
+                condition: erase_box(&if_struct.condition),
+                then: erase_box(&if_struct.then),
+                els: erase_box(&if_struct.els),
+            }),
+            TypedASTEnum::Identifier(name) => AST::Identifier(name.clone()),
+            TypedASTEnum::FunctionApplication(function_application) => {
+                AST::FunctionApplication(FunctionApplication {
+                    function: erase_box(&function_application.function),
+                    argument: erase_box(&function_application.argument),
+                })
+            }
+            TypedASTEnum::FunctionDefinition(function_definition) => {
+                AST::FunctionDefinition(FunctionDefinition {
+                    argument_name: function_definition.argument_name.clone(),
+                    argument_type: function_definition.argument_type.clone(),
+                    return_type: function_definition.return_type.clone(),
+                    body: erase_box(&function_definition.body),
+                })
+            }
+            TypedASTEnum::RecursiveFunction(recursive_function) => {
+                AST::RecursiveFunction(RecursiveFunction {
+                    function_name: recursive_function.function_name.clone(),
+                    argument_name: recursive_function.argument_name.clone(),
+                    argument_type: recursive_function.argument_type.clone(),
+                    return_type: recursive_function.return_type.clone(),
+                    body: erase_box(&recursive_function.body),
+                    function_use: erase_box(&recursive_function.function_use),
+                })
+            }
+            TypedASTEnum::RecursiveFunctionGroup(recursive_function_group) => {
+                AST::RecursiveFunctionGroup(RecursiveFunctionGroup {
+                    functions: recursive_function_group
+                        .functions
+                        .iter()
+                        .map(|function| RecursiveBinding {
+                            function_name: function.function_name.clone(),
+                            argument_name: function.argument_name.clone(),
+                            argument_type: function.argument_type.clone(),
+                            return_type: function.return_type.clone(),
+                            body: erase_box(&function.body),
+                        })
+                        .collect(),
+                    function_use: erase_box(&recursive_function_group.function_use),
+                })
+            }
+            TypedASTEnum::DataDeclaration(data_declaration) => {
+                AST::DataDeclaration(DataDeclaration {
+                    name: data_declaration.name.clone(),
+                    constructors: data_declaration.constructors.clone(),
+                    body: erase_box(&data_declaration.body),
+                })
+            }
+            TypedASTEnum::Construct(construct) => AST::Construct(Construct {
+                constructor: construct.constructor.clone(),
+                arguments: construct.arguments.iter().map(TypedAST::erase).collect(),
+            }),
+            TypedASTEnum::Match(match_struct) => AST::Match(Match {
+                scrutinee: erase_box(&match_struct.scrutinee),
+                branches: match_struct
+                    .branches
+                    .iter()
+                    .map(|branch| MatchBranch {
+                        constructor: branch.constructor.clone(),
+                        variables: branch.variables.clone(),
+                        body: erase_box(&branch.body),
+                    })
+                    .collect(),
+            }),
+            TypedASTEnum::Reference(ast) => AST::Reference(erase_box(ast)),
+            TypedASTEnum::Dereference(ast) => AST::Dereference(erase_box(ast)),
+            TypedASTEnum::Set(reference, value) => AST::Set(erase_box(reference), erase_box(value)),
+            TypedASTEnum::PhysicalEquals(lhs, rhs) => {
+                AST::PhysicalEquals(erase_box(lhs), erase_box(rhs))
+            }
+            TypedASTEnum::Fix(ast) => AST::Fix(erase_box(ast)),
+            TypedASTEnum::TypeDeclaration(type_declaration) => {
+                AST::TypeDeclaration(TypeDeclaration {
+                    name: type_declaration.name.clone(),
+                    ty: type_declaration.ty.clone(),
+                    body: erase_box(&type_declaration.body),
+                })
+            }
+        }
     }
 
-    fn typer(ast: &AST, type_enviroment: &mut HashMap<String, Type>) -> TypedAST {
+    fn typer(ast: &AST, type_enviroment: &mut TypeEnvironment) -> TypedAST {
         match ast {
             AST::TrueLiteral => TypedAST {
                 ty: Type::Boolean,
@@ -93,7 +365,9 @@ impl TypedAST {
                 let typed_ast1 = TypedAST::typer(operand1, type_enviroment);
                 let typed_ast2 = TypedAST::typer(operand2, type_enviroment);
 
-                if typed_ast1.ty != Type::Number || typed_ast2.ty != Type::Number {
+                if !type_enviroment.same(&typed_ast1.ty, &Type::Number)
+                    || !type_enviroment.same(&typed_ast2.ty, &Type::Number)
+                {
                     panic!("Types differ in PlusC!")
                 }
 
@@ -106,7 +380,9 @@ impl TypedAST {
                 let typed_ast1 = TypedAST::typer(operand1, type_enviroment);
                 let typed_ast2 = TypedAST::typer(operand2, type_enviroment);
 
-                if typed_ast1.ty != Type::Number || typed_ast2.ty != Type::Number {
+                if !type_enviroment.same(&typed_ast1.ty, &Type::Number)
+                    || !type_enviroment.same(&typed_ast2.ty, &Type::Number)
+                {
                     panic!("Types differ in MultC!")
                 }
 
@@ -119,11 +395,21 @@ impl TypedAST {
                 let typed_ast1 = TypedAST::typer(operand1, type_enviroment);
                 let typed_ast2 = TypedAST::typer(operand2, type_enviroment);
 
-                if let Type::Function { .. } = typed_ast1.ty {
+                let ty1 = type_enviroment.unalias(&typed_ast1.ty);
+                let ty2 = type_enviroment.unalias(&typed_ast2.ty);
+                if let Type::Function { .. } = ty1 {
                     panic!("First argument to EqC is a FunT")
-                } else if let Type::Function { .. } = typed_ast2.ty {
+                } else if let Type::Function { .. } = ty2 {
                     panic!("Second argument to EqC is a FunT")
-                } else if typed_ast1.ty != typed_ast2.ty {
+                } else if let Type::Data(_) = ty1 {
+                    panic!("First argument to EqC is a DataT, use matchC to inspect it")
+                } else if let Type::Data(_) = ty2 {
+                    panic!("Second argument to EqC is a DataT, use matchC to inspect it")
+                } else if let Type::Reference(_) = ty1 {
+                    panic!("First argument to EqC is a RefT, use physEqC to compare references")
+                } else if let Type::Reference(_) = ty2 {
+                    panic!("Second argument to EqC is a RefT, use physEqC to compare references")
+                } else if ty1 != ty2 {
                     panic!("Types differ in EqC!")
                 }
 
@@ -132,15 +418,71 @@ impl TypedAST {
                     ast: Box::new(TypedASTEnum::Equals(typed_ast1, typed_ast2)),
                 }
             }
+            AST::PhysicalEquals(operand1, operand2) => {
+                let typed_ast1 = TypedAST::typer(operand1, type_enviroment);
+                let typed_ast2 = TypedAST::typer(operand2, type_enviroment);
+
+                if !matches!(type_enviroment.unalias(&typed_ast1.ty), Type::Reference(_)) {
+                    panic!("First argument to physEqC is {}, not a RefT", typed_ast1.ty)
+                }
+                if !type_enviroment.same(&typed_ast1.ty, &typed_ast2.ty) {
+                    panic!(
+                        "Types differ in physEqC: {} and {}",
+                        typed_ast1.ty, typed_ast2.ty
+                    )
+                }
+
+                TypedAST {
+                    ty: Type::Boolean,
+                    ast: Box::new(TypedASTEnum::PhysicalEquals(typed_ast1, typed_ast2)),
+                }
+            }
+            AST::Reference(ast) => {
+                let typed_ast = TypedAST::typer(ast, type_enviroment);
+
+                TypedAST {
+                    ty: Type::Reference(Box::new(typed_ast.ty.clone())),
+                    ast: Box::new(TypedASTEnum::Reference(typed_ast)),
+                }
+            }
+            AST::Dereference(ast) => {
+                let typed_ast = TypedAST::typer(ast, type_enviroment);
+                let ty = match type_enviroment.unalias(&typed_ast.ty) {
+                    Type::Reference(ty) => (**ty).clone(),
+                    _ => panic!("derefC expects a RefT but was given {}", typed_ast.ty),
+                };
+
+                TypedAST {
+                    ty,
+                    ast: Box::new(TypedASTEnum::Dereference(typed_ast)),
+                }
+            }
+            AST::Set(reference, value) => {
+                let reference = TypedAST::typer(reference, type_enviroment);
+                let value = TypedAST::typer(value, type_enviroment);
+                match type_enviroment.unalias(&reference.ty) {
+                    Type::Reference(ty) if type_enviroment.same(ty, &value.ty) => (),
+                    Type::Reference(ty) => {
+                        panic!("setC stores {} in a reference to {}", value.ty, ty)
+                    }
+                    _ => panic!("setC expects a RefT but was given {}", reference.ty),
+                }
+
+                // Like assignment in ML, except that the stored value is returned instead of unit.
+                TypedAST {
+                    ty: value.ty.clone(),
+                    ast: Box::new(TypedASTEnum::Set(reference, value)),
+                }
+            }
             AST::If(if_struct) => {
                 let condition = TypedAST::typer(&if_struct.condition, type_enviroment);
-                if condition.ty != Type::Boolean {
+                if !type_enviroment.same(&condition.ty, &Type::Boolean) {
                     panic!("Condition in an if statement is not boolean!")
                 }
 
                 let then = TypedAST::typer(&if_struct.then, type_enviroment);
                 let els = TypedAST::typer(&if_struct.els, type_enviroment);
-                if then.ty != els.ty {
+                if !type_enviroment.same(&then.ty, &els.ty) {
                     panic!("Types differ in then and else part of an if statement!")
                 }
 
@@ -154,31 +496,31 @@ impl TypedAST {
                 }
             }
             AST::Identifier(identifier) => {
-                if !type_enviroment.contains_key(identifier) {
+                if !type_enviroment.variables.contains_key(identifier) {
                     panic!("Variable not saved in type environment")
                 }
 
                 TypedAST {
-                    ty: type_enviroment[identifier].clone(),
+                    ty: type_enviroment.variables[identifier].clone(),
                     ast: Box::new(TypedASTEnum::Identifier(identifier.clone())),
                 }
             }
             AST::FunctionApplication(function_application_struct) => {
                 let function =
                     TypedAST::typer(&function_application_struct.function, type_enviroment);
-                match &function.ty {
+                match type_enviroment.unalias(&function.ty).clone() {
                     Type::Function {
                         argument: function_argument_type,
                         ret,
                     } => {
                         let argument =
                             TypedAST::typer(&function_application_struct.argument, type_enviroment);
-                        if **function_argument_type != argument.ty {
+                        if !type_enviroment.same(&function_argument_type, &argument.ty) {
                             panic!("Argument type doesn't match declared type")
                         }
 
                         TypedAST {
-                            ty: (**ret).clone(),
+                            ty: *ret,
                             ast: Box::new(TypedASTEnum::FunctionApplication(
                                 TypedFunctionApplication { function, argument },
                             )),
@@ -188,73 +530,386 @@ impl TypedAST {
                 }
             }
             AST::FunctionDefinition(function_definition_struct) => {
-                type_enviroment.insert(
+                let argument_type =
+                    type_enviroment.annotation(&function_definition_struct.argument_type);
+                type_enviroment.variables.insert(
                     function_definition_struct.argument_name.clone(),
-                    function_definition_struct.argument_type.clone(),
+                    argument_type.clone(),
                 );
 
                 let body = TypedAST::typer(&function_definition_struct.body, type_enviroment);
-                if body.ty != function_definition_struct.return_type {
-                    panic!("Body type doesn't match declared type")
+                let return_type =
+                    type_enviroment.annotation(&function_definition_struct.return_type);
+                if !type_enviroment.same(&body.ty, &return_type) {
+                    panic!(
+                        "Body type {} doesn't match declared type {}",
+                        body.ty, function_definition_struct.return_type
+                    )
                 }
 
                 /*
                  * Since the body has type checked we can remove the variable name form the scope to
                  * preserve a common understanding of scope. This allows us ot avoid cloning the HashMap.
                  */
-                type_enviroment.remove(&function_definition_struct.argument_name);
+                type_enviroment
+                    .variables
+                    .remove(&function_definition_struct.argument_name);
 
                 TypedAST {
                     ty: Type::Function {
-                        argument: Box::new(function_definition_struct.argument_type.clone()),
-                        ret: Box::new(function_definition_struct.return_type.clone()),
+                        argument: Box::new(argument_type.clone()),
+                        ret: Box::new(return_type.clone()),
                     },
                     ast: Box::new(TypedASTEnum::FunctionDefinition(TypedFunctionDefinition {
                         argument_name: function_definition_struct.argument_name.clone(),
-                        argument_type: function_definition_struct.argument_type.clone(),
-                        return_type: function_definition_struct.return_type.clone(),
+                        argument_type,
+                        return_type,
                         body,
                     })),
                 }
             }
+            AST::Fix(ast) => {
+                let function = TypedAST::typer(ast, type_enviroment);
+                let ty = match type_enviroment.unalias(&function.ty) {
+                    Type::Function { argument, ret } if type_enviroment.same(argument, ret) => {
+                        match type_enviroment.unalias(argument) {
+                            Type::Function { .. } => (**argument).clone(),
+                            _ => panic!(
+                                "fixC is restricted to function types but was given {}",
+                                argument
+                            ),
+                        }
+                    }
+                    _ => panic!(
+                        "fixC expects a function from a type to itself but was given {}",
+                        function.ty
+                    ),
+                };
+
+                TypedAST {
+                    ty,
+                    ast: Box::new(TypedASTEnum::Fix(function)),
+                }
+            }
             AST::RecursiveFunction(recursive_function_struct) => {
-                type_enviroment.insert(
+                let argument_type =
+                    type_enviroment.annotation(&recursive_function_struct.argument_type);
+                let return_type =
+                    type_enviroment.annotation(&recursive_function_struct.return_type);
+                type_enviroment.variables.insert(
                     recursive_function_struct.function_name.clone(),
                     Type::Function {
-                        argument: Box::new(recursive_function_struct.argument_type.clone()),
-                        ret: Box::new(recursive_function_struct.return_type.clone()),
+                        argument: Box::new(argument_type.clone()),
+                        ret: Box::new(return_type.clone()),
                     },
                 );
-                type_enviroment.insert(
+                type_enviroment.variables.insert(
                     recursive_function_struct.argument_name.clone(),
-                    recursive_function_struct.argument_type.clone(),
+                    argument_type.clone(),
                 );
 
                 let body = TypedAST::typer(&recursive_function_struct.body, type_enviroment);
-                if recursive_function_struct.return_type != body.ty {
+                if !type_enviroment.same(&return_type, &body.ty) {
                     panic!(
-                        "Return type of recursive function does not match return type of the body!"
+                        "Return type of recursive function, {}, does not match return type of the body, {}!",
+                        recursive_function_struct.return_type, body.ty
                     );
                 }
 
                 let function_use =
                     TypedAST::typer(&recursive_function_struct.function_use, type_enviroment);
 
-                type_enviroment.remove(&recursive_function_struct.function_name);
-                type_enviroment.remove(&recursive_function_struct.argument_name);
+                type_enviroment
+                    .variables
+                    .remove(&recursive_function_struct.function_name);
+                type_enviroment
+                    .variables
+                    .remove(&recursive_function_struct.argument_name);
 
                 TypedAST {
                     ty: function_use.ty.clone(),
                     ast: Box::new(TypedASTEnum::RecursiveFunction(TypedRecursiveFunction {
                         function_name: recursive_function_struct.function_name.clone(),
                         argument_name: recursive_function_struct.argument_name.clone(),
-                        argument_type: recursive_function_struct.argument_type.clone(),
-                        return_type: recursive_function_struct.return_type.clone(),
+                        argument_type,
+                        return_type,
                         body,
                         function_use,
                     })),
                 }
             }
+            AST::RecursiveFunctionGroup(recursive_function_group) => {
+                // All of the functions are in scope before any body is checked.
+                for function in &recursive_function_group.functions {
+                    type_enviroment.variables.insert(
+                        function.function_name.clone(),
+                        Type::Function {
+                            argument: Box::new(type_enviroment.annotation(&function.argument_type)),
+                            ret: Box::new(type_enviroment.annotation(&function.return_type)),
+                        },
+                    );
+                }
+
+                let functions = recursive_function_group
+                    .functions
+                    .iter()
+                    .map(|function| {
+                        let argument_type = type_enviroment.annotation(&function.argument_type);
+                        let return_type = type_enviroment.annotation(&function.return_type);
+                        type_enviroment
+                            .variables
+                            .insert(function.argument_name.clone(), argument_type.clone());
+                        let body = TypedAST::typer(&function.body, type_enviroment);
+                        if !type_enviroment.same(&return_type, &body.ty) {
+                            panic!(
+                                "Return type of recursive function '{}', {}, does not match return type of the body, {}!",
+                                function.function_name, function.return_type, body.ty
+                            );
+                        }
+                        type_enviroment.variables.remove(&function.argument_name);
+
+                        TypedRecursiveBinding {
+                            function_name: function.function_name.clone(),
+                            argument_name: function.argument_name.clone(),
+                            argument_type,
+                            return_type,
+                            body,
+                        }
+                    })
+                    .collect();
+
+                let function_use =
+                    TypedAST::typer(&recursive_function_group.function_use, type_enviroment);
+
+                for function in &recursive_function_group.functions {
+                    type_enviroment.variables.remove(&function.function_name);
+                }
+
+                TypedAST {
+                    ty: function_use.ty.clone(),
+                    ast: Box::new(TypedASTEnum::RecursiveFunctionGroup(
+                        TypedRecursiveFunctionGroup {
+                            functions,
+                            function_use,
+                        },
+                    )),
+                }
+            }
+            AST::DataDeclaration(data_declaration) => {
+                type_enviroment.check_type_name_is_available(&data_declaration.name);
+                for (index, constructor) in data_declaration.constructors.iter().enumerate() {
+                    if constructor.name == WILDCARD
+                        || data_declaration.constructors[..index]
+                            .iter()
+                            .any(|previous| previous.name == constructor.name)
+                        || type_enviroment.constructor(&constructor.name).is_some()
+                    {
+                        panic!("Constructor name '{}' is not available", constructor.name)
+                    }
+                }
+
+                // The data type is in scope in its own constructors so that it can be recursive.
+                type_enviroment.data_types.insert(
+                    data_declaration.name.clone(),
+                    data_declaration.constructors.clone(),
+                );
+                let constructors: Vec<Constructor> = data_declaration
+                    .constructors
+                    .iter()
+                    .map(|constructor| Constructor {
+                        name: constructor.name.clone(),
+                        fields: constructor
+                            .fields
+                            .iter()
+                            .map(|field| type_enviroment.annotation(field))
+                            .collect(),
+                    })
+                    .collect();
+                type_enviroment
+                    .data_types
+                    .insert(data_declaration.name.clone(), constructors.clone());
+
+                let body = TypedAST::typer(&data_declaration.body, type_enviroment);
+                if body.ty.mentions_data(&data_declaration.name) {
+                    panic!(
+                        "The type of dataC's body, {}, refers to '{}' outside of its declaration",
+                        body.ty, data_declaration.name
+                    )
+                }
+
+                type_enviroment.data_types.remove(&data_declaration.name);
+
+                TypedAST {
+                    ty: body.ty.clone(),
+                    ast: Box::new(TypedASTEnum::DataDeclaration(TypedDataDeclaration {
+                        name: data_declaration.name.clone(),
+                        constructors,
+                        body,
+                    })),
+                }
+            }
+            AST::Located(_, ast) | AST::Commented(_, ast) => TypedAST::typer(ast, type_enviroment),
+            AST::Error => panic!("Can't type check a node that failed to parse"),
+            AST::TypeDeclaration(type_declaration) => {
+                type_enviroment.check_type_name_is_available(&type_declaration.name);
+                let ty = type_enviroment.expand(&type_declaration.ty);
+                type_enviroment
+                    .aliases
+                    .insert(type_declaration.name.clone(), ty.clone());
+
+                let body = TypedAST::typer(&type_declaration.body, type_enviroment);
+
+                type_enviroment.aliases.remove(&type_declaration.name);
+
+                // The alias can't be used outside of its declaration, so it is expanded in the type
+                // of the body.
+                TypedAST {
+                    ty: body.ty.substitute(&type_declaration.name, &ty),
+                    ast: Box::new(TypedASTEnum::TypeDeclaration(TypedTypeDeclaration {
+                        name: type_declaration.name.clone(),
+                        ty,
+                        body,
+                    })),
+                }
+            }
+            AST::Construct(construct) => {
+                let (data_name, tag, constructor) =
+                    match type_enviroment.constructor(&construct.constructor) {
+                        Some((data_name, tag, constructor)) => {
+                            (data_name.clone(), tag, constructor.clone())
+                        }
+                        None => panic!("Constructor '{}' is not declared", construct.constructor),
+                    };
+                if constructor.fields.len() != construct.arguments.len() {
+                    panic!(
+                        "Constructor '{}' takes {} arguments but was given {}",
+                        constructor.name,
+                        constructor.fields.len(),
+                        construct.arguments.len()
+                    )
+                }
+
+                let arguments = construct
+                    .arguments
+                    .iter()
+                    .zip(&constructor.fields)
+                    .map(|(argument, field)| {
+                        let argument = TypedAST::typer(argument, type_enviroment);
+                        if !type_enviroment.same(&argument.ty, field) {
+                            panic!(
+                                "Argument to constructor '{}' has type {} but the field has type {}",
+                                constructor.name, argument.ty, field
+                            )
+                        }
+                        argument
+                    })
+                    .collect();
+
+                TypedAST {
+                    ty: Type::Data(data_name),
+                    ast: Box::new(TypedASTEnum::Construct(TypedConstruct {
+                        constructor: constructor.name,
+                        tag,
+                        arguments,
+                    })),
+                }
+            }
+            AST::Match(match_struct) => {
+                let scrutinee = TypedAST::typer(&match_struct.scrutinee, type_enviroment);
+                let data_name = match type_enviroment.unalias(&scrutinee.ty) {
+                    Type::Data(data_name) => data_name.clone(),
+                    _ => panic!("matchC expects a DataT but was given {}", scrutinee.ty),
+                };
+                let constructors = type_enviroment.data_types[&data_name].clone();
+
+                let mut covered = vec![false; constructors.len()];
+                let mut branches: Vec<TypedMatchBranch> = Vec::new();
+                let mut ty: Option<Type> = None;
+                for branch in &match_struct.branches {
+                    if branches.iter().any(|previous| previous.tag.is_none())
+                        || (branch.constructor == WILDCARD && covered.iter().all(|c| *c))
+                    {
+                        panic!(
+                            "Redundant branch '{}' in matchC on {}",
+                            branch.constructor, data_name
+                        )
+                    }
+
+                    let (tag, field_types) = if branch.constructor == WILDCARD {
+                        (None, Vec::new())
+                    } else {
+                        let tag = constructors
+                            .iter()
+                            .position(|constructor| constructor.name == branch.constructor)
+                            .unwrap_or_else(|| {
+                                panic!(
+                                    "'{}' is not a constructor of {}",
+                                    branch.constructor, data_name
+                                )
+                            });
+                        if covered[tag] {
+                            panic!(
+                                "Redundant branch '{}' in matchC on {}",
+                                branch.constructor, data_name
+                            )
+                        }
+                        covered[tag] = true;
+                        (Some(tag), constructors[tag].fields.clone())
+                    };
+                    if field_types.len() != branch.variables.len() {
+                        panic!(
+                            "Branch '{}' binds {} variables but the constructor has {} fields",
+                            branch.constructor,
+                            branch.variables.len(),
+                            field_types.len()
+                        )
+                    }
+
+                    for (variable, field_type) in branch.variables.iter().zip(&field_types) {
+                        type_enviroment
+                            .variables
+                            .insert(variable.clone(), field_type.clone());
+                    }
+                    let body = TypedAST::typer(&branch.body, type_enviroment);
+                    for variable in &branch.variables {
+                        type_enviroment.variables.remove(variable);
+                    }
+
+                    match &ty {
+                        Some(ty) if !type_enviroment.same(ty, &body.ty) => panic!(
+                            "Types differ in the branches of matchC: {} and {}",
+                            ty, body.ty
+                        ),
+                        Some(_) => (),
+                        None => ty = Some(body.ty.clone()),
+                    }
+
+                    branches.push(TypedMatchBranch {
+                        constructor: branch.constructor.clone(),
+                        tag,
+                        variables: branch.variables.clone(),
+                        field_types,
+                        body,
+                    });
+                }
+
+                if branches.iter().all(|branch| branch.tag.is_some()) {
+                    if let Some(missing) = covered.iter().position(|c| !*c) {
+                        panic!(
+                            "Non-exhaustive matchC on {}: '{}' is not covered",
+                            data_name, constructors[missing].name
+                        )
+                    }
+                }
+
+                TypedAST {
+                    ty: ty.unwrap(),
+                    ast: Box::new(TypedASTEnum::Match(TypedMatch {
+                        scrutinee,
+                        branches,
+                    })),
+                }
+            }
         }
     }
 }
@@ -266,7 +921,10 @@ pub fn type_of(ast: &AST) -> Type {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::parse::{FunctionApplication, FunctionDefinition, RecursiveFunction};
+    use crate::read::{
+        self,
+        tests::{SYNTAX_ERRORS, TYPE_ERRORS},
+    };
 
     #[test]
     fn false_literal() {
@@ -337,4 +995,148 @@ mod tests {
         }));
         assert_eq!(type_of(&input), Type::Boolean);
     }
+
+    fn parity_group(is_odd_return_type: Type) -> AST {
+        AST::RecursiveFunctionGroup(RecursiveFunctionGroup {
+            functions: vec![
+                RecursiveBinding {
+                    function_name: String::from("is_even"),
+                    argument_name: String::from("n"),
+                    argument_type: Type::Number,
+                    return_type: Type::Boolean,
+                    body: Box::new(AST::If(If {
+                        condition: Box::new(AST::Equals(
+                            Box::new(AST::Identifier(String::from("n"))),
+                            Box::new(AST::NumberLiteral(0)),
+                        )),
+                        then: Box::new(AST::TrueLiteral),
+                        els: Box::new(AST::FunctionApplication(FunctionApplication {
+                            function: Box::new(AST::Identifier(String::from("is_odd"))),
+                            argument: Box::new(AST::Plus(
+                                Box::new(AST::Identifier(String::from("n"))),
+                                Box::new(AST::NumberLiteral(-1)),
+                            )),
+                        })),
+                    })),
+                },
+                RecursiveBinding {
+                    function_name: String::from("is_odd"),
+                    argument_name: String::from("m"),
+                    argument_type: Type::Number,
+                    return_type: is_odd_return_type,
+                    body: Box::new(AST::If(If {
+                        condition: Box::new(AST::Equals(
+                            Box::new(AST::Identifier(String::from("m"))),
+                            Box::new(AST::NumberLiteral(0)),
+                        )),
+                        then: Box::new(AST::FalseLiteral),
+                        els: Box::new(AST::FunctionApplication(FunctionApplication {
+                            function: Box::new(AST::Identifier(String::from("is_even"))),
+                            argument: Box::new(AST::Plus(
+                                Box::new(AST::Identifier(String::from("m"))),
+                                Box::new(AST::NumberLiteral(-1)),
+                            )),
+                        })),
+                    })),
+                },
+            ],
+            function_use: Box::new(AST::FunctionApplication(FunctionApplication {
+                function: Box::new(AST::Identifier(String::from("is_even"))),
+                argument: Box::new(AST::NumberLiteral(4)),
+            })),
+        })
+    }
+
+    #[test]
+    fn rec_group_c() {
+        assert_eq!(type_of(&parity_group(Type::Boolean)), Type::Boolean);
+    }
+
+    #[test]
+    #[should_panic]
+    fn rec_group_c_fail_return_type() {
+        type_of(&parity_group(Type::Number));
+    }
+
+    fn increment_with_alias(body: AST) -> AST {
+        AST::TypeDeclaration(TypeDeclaration {
+            name: String::from("Counter"),
+            ty: Type::Number,
+            body: Box::new(AST::FunctionDefinition(FunctionDefinition {
+                argument_name: String::from("x"),
+                argument_type: Type::Named(String::from("Counter")),
+                return_type: Type::Named(String::from("Counter")),
+                body: Box::new(body),
+            })),
+        })
+    }
+
+    #[test]
+    fn type_c() {
+        let input = increment_with_alias(AST::Plus(
+            Box::new(AST::Identifier(String::from("x"))),
+            Box::new(AST::NumberLiteral(1)),
+        ));
+        assert_eq!(
+            type_of(&input),
+            Type::Function {
+                argument: Box::new(Type::Number),
+                ret: Box::new(Type::Number),
+            }
+        );
+    }
+
+    #[test]
+    fn type_c_keeps_names_inside() {
+        let input = increment_with_alias(AST::Plus(
+            Box::new(AST::Identifier(String::from("x"))),
+            Box::new(AST::NumberLiteral(1)),
+        ));
+        let typed_ast = TypedAST::new(&input);
+        let counter = Type::Named(String::from("Counter"));
+        match &*typed_ast.ast {
+            TypedASTEnum::TypeDeclaration(type_declaration) => assert_eq!(
+                type_declaration.body.ty,
+                Type::Function {
+                    argument: Box::new(counter.clone()),
+                    ret: Box::new(counter),
+                }
+            ),
+            ast => panic!("Expected a type declaration, found {:?}", ast),
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "derefC expects a RefT but was given Counter")]
+    fn type_c_fail_reports_name() {
+        type_of(&increment_with_alias(AST::Dereference(Box::new(
+            AST::Identifier(String::from("x")),
+        ))));
+    }
+
+    #[test]
+    #[should_panic(expected = "doesn't match declared type Counter")]
+    fn type_c_fail_body_type() {
+        type_of(&increment_with_alias(AST::TrueLiteral));
+    }
+
+    #[test]
+    #[should_panic(expected = "Type 'Counter' is not declared")]
+    fn named_t_fail_undeclared() {
+        type_of(&AST::FunctionDefinition(FunctionDefinition {
+            argument_name: String::from("x"),
+            argument_type: Type::Named(String::from("Counter")),
+            return_type: Type::Number,
+            body: Box::new(AST::NumberLiteral(0)),
+        }));
+    }
+
+    #[test]
+    fn erase_round_trip() {
+        let excluded = [&SYNTAX_ERRORS[..], &TYPE_ERRORS[..]].concat();
+        for (name, code) in read::tests::inputs(&excluded) {
+            let ast = crate::parse(&code);
+            assert_eq!(TypedAST::new(&ast).erase(), ast, "{}", name);
+        }
+    }
 }
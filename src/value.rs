@@ -0,0 +1,136 @@
+use std::{
+    cell::RefCell,
+    fmt::{self, Display},
+    rc::Rc,
+};
+
+/// A value computed by `resolve::evaluate` or `vm::run`, which represent a closure as `C` and a
+/// group of recursive functions as `G`.
+#[derive(Debug, PartialEq)]
+pub enum Value<C, G> {
+    Boolean(bool),
+    Number(i64),
+    Closure(Rc<C>),
+    /// The function at the index in a group of recursive functions.
+    Recursive(Rc<G>, usize),
+    /// The value of `fixC(f)`, where `f` is the contained function.
+    Fixpoint(Rc<Value<C, G>>),
+    Constructed(Rc<Constructed<C, G>>),
+    Reference(Rc<RefCell<Value<C, G>>>),
+}
+
+/// `tag` is the index of `constructor` in its data type's declaration.
+#[derive(Debug, PartialEq)]
+pub struct Constructed<C, G> {
+    pub tag: usize,
+    pub constructor: String,
+    pub fields: Vec<Value<C, G>>,
+}
+
+// Derived, `Clone` would require `C: Clone` and `G: Clone`, though only the `Rc`s are cloned.
+impl<C, G> Clone for Value<C, G> {
+    fn clone(&self) -> Self {
+        match self {
+            Value::Boolean(boolean) => Value::Boolean(*boolean),
+            Value::Number(number) => Value::Number(*number),
+            Value::Closure(closure) => Value::Closure(closure.clone()),
+            Value::Recursive(group, index) => Value::Recursive(group.clone(), *index),
+            Value::Fixpoint(generator) => Value::Fixpoint(generator.clone()),
+            Value::Constructed(constructed) => Value::Constructed(constructed.clone()),
+            Value::Reference(reference) => Value::Reference(reference.clone()),
+        }
+    }
+}
+
+impl<C, G> Value<C, G> {
+    pub(crate) fn boolean(&self) -> bool {
+        if let Value::Boolean(boolean) = self {
+            *boolean
+        } else {
+            panic!("Trying to access a non-active variant of Value enum")
+        }
+    }
+
+    pub(crate) fn number(&self) -> i64 {
+        if let Value::Number(number) = self {
+            *number
+        } else {
+            panic!("Trying to access a non-active variant of Value enum")
+        }
+    }
+
+    pub(crate) fn reference(&self) -> &Rc<RefCell<Value<C, G>>> {
+        if let Value::Reference(reference) = self {
+            reference
+        } else {
+            panic!("Trying to access a non-active variant of Value enum")
+        }
+    }
+}
+
+impl<C, G> Display for Value<C, G> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Boolean(boolean) => write!(f, "{}", boolean),
+            Value::Number(number) => write!(f, "{}", number),
+            Value::Closure(_) | Value::Recursive(_, _) => write!(f, "<function>"),
+            Value::Fixpoint(_) => write!(f, "<fixpoint>"),
+            Value::Constructed(constructed) => {
+                let fields: Vec<String> = constructed.fields.iter().map(Value::to_string).collect();
+                write!(f, "{}({})", constructed.constructor, fields.join(", "))
+            }
+            Value::Reference(reference) => write!(f, "refC({})", reference.borrow()),
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::{
+        interpret,
+        parse::AST,
+        read::{
+            self,
+            tests::{SYNTAX_ERRORS, TYPE_ERRORS},
+        },
+        tokenize::TokenStream,
+    };
+    use std::fmt::Debug;
+
+    /// Asserts that `evaluate` prints the same result as `interpret::interpret` for every input that
+    /// type checks, other than those in `excluded`.
+    pub(crate) fn assert_matches_interpret<C: Debug + PartialEq, G: Debug + PartialEq>(
+        excluded: &[&str],
+        evaluate: impl Fn(&AST) -> Value<C, G>,
+    ) {
+        // `interpret` scopes variables dynamically, so the function returned in nested_function
+        // can't find the argument of the function that returned it, and strategy_termination only
+        // terminates when arguments are delayed.
+        let unsupported = ["nested_function", "strategy_termination"];
+        let excluded = [&SYNTAX_ERRORS[..], &TYPE_ERRORS[..], &unsupported, excluded].concat();
+        for (name, code) in read::tests::inputs(&excluded) {
+            let mut token_stream = TokenStream::build(code.chars());
+            let ast = AST::build(&mut token_stream);
+            assert_eq!(
+                evaluate(&ast).to_string(),
+                interpret::interpret(&ast).to_string(),
+                "{}",
+                name
+            );
+        }
+    }
+
+    #[test]
+    fn value_display() {
+        let value: Value<(), ()> = Value::Constructed(Rc::new(Constructed {
+            tag: 1,
+            constructor: String::from("cons"),
+            fields: vec![
+                Value::Number(1),
+                Value::Reference(Rc::new(RefCell::new(Value::Boolean(true)))),
+            ],
+        }));
+        assert_eq!(value.to_string(), "cons(1, refC(true))");
+    }
+}
@@ -0,0 +1,875 @@
+use crate::{
+    type_check::{TypedAST, TypedASTEnum},
+    value,
+};
+use std::{
+    cell::RefCell,
+    fmt::{self, Display},
+    rc::Rc,
+};
+
+/// Where an instruction finds a variable, relative to the function being executed.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Variable {
+    /// The stack slot at the index above the frame's base. Slot 0 holds the argument.
+    Local(usize),
+    /// The value at the index in the closure's captures.
+    Captured(usize),
+    /// The function at the index in the closure's `recC` or `recGroupC`.
+    Recursive(usize),
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum Instruction {
+    Number(i64),
+    Boolean(bool),
+    Add,
+    Multiply,
+    Equals,
+    PhysicalEquals,
+    Reference,
+    Dereference,
+    Set,
+    /// Replaces the function on top of the stack with its fixpoint.
+    Fix,
+    Load(Variable),
+    /// Pushes a closure of the function at the index, capturing the variables in order.
+    Closure(usize, Vec<Variable>),
+    /// Pushes the closures of a `recC` or `recGroupC`, which can call each other with
+    /// `Variable::Recursive`.
+    RecursiveClosures(Vec<(usize, Vec<Variable>)>),
+    /// Pops an argument and a function and calls the function.
+    Call,
+    /// Like `Call`, but replaces the current frame.
+    TailCall,
+    Return,
+    Jump(usize),
+    JumpIfFalse(usize),
+    /// Pops the arguments and pushes a value of the constructor at the index in
+    /// `Program::constructors`, whose tag in its data type is `tag`.
+    Construct {
+        tag: usize,
+        constructor: usize,
+        arity: usize,
+    },
+    /// Jumps to the target unless the value on top of the stack has the tag. Otherwise, replaces
+    /// the value with its fields.
+    Test(usize, usize),
+    /// Replaces the value on top of the stack with its fields.
+    Unpack,
+    Pop,
+    /// Removes the given number of values below the top of the stack.
+    Slide(usize),
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Function {
+    pub name: String,
+    pub code: Vec<Instruction>,
+}
+
+/// Bytecode compiled from a `TypedAST`. The program starts by running `functions[0]`, which takes
+/// no argument.
+#[derive(Debug, PartialEq)]
+pub struct Program {
+    pub functions: Vec<Function>,
+    pub constructors: Vec<String>,
+}
+
+impl Display for Variable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Variable::Local(index) => write!(f, "local {}", index),
+            Variable::Captured(index) => write!(f, "captured {}", index),
+            Variable::Recursive(index) => write!(f, "recursive {}", index),
+        }
+    }
+}
+
+/// `closure 3 [local 0, captured 1]`
+fn write_closure(
+    f: &mut fmt::Formatter<'_>,
+    function: usize,
+    captures: &[Variable],
+) -> fmt::Result {
+    let captures: Vec<String> = captures.iter().map(Variable::to_string).collect();
+    write!(f, "{} [{}]", function, captures.join(", "))
+}
+
+impl Program {
+    fn write_instruction(
+        &self,
+        f: &mut fmt::Formatter<'_>,
+        instruction: &Instruction,
+    ) -> fmt::Result {
+        match instruction {
+            Instruction::Number(number) => write!(f, "number {}", number),
+            Instruction::Boolean(boolean) => write!(f, "boolean {}", boolean),
+            Instruction::Add => write!(f, "add"),
+            Instruction::Multiply => write!(f, "multiply"),
+            Instruction::Equals => write!(f, "equals"),
+            Instruction::PhysicalEquals => write!(f, "physical_equals"),
+            Instruction::Reference => write!(f, "reference"),
+            Instruction::Dereference => write!(f, "dereference"),
+            Instruction::Set => write!(f, "set"),
+            Instruction::Fix => write!(f, "fix"),
+            Instruction::Load(variable) => write!(f, "load {}", variable),
+            Instruction::Closure(function, captures) => {
+                write!(f, "closure ")?;
+                write_closure(f, *function, captures)
+            }
+            Instruction::RecursiveClosures(closures) => {
+                write!(f, "recursive_closures")?;
+                for (function, captures) in closures {
+                    write!(f, " ")?;
+                    write_closure(f, *function, captures)?;
+                }
+                Ok(())
+            }
+            Instruction::Call => write!(f, "call"),
+            Instruction::TailCall => write!(f, "tail_call"),
+            Instruction::Return => write!(f, "return"),
+            Instruction::Jump(target) => write!(f, "jump {}", target),
+            Instruction::JumpIfFalse(target) => write!(f, "jump_if_false {}", target),
+            Instruction::Construct {
+                tag,
+                constructor,
+                arity,
+            } => write!(
+                f,
+                "construct {} {} ({})",
+                tag, arity, self.constructors[*constructor]
+            ),
+            Instruction::Test(tag, target) => write!(f, "test {} {}", tag, target),
+            Instruction::Unpack => write!(f, "unpack"),
+            Instruction::Pop => write!(f, "pop"),
+            Instruction::Slide(count) => write!(f, "slide {}", count),
+        }
+    }
+}
+
+/// The disassembly of the program, one function at a time.
+impl Display for Program {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (index, function) in self.functions.iter().enumerate() {
+            if index != 0 {
+                writeln!(f)?;
+            }
+            writeln!(f, "{} {}:", index, function.name)?;
+            for (offset, instruction) in function.code.iter().enumerate() {
+                write!(f, "{:>6}  ", offset)?;
+                self.write_instruction(f, instruction)?;
+                writeln!(f)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A function being compiled.
+struct Context {
+    function: usize,
+    code: Vec<Instruction>,
+    /// The slot of every variable on the stack, innermost last.
+    locals: Vec<(String, usize)>,
+    /// The functions of the `recC` or `recGroupC` that defines this function.
+    recursive: Vec<String>,
+    captures: Vec<(String, Variable)>,
+    /// The number of values on the stack above the frame's base.
+    height: usize,
+}
+
+struct Compiler {
+    functions: Vec<Function>,
+    constructors: Vec<String>,
+    contexts: Vec<Context>,
+}
+
+pub fn compile(typed_ast: &TypedAST) -> Program {
+    let mut compiler = Compiler {
+        functions: Vec::new(),
+        constructors: Vec::new(),
+        contexts: Vec::new(),
+    };
+    compiler.begin_function("<main>", Vec::new(), None);
+    compiler.expression(typed_ast, true);
+    compiler.end_function();
+    Program {
+        functions: compiler.functions,
+        constructors: compiler.constructors,
+    }
+}
+
+impl Compiler {
+    fn context(&mut self) -> &mut Context {
+        self.contexts.last_mut().unwrap()
+    }
+
+    fn emit(&mut self, instruction: Instruction) {
+        let context = self.context();
+        context.height = match &instruction {
+            Instruction::Number(_)
+            | Instruction::Boolean(_)
+            | Instruction::Load(_)
+            | Instruction::Closure(_, _) => context.height + 1,
+            Instruction::RecursiveClosures(closures) => context.height + closures.len(),
+            Instruction::Add
+            | Instruction::Multiply
+            | Instruction::Equals
+            | Instruction::PhysicalEquals
+            | Instruction::Set
+            | Instruction::Call
+            | Instruction::JumpIfFalse(_)
+            | Instruction::Pop => context.height - 1,
+            Instruction::Construct { arity, .. } => context.height + 1 - arity,
+            Instruction::Slide(count) => context.height - count,
+            // The height after a test or unpack depends on the constructor, so it is set by the
+            // caller.
+            Instruction::Reference
+            | Instruction::Dereference
+            | Instruction::Fix
+            | Instruction::TailCall
+            | Instruction::Return
+            | Instruction::Jump(_)
+            | Instruction::Test(_, _)
+            | Instruction::Unpack => context.height,
+        };
+        context.code.push(instruction);
+    }
+
+    /// The offset of the next instruction.
+    fn label(&mut self) -> usize {
+        self.context().code.len()
+    }
+
+    fn patch(&mut self, offset: usize, target: usize) {
+        match &mut self.context().code[offset] {
+            Instruction::Jump(jump)
+            | Instruction::JumpIfFalse(jump)
+            | Instruction::Test(_, jump) => *jump = target,
+            _ => unreachable!("Only jumps are patched"),
+        }
+    }
+
+    fn begin_function(&mut self, name: &str, recursive: Vec<String>, argument: Option<&str>) {
+        self.functions.push(Function {
+            name: name.to_string(),
+            code: Vec::new(),
+        });
+        self.contexts.push(Context {
+            function: self.functions.len() - 1,
+            code: Vec::new(),
+            locals: argument
+                .map(|argument| (argument.to_string(), 0))
+                .into_iter()
+                .collect(),
+            recursive,
+            captures: Vec::new(),
+            height: argument.map_or(0, |_| 1),
+        });
+    }
+
+    /// Returns the index of the function and the variables its closures capture.
+    fn end_function(&mut self) -> (usize, Vec<Variable>) {
+        let context = self.contexts.pop().unwrap();
+        self.functions[context.function].code = context.code;
+        (
+            context.function,
+            context
+                .captures
+                .into_iter()
+                .map(|(_, variable)| variable)
+                .collect(),
+        )
+    }
+
+    fn function(
+        &mut self,
+        name: &str,
+        recursive: Vec<String>,
+        argument: &str,
+        body: &TypedAST,
+    ) -> (usize, Vec<Variable>) {
+        self.begin_function(name, recursive, Some(argument));
+        self.expression(body, true);
+        self.end_function()
+    }
+
+    /// Finds `name` in the function being compiled at `depth`, capturing it from the enclosing
+    /// functions if necessary.
+    fn variable(&mut self, name: &str, depth: usize) -> Variable {
+        let context = &self.contexts[depth];
+        if let Some((_, slot)) = context.locals.iter().rev().find(|(local, _)| local == name) {
+            return Variable::Local(*slot);
+        }
+        if let Some(index) = context
+            .recursive
+            .iter()
+            .position(|function| function == name)
+        {
+            return Variable::Recursive(index);
+        }
+        if let Some(index) = context
+            .captures
+            .iter()
+            .position(|(captured, _)| captured == name)
+        {
+            return Variable::Captured(index);
+        }
+        if depth == 0 {
+            panic!("Unable to find identifier: {:?}", name)
+        }
+
+        let variable = self.variable(name, depth - 1);
+        let captures = &mut self.contexts[depth].captures;
+        captures.push((name.to_string(), variable));
+        Variable::Captured(captures.len() - 1)
+    }
+
+    /// Binds `names` to the `names.len()` values on top of the stack.
+    fn bind(&mut self, names: &[&str]) {
+        let context = self.context();
+        let first = context.height - names.len();
+        for (index, name) in names.iter().enumerate() {
+            context.locals.push((name.to_string(), first + index));
+        }
+    }
+
+    fn unbind(&mut self, count: usize) {
+        let context = self.context();
+        context.locals.truncate(context.locals.len() - count);
+    }
+
+    /// Removes the `count` values below the value of the expression that was just compiled,
+    /// unless the expression was in tail position and so has already returned.
+    fn end_scope(&mut self, count: usize, tail: bool) {
+        self.unbind(count);
+        if !tail && count != 0 {
+            self.emit(Instruction::Slide(count));
+        }
+    }
+
+    fn binary(&mut self, lhs: &TypedAST, rhs: &TypedAST, instruction: Instruction, tail: bool) {
+        self.expression(lhs, false);
+        self.expression(rhs, false);
+        self.emit(instruction);
+        self.end_expression(tail);
+    }
+
+    fn unary(&mut self, ast: &TypedAST, instruction: Instruction, tail: bool) {
+        self.expression(ast, false);
+        self.emit(instruction);
+        self.end_expression(tail);
+    }
+
+    fn end_expression(&mut self, tail: bool) {
+        if tail {
+            self.emit(Instruction::Return);
+        }
+    }
+
+    /// Compiles `typed_ast`, which leaves its value on the stack or, if it is in `tail` position,
+    /// returns it.
+    fn expression(&mut self, typed_ast: &TypedAST, tail: bool) {
+        match &*typed_ast.ast {
+            TypedASTEnum::NumberLiteral(number) => {
+                self.emit(Instruction::Number(*number));
+                self.end_expression(tail);
+            }
+            TypedASTEnum::TrueLiteral => {
+                self.emit(Instruction::Boolean(true));
+                self.end_expression(tail);
+            }
+            TypedASTEnum::FalseLiteral => {
+                self.emit(Instruction::Boolean(false));
+                self.end_expression(tail);
+            }
+            TypedASTEnum::Plus(lhs, rhs) => self.binary(lhs, rhs, Instruction::Add, tail),
+            TypedASTEnum::Multiply(lhs, rhs) => self.binary(lhs, rhs, Instruction::Multiply, tail),
+            TypedASTEnum::Equals(lhs, rhs) => self.binary(lhs, rhs, Instruction::Equals, tail),
+            TypedASTEnum::PhysicalEquals(lhs, rhs) => {
+                self.binary(lhs, rhs, Instruction::PhysicalEquals, tail)
+            }
+            TypedASTEnum::Set(reference, value) => {
+                self.binary(reference, value, Instruction::Set, tail)
+            }
+            TypedASTEnum::Reference(ast) => self.unary(ast, Instruction::Reference, tail),
+            TypedASTEnum::Dereference(ast) => self.unary(ast, Instruction::Dereference, tail),
+            TypedASTEnum::Fix(ast) => self.unary(ast, Instruction::Fix, tail),
+            TypedASTEnum::If(if_struct) => {
+                self.expression(&if_struct.condition, false);
+                let jump_to_else = self.label();
+                self.emit(Instruction::JumpIfFalse(0));
+                let height = self.context().height;
+
+                self.expression(&if_struct.then, tail);
+                let jump_to_end = self.label();
+                if !tail {
+                    self.emit(Instruction::Jump(0));
+                }
+
+                let els = self.label();
+                self.patch(jump_to_else, els);
+                self.context().height = height;
+                self.expression(&if_struct.els, tail);
+                if !tail {
+                    let end = self.label();
+                    self.patch(jump_to_end, end);
+                }
+            }
+            TypedASTEnum::Identifier(name) => {
+                let variable = self.variable(name, self.contexts.len() - 1);
+                self.emit(Instruction::Load(variable));
+                self.end_expression(tail);
+            }
+            TypedASTEnum::FunctionApplication(function_application) => {
+                self.expression(&function_application.function, false);
+                self.expression(&function_application.argument, false);
+                if tail {
+                    self.emit(Instruction::TailCall);
+                } else {
+                    self.emit(Instruction::Call);
+                }
+            }
+            TypedASTEnum::FunctionDefinition(function_definition) => {
+                let (function, captures) = self.function(
+                    "<lambda>",
+                    Vec::new(),
+                    &function_definition.argument_name,
+                    &function_definition.body,
+                );
+                self.emit(Instruction::Closure(function, captures));
+                self.end_expression(tail);
+            }
+            TypedASTEnum::RecursiveFunction(recursive_function) => {
+                let names = vec![recursive_function.function_name.clone()];
+                let closure = self.function(
+                    &recursive_function.function_name,
+                    names,
+                    &recursive_function.argument_name,
+                    &recursive_function.body,
+                );
+                self.emit(Instruction::RecursiveClosures(vec![closure]));
+                self.bind(&[&recursive_function.function_name]);
+                self.expression(&recursive_function.function_use, tail);
+                self.end_scope(1, tail);
+            }
+            TypedASTEnum::RecursiveFunctionGroup(recursive_function_group) => {
+                let names: Vec<String> = recursive_function_group
+                    .functions
+                    .iter()
+                    .map(|function| function.function_name.clone())
+                    .collect();
+                let closures = recursive_function_group
+                    .functions
+                    .iter()
+                    .map(|function| {
+                        self.function(
+                            &function.function_name,
+                            names.clone(),
+                            &function.argument_name,
+                            &function.body,
+                        )
+                    })
+                    .collect();
+                self.emit(Instruction::RecursiveClosures(closures));
+                self.bind(&names.iter().map(String::as_str).collect::<Vec<&str>>());
+                self.expression(&recursive_function_group.function_use, tail);
+                self.end_scope(names.len(), tail);
+            }
+            TypedASTEnum::DataDeclaration(data_declaration) => {
+                self.expression(&data_declaration.body, tail)
+            }
+            TypedASTEnum::TypeDeclaration(type_declaration) => {
+                self.expression(&type_declaration.body, tail)
+            }
+            TypedASTEnum::Construct(construct) => {
+                for argument in &construct.arguments {
+                    self.expression(argument, false);
+                }
+                let constructor = match self
+                    .constructors
+                    .iter()
+                    .position(|name| *name == construct.constructor)
+                {
+                    Some(index) => index,
+                    None => {
+                        self.constructors.push(construct.constructor.clone());
+                        self.constructors.len() - 1
+                    }
+                };
+                self.emit(Instruction::Construct {
+                    tag: construct.tag,
+                    constructor,
+                    arity: construct.arguments.len(),
+                });
+                self.end_expression(tail);
+            }
+            TypedASTEnum::Match(match_struct) => {
+                self.expression(&match_struct.scrutinee, false);
+                let height = self.context().height;
+                let mut jumps_to_end = Vec::new();
+                for (index, branch) in match_struct.branches.iter().enumerate() {
+                    // The type checker ensures the match is exhaustive, so the last branch matches
+                    // without a test.
+                    let last = index == match_struct.branches.len() - 1;
+                    let test = self.label();
+                    match branch.tag {
+                        Some(tag) if !last => self.emit(Instruction::Test(tag, 0)),
+                        Some(_) => self.emit(Instruction::Unpack),
+                        None => self.emit(Instruction::Pop),
+                    }
+                    let count = branch.variables.len();
+                    self.context().height = height - 1 + count;
+
+                    self.bind(
+                        &branch
+                            .variables
+                            .iter()
+                            .map(String::as_str)
+                            .collect::<Vec<&str>>(),
+                    );
+                    self.expression(&branch.body, tail);
+                    self.end_scope(count, tail);
+                    if !tail && !last {
+                        jumps_to_end.push(self.label());
+                        self.emit(Instruction::Jump(0));
+                    }
+                    if !last {
+                        let next = self.label();
+                        self.patch(test, next);
+                        self.context().height = height;
+                    }
+                }
+                let end = self.label();
+                for jump in jumps_to_end {
+                    self.patch(jump, end);
+                }
+            }
+        }
+    }
+}
+
+pub type Value = value::Value<Closure, RecursiveGroup>;
+pub type Constructed = value::Constructed<Closure, RecursiveGroup>;
+
+#[derive(Debug, PartialEq)]
+pub struct Closure {
+    function: usize,
+    captures: Vec<Value>,
+}
+
+/// The closures of a `recC` or `recGroupC`. They find each other through the group rather than
+/// their captures, so there is no reference cycle.
+#[derive(Debug, PartialEq)]
+pub struct RecursiveGroup {
+    closures: Vec<Closure>,
+}
+
+struct Frame {
+    function: usize,
+    ip: usize,
+    base: usize,
+    /// The closure being executed, which is `None` for `functions[0]`.
+    callee: Option<Value>,
+}
+
+struct Machine<'a> {
+    program: &'a Program,
+    stack: Vec<Value>,
+    frames: Vec<Frame>,
+}
+
+/// Runs `program`. Calls don't recurse on the Rust stack, except to unroll a `fixC`.
+pub fn run(program: &Program) -> Value {
+    let mut machine = Machine {
+        program,
+        stack: Vec::new(),
+        frames: vec![Frame {
+            function: 0,
+            ip: 0,
+            base: 0,
+            callee: None,
+        }],
+    };
+    machine.execute(0)
+}
+
+impl<'a> Machine<'a> {
+    fn pop(&mut self) -> Value {
+        self.stack.pop().expect("The stack is empty")
+    }
+
+    fn load(&self, variable: Variable) -> Value {
+        let frame = self.frames.last().unwrap();
+        match (variable, &frame.callee) {
+            (Variable::Local(slot), _) => self.stack[frame.base + slot].clone(),
+            (Variable::Captured(index), Some(Value::Closure(closure))) => {
+                closure.captures[index].clone()
+            }
+            (Variable::Captured(index), Some(Value::Recursive(group, function))) => {
+                group.closures[*function].captures[index].clone()
+            }
+            (Variable::Recursive(index), Some(Value::Recursive(group, _))) => {
+                Value::Recursive(group.clone(), index)
+            }
+            _ => panic!("Unable to load {}", variable),
+        }
+    }
+
+    /// Pushes a frame that calls `function`.
+    fn call(&mut self, function: Value, argument: Value) {
+        let index = match &function {
+            Value::Closure(closure) => closure.function,
+            Value::Recursive(group, index) => group.closures[*index].function,
+            // fixC(f)(argument) = f(fixC(f))(argument)
+            Value::Fixpoint(generator) => {
+                let unrolled = self.apply((**generator).clone(), function.clone());
+                return self.call(unrolled, argument);
+            }
+            _ => panic!("Trying to access a non-active variant of Value enum"),
+        };
+        self.frames.push(Frame {
+            function: index,
+            ip: 0,
+            base: self.stack.len(),
+            callee: Some(function),
+        });
+        self.stack.push(argument);
+    }
+
+    /// Calls `function` and runs it to completion.
+    fn apply(&mut self, function: Value, argument: Value) -> Value {
+        let depth = self.frames.len();
+        self.call(function, argument);
+        self.execute(depth)
+    }
+
+    /// Runs until the number of frames drops to `depth`, returning the value of the last frame.
+    fn execute(&mut self, depth: usize) -> Value {
+        let program = self.program;
+        loop {
+            let frame = self.frames.last_mut().unwrap();
+            let instruction = &program.functions[frame.function].code[frame.ip];
+            frame.ip += 1;
+            match instruction {
+                Instruction::Number(number) => self.stack.push(Value::Number(*number)),
+                Instruction::Boolean(boolean) => self.stack.push(Value::Boolean(*boolean)),
+                Instruction::Add => {
+                    let rhs = self.pop().number();
+                    let lhs = self.pop().number();
+                    self.stack.push(Value::Number(lhs + rhs));
+                }
+                Instruction::Multiply => {
+                    let rhs = self.pop().number();
+                    let lhs = self.pop().number();
+                    self.stack.push(Value::Number(lhs * rhs));
+                }
+                Instruction::Equals => {
+                    let rhs = self.pop();
+                    let lhs = self.pop();
+                    self.stack.push(Value::Boolean(lhs == rhs));
+                }
+                Instruction::PhysicalEquals => {
+                    let rhs = self.pop();
+                    let lhs = self.pop();
+                    let equal = Rc::ptr_eq(lhs.reference(), rhs.reference());
+                    self.stack.push(Value::Boolean(equal));
+                }
+                Instruction::Reference => {
+                    let value = self.pop();
+                    self.stack
+                        .push(Value::Reference(Rc::new(RefCell::new(value))));
+                }
+                Instruction::Dereference => {
+                    let value = self.pop().reference().borrow().clone();
+                    self.stack.push(value);
+                }
+                Instruction::Set => {
+                    let value = self.pop();
+                    let reference = self.pop();
+                    *reference.reference().borrow_mut() = value.clone();
+                    self.stack.push(value);
+                }
+                Instruction::Fix => {
+                    let generator = self.pop();
+                    self.stack.push(Value::Fixpoint(Rc::new(generator)));
+                }
+                Instruction::Load(variable) => {
+                    let value = self.load(*variable);
+                    self.stack.push(value);
+                }
+                Instruction::Closure(function, captures) => {
+                    let captures = captures
+                        .iter()
+                        .map(|variable| self.load(*variable))
+                        .collect();
+                    self.stack.push(Value::Closure(Rc::new(Closure {
+                        function: *function,
+                        captures,
+                    })));
+                }
+                Instruction::RecursiveClosures(closures) => {
+                    let group = Rc::new(RecursiveGroup {
+                        closures: closures
+                            .iter()
+                            .map(|(function, captures)| Closure {
+                                function: *function,
+                                captures: captures
+                                    .iter()
+                                    .map(|variable| self.load(*variable))
+                                    .collect(),
+                            })
+                            .collect(),
+                    });
+                    for index in 0..closures.len() {
+                        self.stack.push(Value::Recursive(group.clone(), index));
+                    }
+                }
+                Instruction::Call => {
+                    let argument = self.pop();
+                    let function = self.pop();
+                    self.call(function, argument);
+                }
+                Instruction::TailCall => {
+                    let argument = self.pop();
+                    let function = self.pop();
+                    let frame = self.frames.pop().unwrap();
+                    self.stack.truncate(frame.base);
+                    self.call(function, argument);
+                }
+                Instruction::Return => {
+                    let value = self.pop();
+                    let frame = self.frames.pop().unwrap();
+                    self.stack.truncate(frame.base);
+                    if self.frames.len() == depth {
+                        return value;
+                    }
+                    self.stack.push(value);
+                }
+                Instruction::Jump(target) => self.frames.last_mut().unwrap().ip = *target,
+                Instruction::JumpIfFalse(target) => {
+                    if !self.pop().boolean() {
+                        self.frames.last_mut().unwrap().ip = *target;
+                    }
+                }
+                Instruction::Construct {
+                    tag,
+                    constructor,
+                    arity,
+                } => {
+                    let fields = self.stack.split_off(self.stack.len() - arity);
+                    self.stack.push(Value::Constructed(Rc::new(Constructed {
+                        tag: *tag,
+                        constructor: program.constructors[*constructor].clone(),
+                        fields,
+                    })));
+                }
+                Instruction::Test(tag, target) => match self.stack.last() {
+                    Some(Value::Constructed(constructed)) if constructed.tag != *tag => {
+                        self.frames.last_mut().unwrap().ip = *target
+                    }
+                    _ => self.unpack(),
+                },
+                Instruction::Unpack => self.unpack(),
+                Instruction::Pop => {
+                    self.pop();
+                }
+                Instruction::Slide(count) => {
+                    let value = self.pop();
+                    self.stack.truncate(self.stack.len() - count);
+                    self.stack.push(value);
+                }
+            }
+        }
+    }
+
+    fn unpack(&mut self) {
+        match self.pop() {
+            Value::Constructed(constructed) => {
+                self.stack.extend(constructed.fields.iter().cloned())
+            }
+            _ => panic!("Trying to access a non-active variant of Value enum"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse::AST, tokenize::TokenStream};
+
+    fn compile_code(code: &str) -> Program {
+        let ast = AST::build(&mut TokenStream::build(code.chars()));
+        compile(&TypedAST::new(&ast))
+    }
+
+    #[test]
+    fn vm_matches_interpret() {
+        value::tests::assert_matches_interpret(&[], |ast| run(&compile(&TypedAST::new(ast))));
+    }
+
+    #[test]
+    fn vm_closure_captures() {
+        let program = compile_code(
+            r#"appC(appC(fdC("x", numT, funT(numT, numT), fdC("y", numT, numT, plusC(idC("x"), idC("y")))), numC(1)), numC(2))"#,
+        );
+        assert_eq!(run(&program), Value::Number(3));
+        assert_eq!(
+            program.functions[2].code,
+            vec![
+                Instruction::Load(Variable::Captured(0)),
+                Instruction::Load(Variable::Local(0)),
+                Instruction::Add,
+                Instruction::Return
+            ]
+        );
+    }
+
+    #[test]
+    fn vm_tail_call() {
+        let program = compile_code(
+            r#"recC("count", "n", numT, boolT,
+                ifC(eqC(idC("n"), numC(0)), trueC, appC(idC("count"), plusC(idC("n"), numC(-1)))),
+                appC(idC("count"), numC(1000000)))"#,
+        );
+        assert!(program.functions[1].code.contains(&Instruction::TailCall));
+        assert_eq!(run(&program), Value::Boolean(true));
+    }
+
+    #[test]
+    fn vm_match() {
+        let program = compile_code(
+            r#"dataC("list", [("nil", []), ("cons", [numT, dataT("list")]), ("end", [])],
+                matchC(conC("cons", [numC(1), conC("cons", [numC(2), conC("nil", [])])]), [
+                    ("nil", [], numC(0)),
+                    ("cons", ["head", "tail"], plusC(idC("head"), matchC(idC("tail"), [
+                        ("cons", ["head", "tail"], idC("head")),
+                        ("_", [], numC(0))
+                    ]))),
+                    ("end", [], numC(0))
+                ]))"#,
+        );
+        assert_eq!(run(&program), Value::Number(3));
+    }
+
+    #[test]
+    fn disassemble() {
+        let program =
+            compile_code(r#"appC(fdC("x", numT, numT, plusC(idC("x"), numC(1))), numC(2))"#);
+        assert_eq!(
+            program.to_string(),
+            "0 <main>:
+     0  closure 1 []
+     1  number 2
+     2  tail_call
+
+1 <lambda>:
+     0  load local 0
+     1  number 1
+     2  add
+     3  return
+"
+        );
+    }
+}
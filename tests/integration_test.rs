@@ -1,11 +1,12 @@
 use core::panic;
 use lamb::{
     codegen,
-    interpret::{self, Data, Function},
+    interpret::{self, Budget, Data, Function, Limit, Strategy},
     parse::AST,
     read,
     tokenize::{self, TokenStream},
     type_check::{Type, TypedAST},
+    Syntax,
 };
 use std::convert::TryInto;
 
@@ -28,6 +29,15 @@ impl TestOptions {
                 _ => panic!(),
             },
             Type::Function { .. } => (),
+            Type::Data(_) => match expected_data {
+                Data::Constructed(_) => (),
+                _ => panic!(),
+            },
+            Type::Reference(_) => match expected_data {
+                Data::Reference(_) => (),
+                _ => panic!(),
+            },
+            Type::Named(_) => unreachable!(),
         }
     }
 
@@ -47,6 +57,10 @@ impl TestOptions {
                 assert_eq!(*number, expected_result.try_into().unwrap());
             }
             Data::Function(_) => panic!("Compiler can't return a function as its final result"),
+            Data::Constructed(_) => panic!("Compiler can't return a data type as its final result"),
+            Data::Reference(_) => panic!("Compiler can't return a reference as its final result"),
+            Data::Fixpoint(_) => panic!("Compiler can't return a function as its final result"),
+            Data::Thunk(_) => unreachable!(),
         }
     }
 
@@ -79,8 +93,8 @@ fn test_read(name: &str) -> String {
     read::build(test_to_file_name(name).as_str())
 }
 
-fn test_tokenizer(name: &str) -> TokenStream {
-    let character_stream = test_read(name).chars().collect();
+fn test_tokenizer(name: &str) -> TokenStream<'static> {
+    let character_stream: Vec<char> = test_read(name).chars().collect();
     tokenize::TokenStream::build(character_stream)
 }
 
@@ -123,9 +137,18 @@ fn test_typed_ast_and_data(name: &str, expected: Option<(Type, Data)>) -> (Typed
                 _ => unreachable!(),
             },
             Type::Function { .. } => match data {
-                Data::Function(_) => (),
+                Data::Function(_) | Data::Fixpoint(_) => (),
                 _ => unreachable!(),
             },
+            Type::Data(_) => match data {
+                Data::Constructed(_) => (),
+                _ => unreachable!(),
+            },
+            Type::Reference(_) => match data {
+                Data::Reference(_) => (),
+                _ => unreachable!(),
+            },
+            Type::Named(_) => unreachable!(),
         };
         assert_eq!(expected_data, data);
     }
@@ -144,12 +167,16 @@ fn compiler_result_to_data(expected: Option<(Type, u64)>) -> Option<(Type, Data,
                 0 => match expected_type {
                     Type::Boolean => Data::Boolean(false),
                     Type::Number => Data::Number(0),
-                    Type::Function { .. } => unreachable!(),
+                    Type::Function { .. } | Type::Data(_) | Type::Reference(_) | Type::Named(_) => {
+                        unreachable!()
+                    }
                 },
                 1 => match expected_type {
                     Type::Boolean => Data::Boolean(true),
                     Type::Number => Data::Number(1),
-                    Type::Function { .. } => unreachable!(),
+                    Type::Function { .. } | Type::Data(_) | Type::Reference(_) | Type::Named(_) => {
+                        unreachable!()
+                    }
                 },
                 number => Data::Number(number.try_into().unwrap()),
             };
@@ -204,6 +231,37 @@ fn basic() {
     test("basic", TestOptions::Compile(Some((Type::Boolean, 0))));
 }
 
+#[test]
+fn commented_factorial() {
+    test(
+        "commented_factorial",
+        TestOptions::Compile(Some((Type::Number, 120))),
+    );
+
+    let formatted = lamb::format(&test_read("commented_factorial"));
+    assert_eq!(
+        formatted,
+        r#"#| The factorial of 5, with comments wherever the
+   formatter has to keep them. |#
+recC(
+  "S", ; the function
+  "num",
+  numT, ; counts up from 1
+  numT,
+  ; count up to the base case
+  ifC(
+    eqC(idC("num"), numC(5)),
+    numC(5), ; the last factor
+    multC(idC("num"), appC(idC("S"), plusC(idC("num"), numC(1))))
+  ),
+  appC(idC("S"), numC(1))
+)
+; 120
+"#
+    );
+    assert_eq!(lamb::format(&formatted), formatted);
+}
+
 #[test]
 fn factorial() {
     test(
@@ -238,6 +296,20 @@ fn function_application_parse_fail() {
     test("function_application_parse_fail", TestOptions::Parse);
 }
 
+#[test]
+fn fix_factorial() {
+    test(
+        "fix_factorial",
+        TestOptions::Compile(Some((Type::Number, 24))),
+    );
+}
+
+#[test]
+#[should_panic]
+fn fix_non_function_fail() {
+    test("fix_non_function_fail", TestOptions::TypeCheck(None));
+}
+
 #[test]
 fn function_if_argument() {
     test(
@@ -304,11 +376,30 @@ fn if_test() {
 }
 
 #[test]
-#[should_panic] // TODO: BUG: implement lambdas
 fn is_even() {
     test("is_even", TestOptions::Compile(Some((Type::Boolean, 0))));
 }
 
+#[test]
+fn is_even_million() {
+    test(
+        "is_even_million",
+        TestOptions::Compile(Some((Type::Boolean, 1))),
+    );
+}
+
+#[test]
+#[should_panic]
+fn match_non_exhaustive() {
+    test("match_non_exhaustive", TestOptions::TypeCheck(None));
+}
+
+#[test]
+#[should_panic]
+fn match_redundant() {
+    test("match_redundant", TestOptions::TypeCheck(None));
+}
+
 #[test]
 fn medium() {
     test(
@@ -335,6 +426,14 @@ fn nested_function() {
     );
 }
 
+#[test]
+fn mutual_recursion() {
+    test(
+        "mutual_recursion",
+        TestOptions::Compile(Some((Type::Boolean, 0))),
+    );
+}
+
 #[test]
 fn number_literal() {
     test(
@@ -362,6 +461,28 @@ fn rec_c_fail() {
     test("rec_c_fail", TestOptions::TypeCheck(None));
 }
 
+#[test]
+#[should_panic]
+fn reference_equals_fail() {
+    test("reference_equals_fail", TestOptions::TypeCheck(None));
+}
+
+#[test]
+fn reference_physical_equals() {
+    test(
+        "reference_physical_equals",
+        TestOptions::Compile(Some((Type::Boolean, 1))),
+    );
+}
+
+#[test]
+fn reference_set() {
+    test(
+        "reference_set",
+        TestOptions::Compile(Some((Type::Number, 42))),
+    );
+}
+
 #[test]
 fn summation() {
     test(
@@ -370,11 +491,79 @@ fn summation() {
     );
 }
 
+#[test]
+fn summation_million() {
+    test(
+        "summation_million",
+        TestOptions::Interpret(Some((Type::Number, Data::Number(500_000_500_000)))),
+    );
+}
+
+#[test]
+fn strategy_termination() {
+    test(
+        "strategy_termination",
+        TestOptions::TypeCheck(Some(Type::Number)),
+    );
+
+    let code = test_read("strategy_termination");
+    let budget = Budget {
+        max_steps: Some(100_000),
+        ..Budget::default()
+    };
+    assert_eq!(
+        lamb::interpret_with_strategy(&code, Strategy::ByValue, &budget)
+            .unwrap_err()
+            .limit,
+        Limit::Steps
+    );
+    assert_eq!(
+        lamb::interpret_with_strategy(&code, Strategy::ByName, &budget),
+        Ok(Data::Number(1))
+    );
+    assert_eq!(
+        lamb::interpret_with_strategy(&code, Strategy::ByNeed, &budget),
+        Ok(Data::Number(1))
+    );
+}
+
 #[test]
 fn super_test() {
     test("super", TestOptions::Compile(Some((Type::Boolean, 0))));
 }
 
+#[test]
+fn syntax_errors() {
+    let (ast, errors) = lamb::parse_recovering(&test_read("syntax_errors"));
+    assert!(matches!(ast, AST::RecursiveFunction(_)));
+    let errors: Vec<String> = errors.iter().map(ToString::to_string).collect();
+    assert_eq!(
+        errors,
+        vec![
+            "7:18: Expected ',', found 'numC'",
+            "9:5: Unknown keyword 'plusX'",
+            "11:22: Expected an expression, found 'numT'",
+            "13:1: Expected the end of the input, found 'numC'",
+        ]
+    );
+}
+
+#[test]
+fn tree_sum() {
+    test("tree_sum", TestOptions::Compile(Some((Type::Number, 10))));
+}
+
+#[test]
+fn tree_sum_surface() {
+    let code = read::build("tests/inputs/tree_sum.lml");
+    let ast = lamb::desugar(&code, Syntax::Ml);
+    assert_eq!(lamb::interpret::interpret(&ast), Data::Number(10));
+    for syntax in [Syntax::Core, Syntax::Ml, Syntax::Sexp] {
+        let converted = lamb::convert(&code, Syntax::Ml, syntax);
+        assert_eq!(lamb::desugar(&converted, syntax), ast);
+    }
+}
+
 #[test]
 fn true_literal() {
     test(
@@ -383,6 +572,38 @@ fn true_literal() {
     );
 }
 
+#[test]
+fn tail_call_million() {
+    test(
+        "tail_call_million",
+        TestOptions::Compile(Some((Type::Boolean, 1))),
+    );
+
+    // Every call is in tail position, so the interpreter's continuation stays shallow.
+    let budget = Budget {
+        max_depth: Some(16),
+        ..Budget::default()
+    };
+    assert_eq!(
+        lamb::interpret_with_budget(&test_read("tail_call_million"), &budget),
+        Ok(Data::Boolean(true))
+    );
+}
+
+#[test]
+fn type_alias_factorial() {
+    test(
+        "type_alias_factorial",
+        TestOptions::Compile(Some((Type::Number, 24))),
+    );
+}
+
+#[test]
+#[should_panic]
+fn type_alias_out_of_scope_fail() {
+    test("type_alias_out_of_scope_fail", TestOptions::TypeCheck(None));
+}
+
 #[test]
 fn unbound_identifier() {
     test("true_literal", TestOptions::TypeCheck(None));